@@ -0,0 +1,181 @@
+use std::env;
+
+use tokio::sync::broadcast::{
+    self,
+    error::{RecvError, TryRecvError},
+};
+
+use crate::messages::PlatformEvent;
+
+static DEFAULT_CAPACITY: usize = 1024;
+
+/// Decides which events a subscriber gets.
+pub type EventFilter = Box<dyn Fn(&PlatformEvent) -> bool + Send>;
+
+pub fn all_events() -> EventFilter {
+    Box::new(|_| true)
+}
+
+pub fn chat_only() -> EventFilter {
+    Box::new(|event| matches!(event, PlatformEvent::Chat(_)))
+}
+
+/// Publish/subscribe bus every connector publishes its events to. Each subscriber has its own
+/// bounded buffer: a slow one misses the oldest events (and is told so) instead of slowing down
+/// the rest.
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<PlatformEvent>,
+}
+
+impl EventBus {
+    /// `capacity` must be at least 1.
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(capacity(env::var("EVENT_BUS_CAPACITY").ok().as_deref()))
+    }
+
+    pub fn publish(&self, event: PlatformEvent) {
+        // Only fails when nobody is subscribed, the event is not needed then
+        let _ = self.sender.send(event);
+    }
+
+    /// Events published before subscribing are not received.
+    pub fn subscribe(&self, name: &'static str, filter: EventFilter) -> Subscription {
+        Subscription {
+            name,
+            receiver: self.sender.subscribe(),
+            filter,
+        }
+    }
+}
+
+fn capacity(value: Option<&str>) -> usize {
+    let Some(value) = value.map(str::trim).filter(|value| !value.is_empty()) else {
+        return DEFAULT_CAPACITY;
+    };
+    match value.parse::<usize>() {
+        Ok(capacity) if capacity > 0 => capacity,
+        _ => {
+            eprintln!("WARN - EVENT_BUS_CAPACITY must be a number greater than 0, using {DEFAULT_CAPACITY}");
+            DEFAULT_CAPACITY
+        }
+    }
+}
+
+pub enum Received {
+    Event(PlatformEvent),
+    /// The subscriber fell behind and this many events were dropped for it
+    Lagged(u64),
+}
+
+pub struct Subscription {
+    name: &'static str,
+    receiver: broadcast::Receiver<PlatformEvent>,
+    filter: EventFilter,
+}
+
+impl Subscription {
+    /// Next event that passes the filter, `None` if there is none yet or the bus is gone.
+    pub fn try_recv(&mut self) -> Option<Received> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(event) if (self.filter)(&event) => return Some(Received::Event(event)),
+                Ok(_) => {}
+                Err(TryRecvError::Lagged(missed)) => return Some(self.lagged(missed)),
+                Err(TryRecvError::Empty | TryRecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Blocks until the next event that passes the filter, `None` once the bus is gone. Not to be
+    /// called from async code.
+    pub fn recv(&mut self) -> Option<Received> {
+        loop {
+            match self.receiver.blocking_recv() {
+                Ok(event) if (self.filter)(&event) => return Some(Received::Event(event)),
+                Ok(_) => {}
+                Err(RecvError::Lagged(missed)) => return Some(self.lagged(missed)),
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    fn lagged(&self, missed: u64) -> Received {
+        eprintln!("WARN - Event bus subscriber {} fell behind and missed {missed} events", self.name);
+        Received::Lagged(missed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        connector::ConnectorStatus,
+        messages::{Platform, PlatformMessage},
+    };
+
+    fn chat(msg: &str) -> PlatformEvent {
+        PlatformMessage::new(Platform::TWITCH, "viewer".to_string(), msg.to_string()).into()
+    }
+
+    fn connected() -> PlatformEvent {
+        PlatformEvent::ConnectionState {
+            platform: Platform::TWITCH,
+            status: ConnectorStatus::Connected,
+        }
+    }
+
+    fn received_chat(received: Option<Received>) -> Option<String> {
+        match received {
+            Some(Received::Event(PlatformEvent::Chat(message))) => Some(message.msg),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn capacity_is_never_zero() {
+        assert_eq!(capacity(None), DEFAULT_CAPACITY);
+        assert_eq!(capacity(Some("")), DEFAULT_CAPACITY);
+        assert_eq!(capacity(Some("0")), DEFAULT_CAPACITY);
+        assert_eq!(capacity(Some("-5")), DEFAULT_CAPACITY);
+        assert_eq!(capacity(Some(" 16 ")), 16);
+    }
+
+    #[test]
+    fn subscribers_only_get_what_passes_their_filter() {
+        let bus = EventBus::new(16);
+        let mut chat_subscription = bus.subscribe("chat", chat_only());
+        let mut all = bus.subscribe("all", all_events());
+        bus.publish(connected());
+        bus.publish(chat("hi"));
+
+        assert_eq!(received_chat(chat_subscription.try_recv()).as_deref(), Some("hi"));
+        assert!(chat_subscription.try_recv().is_none());
+        assert!(matches!(
+            all.try_recv(),
+            Some(Received::Event(PlatformEvent::ConnectionState { .. }))
+        ));
+        assert_eq!(received_chat(all.try_recv()).as_deref(), Some("hi"));
+    }
+
+    #[test]
+    fn slow_subscribers_are_told_what_they_missed() {
+        let bus = EventBus::new(2);
+        let mut slow = bus.subscribe("slow", chat_only());
+        bus.publish(connected());
+        for msg in ["a", "b", "c"] {
+            bus.publish(chat(msg));
+        }
+
+        assert!(matches!(slow.try_recv(), Some(Received::Lagged(2))));
+        assert_eq!(received_chat(slow.try_recv()).as_deref(), Some("b"));
+        assert_eq!(received_chat(slow.recv()).as_deref(), Some("c"));
+        drop(bus);
+        assert!(slow.recv().is_none());
+    }
+}
@@ -0,0 +1,44 @@
+use std::{
+    env,
+    fs::{File, OpenOptions},
+    io::Write,
+    thread,
+};
+
+use anyhow::Result;
+
+use crate::{
+    bus::{all_events, chat_only, EventBus, Received},
+    messages::PlatformEvent,
+};
+
+/// Appends events to the file at `CHAT_LOG_PATH`, one JSON document per line. Only chat messages
+/// if `CHAT_LOG_EVENTS` is `chat`. Does nothing if `CHAT_LOG_PATH` is not set.
+pub fn start(bus: &EventBus) -> Result<()> {
+    let Ok(path) = env::var("CHAT_LOG_PATH") else {
+        return Ok(());
+    };
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    let filter = match env::var("CHAT_LOG_EVENTS").as_deref() {
+        Ok("chat") => chat_only(),
+        _ => all_events(),
+    };
+    let mut subscription = bus.subscribe("chat log", filter);
+
+    thread::spawn(move || {
+        while let Some(received) = subscription.recv() {
+            let Received::Event(event) = received else {
+                continue;
+            };
+            if let Err(err) = write_event(&mut file, &event) {
+                eprintln!("ERROR - Cannot write to chat log {path}: {:?}", err);
+            }
+        }
+    });
+    Ok(())
+}
+
+fn write_event(file: &mut File, event: &PlatformEvent) -> Result<()> {
+    writeln!(file, "{}", serde_json::to_string(event)?)?;
+    Ok(())
+}
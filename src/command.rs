@@ -1,19 +1,35 @@
 use std::{
-    collections::HashSet,
-    fmt,
-    fs::{File, OpenOptions},
-    io::{Read, Write},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    env, fmt, mem,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
 };
 
 use anyhow::Result;
+use chrono::{DateTime, Utc};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
-use serde_json::{from_str, from_value, Map, Value};
+
+use crate::{
+    commands_file::Backup,
+    cooldown::{Cooldown, Cooldowns, SystemClock},
+    help,
+    messages::{ChatTarget, Platform, PlatformMessage},
+    permission::{PermissionLevel, Permissions},
+    store::CommandStore,
+    template::{Template, TemplateContext},
+    trigger::Trigger,
+};
 
 type HandleCommandResult<T> = std::result::Result<T, HandleCommandError>;
 
 pub static COMMAND_SYMBOL: char = '!';
 pub static CREATE_COMMAND_SYMBOL: char = '#';
 
+/// How often the store is checked for commands changed outside the bot
+static RELOAD_INTERVAL: Duration = Duration::from_secs(2);
+
 #[derive(Debug)]
 pub enum HandleCommandError {
     MissingCommand(String),
@@ -45,192 +61,472 @@ impl fmt::Display for CreateCommandError {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// A command as stored in the commands file. New fields need `#[serde(default)]` so files written
+/// before them keep loading.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct BotCommand {
     pub name: String,
     pub contents: String,
+    /// Shown with `{count}`, changed with `!command +`, `-`, `= N` and `reset`
+    #[serde(default)]
+    pub count: i64,
+    #[serde(default)]
+    pub cooldown: Cooldown,
+    /// Lowest level allowed to use it
+    #[serde(default)]
+    pub permission: PermissionLevel,
+    /// Other names that answer the same, like `dc` for `discord`
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// Chat messages that get the command response without using the command
+    #[serde(default)]
+    pub triggers: Vec<Trigger>,
+    /// What `!help <command>` tells about it
+    #[serde(default)]
+    pub description: String,
+    /// Platforms, or channels of them, where it works. Everywhere if empty
+    #[serde(default)]
+    pub scope: Vec<ChatTarget>,
+    /// Response on a platform instead of `contents`, by platform id
+    #[serde(default)]
+    pub overrides: BTreeMap<String, String>,
 }
 
-impl Into<BotCommand> for &Map<String, Value> {
-    fn into(self) -> BotCommand {
-        BotCommand {
-            name: self
-                .get("name")
-                .expect("Command to have name property")
-                .as_str()
-                .unwrap()
-                .to_string(),
-            contents: self
-                .get("contents")
-                .expect("Command to have name property")
-                .as_str()
-                .unwrap()
-                .to_string(),
+impl BotCommand {
+    /// Checks what the command needs to work, not how it relates to other commands.
+    pub fn validate(&self) -> Result<()> {
+        // TODO: handle whitespace-only name
+        if self.name.is_empty() {
+            return Err(anyhow::Error::msg("Command name cannot be empty"));
+        }
+        if self.name.find(' ').is_some() {
+            return Err(anyhow::Error::msg("Command name cannot contain spaces"));
+        }
+        if let Err(err) = self.triggers.iter().try_for_each(Trigger::validate) {
+            return Err(anyhow::Error::msg(format!("Command {} has an invalid trigger: {err}", self.name)));
         }
+        if let Err(err) = Template::parse(&self.contents) {
+            return Err(anyhow::Error::msg(format!("Command {} has an invalid template: {err}", self.name)));
+        }
+        for (platform, contents) in &self.overrides {
+            if let Err(err) = Template::parse(contents) {
+                return Err(anyhow::Error::msg(format!(
+                    "Command {} has an invalid template for {platform}: {err}",
+                    self.name
+                )));
+            }
+        }
+        if self.scope.iter().any(|target| target.platform.id().is_empty()) {
+            return Err(anyhow::Error::msg(format!("Command {} is scoped to an empty platform", self.name)));
+        }
+        Ok(())
+    }
+
+    /// Whether it works where the message was sent.
+    pub fn available_in(&self, message: &PlatformMessage) -> bool {
+        self.scope.is_empty() || self.scope.iter().any(|target| target.matches(message))
+    }
+
+    /// What it answers on `platform`.
+    pub fn response_for(&self, platform: &Platform) -> &str {
+        self.overrides.get(platform.id()).unwrap_or(&self.contents)
     }
 }
 
-static COMMANDS_FILE: &str = "commands.json";
+/// Commands changing in the store without going through the `CommandHandler`, for the GUI to tell.
+#[derive(Debug, Clone)]
+pub enum CommandsNotice {
+    /// Names of the commands added, changed or removed
+    Reloaded(Vec<String>),
+    /// The changed commands can't be loaded, the previous ones are kept
+    Invalid(String),
+}
 
-fn is_sender_allowed(sender: &str) -> bool {
-    return sender.eq("zartisimo");
+/// Where a command was sent from, what templates get their values from.
+pub struct CommandContext<'a> {
+    pub message: &'a PlatformMessage,
+    /// When the stream went live, only looked up by templates using `{uptime}`
+    pub live_since: &'a dyn Fn() -> Option<DateTime<Utc>>,
 }
 
-pub struct CommandHandler {
-    contents: Value,
-    command_names: HashSet<String>,
+fn not_allowed(sender: &str) -> HandleCommandResult<Option<String>> {
+    Ok(Some(format!("I'm sorry {}. You are not allowed to execute this command.", sender)))
 }
 
-fn read_file() -> Value {
-    let mut file = File::open(COMMANDS_FILE).expect("Commands file to exist");
-    let mut contents = String::new();
-    let _bytes = file.read_to_string(&mut contents).expect("The file to be UTF-8");
-    return from_str(&contents).expect("The file to be a valid JSON");
+pub struct CommandHandler {
+    store: Box<dyn CommandStore>,
+    /// Same as in the store, to not ask it on every message
+    commands: BTreeMap<String, BotCommand>,
+    cooldowns: Cooldowns,
+    permissions: Permissions,
+    /// `!Help` finds `help` unless COMMANDS_CASE_SENSITIVE=true
+    case_insensitive: bool,
+    /// Compiled trigger regexes by pattern
+    regexes: HashMap<String, Regex>,
+    notices: Vec<CommandsNotice>,
 }
 
 impl CommandHandler {
-    pub fn new() -> Self {
-        let contents = read_file();
-        let commands = contents.as_object().unwrap();
-        let command_names = commands.keys().map(|key| key.to_string()).collect();
-        Self {
-            contents,
-            command_names,
-        }
+    pub fn new(mut store: Box<dyn CommandStore>) -> Result<Self> {
+        let commands = store.load()?.into_iter().map(|command| (command.name.clone(), command)).collect();
+        Ok(Self {
+            store,
+            commands,
+            cooldowns: Cooldowns::new(Box::new(SystemClock)),
+            permissions: Permissions::from_env(),
+            case_insensitive: env::var("COMMANDS_CASE_SENSITIVE").map_or(true, |value| value != "true"),
+            regexes: HashMap::new(),
+            notices: Vec::new(),
+        })
     }
 
     pub fn get_command(&self, command_name: &String) -> BotCommand {
-        from_value(self.contents.get(command_name).expect("Command to exist").to_owned())
-            .expect("Command in file to be valid BotCommand")
         // For now let's assume the command always exists
-        //from_str(self.contents.get(command_name).unwrap().as_object().unwrap()).expect("Command from file to be valid BotCommand")
+        self.commands.get(command_name).expect("Command to exist").clone()
     }
 
-    pub fn get_command_names(&mut self) -> HashSet<String> {
-        if !self.command_names.contains("help") {
-            self.generate_help_command();
-        }
-        self.command_names.clone()
+    /// Every command, by name.
+    pub fn commands(&self) -> impl Iterator<Item = &BotCommand> {
+        self.commands.values()
     }
 
-    pub fn generate_help_command(&mut self) {
-        let mut help_contents = String::from("Available commands:");
-        for command_name in &self.command_names {
-            help_contents = format!("{help_contents} {COMMAND_SYMBOL}{command_name}");
-        }
+    pub fn get_command_names(&self) -> HashSet<String> {
+        self.commands.keys().cloned().collect()
+    }
 
-        let help_command = BotCommand {
-            name: "help".to_string(),
-            contents: help_contents,
-        };
+    fn same_name(&self, a: &str, b: &str) -> bool {
+        a == b || (self.case_insensitive && a.to_lowercase() == b.to_lowercase())
+    }
 
-        self.create_command(&help_command).expect("To create the help command");
-        self.command_names.insert(help_command.name);
-        self.write_file();
+    /// Name of the command `name` refers to, be it its name or one of its aliases.
+    pub fn resolve(&self, name: &str) -> Option<String> {
+        if self.commands.contains_key(name) {
+            return Some(name.to_string());
+        }
+        self.commands
+            .values()
+            .find(|command| self.same_name(&command.name, name) || command.aliases.iter().any(|alias| self.same_name(alias, name)))
+            .map(|command| command.name.clone())
     }
 
-    pub fn update_command_content(&mut self, command_name: &String, new_contents: &String) -> Result<()> {
-        match self.contents.get_mut(&command_name) {
-            Some(contents) => {
-                contents["contents"] = Value::String(new_contents.to_string());
-                self.write_file();
-                Ok(())
+    /// Checks that none of the aliases is already the name or alias of another command.
+    fn check_aliases(&self, command: &BotCommand) -> Result<()> {
+        for alias in &command.aliases {
+            if alias.is_empty() || alias.contains(' ') {
+                return Err(anyhow::Error::msg(format!("Alias \"{alias}\" cannot be empty or contain spaces")));
             }
-            None => Err(anyhow::Error::msg("Command does not exist")),
+            match self.resolve(alias) {
+                Some(owner) if owner != command.name => {
+                    return Err(anyhow::Error::msg(format!("{alias} is already used by command {owner}")));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces everything but the name of an existing command.
+    pub fn update_command(&mut self, command: &BotCommand) -> Result<()> {
+        command.validate()?;
+        self.check_aliases(command)?;
+        if !self.commands.contains_key(&command.name) {
+            return Err(anyhow::Error::msg("Command does not exist"));
         }
+        self.save(command.clone())
     }
 
     pub fn delete_command(&mut self, command_name: &String) -> Result<()> {
-        if !self.command_names.contains(command_name) {
+        if !self.commands.contains_key(command_name) {
             return Err(anyhow::Error::msg(format!("Command {} does not exist", command_name)));
         }
-
-        let mut new_contents: Map<String, Value> = Map::new();
-
-        for name in &self.command_names {
-            if *name != *command_name {
-                let command = &self.get_command(&name);
-                new_contents.insert(
-                    name.to_string(), // can this be achieved without copying?
-                    serde_json::from_str(
-                        serde_json::to_string(&command)
-                            .expect("command to be valid BotCommand")
-                            .as_str(),
-                    )
-                    .expect("stringified BotCommand to be valid Json"),
-                );
-            }
+        if let Err(err) = self.store.delete_command(command_name) {
+            eprintln!("ERROR - Cannot delete command {command_name}: {:?}", err);
+            return Err(anyhow::Error::msg(format!("Cannot save commands: {err}")));
         }
-
-        self.contents = Value::Object(new_contents);
-        self.command_names.remove(command_name);
-        self.write_file();
-
+        self.commands.remove(command_name);
         Ok(())
     }
 
     pub fn create_command(&mut self, command: &BotCommand) -> Result<()> {
-        // TODO: handle whitespace-only name and contents
-        if command.name.is_empty() {
-            return Err(anyhow::Error::msg("Command name cannot be empty"));
-        }
+        self.check_new(command)?;
+        self.save(command.clone())
+    }
 
-        if command.name.find(' ').is_some() {
-            return Err(anyhow::Error::msg("Command name cannot contain spaces"));
+    /// Creates all the commands that can be created, saving them at once. Returns the others, with
+    /// why they couldn't be.
+    pub fn create_commands(&mut self, commands: Vec<BotCommand>) -> Result<Vec<(String, anyhow::Error)>> {
+        let previous = self.commands.clone();
+        let mut created = Vec::new();
+        let mut rejected = Vec::new();
+        for command in commands {
+            // Checked against the ones created before it too
+            match self.check_new(&command) {
+                Ok(_) => {
+                    self.commands.insert(command.name.clone(), command.clone());
+                    created.push(command);
+                }
+                Err(err) => rejected.push((command.name, err)),
+            }
+        }
+        if let Err(err) = self.store.save_commands(&created) {
+            eprintln!("ERROR - Cannot save {} new commands: {:?}", created.len(), err);
+            self.commands = previous;
+            return Err(anyhow::Error::msg(format!("Cannot save commands: {err}")));
         }
+        Ok(rejected)
+    }
+
+    fn check_new(&self, command: &BotCommand) -> Result<()> {
+        // TODO: handle whitespace-only contents
+        command.validate()?;
 
         if command.contents.is_empty() {
             return Err(anyhow::Error::msg("Command contents cannot be empty"));
         }
 
-        if self.command_names.contains(&command.name) {
-            return Err(anyhow::Error::msg(format!("Command {} alredy exists", command.name)));
+        if let Some(existing) = self.resolve(&command.name) {
+            return Err(anyhow::Error::msg(format!("Command {} alredy exists", existing)));
         }
 
-        self.contents[&command.name] = serde_json::from_str(
-            serde_json::to_string(command)
-                .expect("command to be valid BotCommand")
-                .as_str(),
-        )
-        .expect("stringified BotCommand to be valid Json");
-        self.command_names.insert(command.name.clone());
+        self.check_aliases(command)
+    }
 
-        self.write_file();
+    /// Saves the command in the store and, only if that works, keeps it.
+    fn save(&mut self, command: BotCommand) -> Result<()> {
+        if let Err(err) = self.store.save_command(&command) {
+            eprintln!("ERROR - Cannot save command {}: {:?}", command.name, err);
+            return Err(anyhow::Error::msg(format!("Cannot save commands: {err}")));
+        }
+        self.commands.insert(command.name.clone(), command);
+        Ok(())
+    }
 
+    /// Backups of the commands, the most recent first.
+    pub fn backups(&self) -> Vec<Backup> {
+        self.store.backups()
+    }
+
+    /// Replaces every command with the ones in the backup.
+    pub fn restore_backup(&mut self, backup: &Backup) -> Result<()> {
+        let commands = self.store.restore(backup)?;
+        self.commands = commands.into_iter().map(|command| (command.name.clone(), command)).collect();
         Ok(())
     }
 
-    fn write_file(&self) {
-        let mut file = OpenOptions::new()
-            .truncate(true)
-            .write(true)
-            .create(true)
-            .open(COMMANDS_FILE)
-            .expect("To open or create commands file");
-        match file.write_all(self.contents.to_string().as_bytes()) {
-            Err(err) => eprintln!("WARN - Cannot write commands file: {:?}", err),
-            _ => {}
+    /// Picks up the commands changed in the store from outside the bot, like `commands.json` edited
+    /// by hand.
+    pub fn reload(&mut self) {
+        let commands: BTreeMap<String, BotCommand> = match self.store.reload() {
+            Ok(Some(commands)) => commands.into_iter().map(|command| (command.name.clone(), command)).collect(),
+            Ok(None) => return,
+            Err(err) => {
+                eprintln!(
+                    "WARN - Commands changed outside the bot can't be loaded, keeping the current ones: {:?}",
+                    err
+                );
+                self.notices.push(CommandsNotice::Invalid(err.to_string()));
+                return;
+            }
         };
+        let changed: Vec<String> = self
+            .commands
+            .keys()
+            .chain(commands.keys())
+            .filter(|name| self.commands.get(*name) != commands.get(*name))
+            .cloned()
+            .collect::<BTreeSet<String>>()
+            .into_iter()
+            .collect();
+        self.commands = commands;
+        if !changed.is_empty() {
+            eprintln!("INFO - Reloaded commands changed outside the bot: {}", changed.join(", "));
+            self.notices.push(CommandsNotice::Reloaded(changed));
+        }
     }
 
-    pub fn handle_command(&mut self, sender: String, msg: String) -> HandleCommandResult<Option<String>> {
+    /// Notices since the last time they were taken.
+    pub fn take_notices(&mut self) -> Vec<CommandsNotice> {
+        mem::take(&mut self.notices)
+    }
+
+    pub fn handle_command(&mut self, context: &CommandContext, msg: String) -> HandleCommandResult<Option<String>> {
         // !today
         // !settoday args
+        // !edit today args
+        // !delete today
+        // !alias dc discord
+        // !quote 3
+        // !addquote something someone said
+        // !help, !help 2, !help today
+        let sender = context.message.sender.clone();
+        let level = self.permissions.level(context.message);
+        let is_admin = level >= self.permissions.admin_verbs;
         let mut iter = msg.split(" ");
         let command = iter.next().expect("Message to not be empty");
+        // Built-in commands follow the same case rule as the others
+        let builtin = match self.case_insensitive {
+            true => command.to_lowercase(),
+            false => command.to_string(),
+        };
+        let builtin = builtin.as_str();
+        // !settoday edits today, but !setup is just a command (or a missing one)
+        let set_target = command
+            .get(..3)
+            .filter(|prefix| match self.case_insensitive {
+                true => prefix.eq_ignore_ascii_case("set"),
+                false => *prefix == "set",
+            })
+            .map(|_| &command[3..])
+            .filter(|name| self.resolve(command).is_none() && self.resolve(name).is_some());
+
+        let is_verb = builtin == "edit"
+            || builtin == "delete"
+            || builtin == "alias"
+            || (builtin == "addquote" && self.resolve(command).is_none())
+            || set_target.is_some()
+            || command.starts_with(CREATE_COMMAND_SYMBOL);
+        if is_verb && !is_admin {
+            return not_allowed(&sender);
+        }
+
+        if builtin == "edit" {
+            let command_name = iter.next().unwrap_or_default().to_string();
+            return self.handle_set_command(command_name, iter.collect::<Vec<&str>>().join(" "));
+        }
+
+        if builtin == "addquote" && self.resolve(command).is_none() {
+            let quote = iter.collect::<Vec<&str>>().join(" ");
+            if quote.trim().is_empty() {
+                return Ok(Some(format!("Usage: {COMMAND_SYMBOL}addquote <quote>")));
+            }
+            return match self.store.add_quote(quote.trim(), &sender) {
+                Ok(id) => Ok(Some(format!("Added quote #{id}"))),
+                Err(err) => Ok(Some(format!("Cannot add the quote: {err}"))),
+            };
+        }
+
+        if builtin == "quote" && self.resolve(command).is_none() {
+            let id = iter.next().and_then(|id| id.trim_start_matches('#').parse().ok());
+            return match self.store.quote(id) {
+                Ok(Some(quote)) => Ok(Some(quote.to_string())),
+                Ok(None) => Ok(Some("No quote found".to_string())),
+                Err(err) => Ok(Some(format!("Cannot get a quote: {err}"))),
+            };
+        }
+
+        if builtin == "help" && self.resolve(command).is_none() {
+            return Ok(Some(self.handle_help_command(context, level, iter.next().unwrap_or_default())));
+        }
 
-        if command.starts_with("set") {
-            return self.handle_set_command(sender, command[3..].to_string(), iter.collect::<Vec<&str>>().join(" "));
+        if builtin == "alias" {
+            return self.handle_alias_command(iter.filter(|arg| !arg.is_empty()).collect());
         }
 
-        if command.starts_with(CREATE_COMMAND_SYMBOL) {
-            return self.handle_create_command(sender, command[1..].to_string(), iter.collect::<Vec<&str>>().join(" "));
+        if builtin == "delete" {
+            let command_name = iter.next().unwrap_or_default().to_string();
+            let Some(command_name) = self.resolve(&command_name) else {
+                return Err(HandleCommandError::MissingCommand(command_name));
+            };
+            return match self.delete_command(&command_name) {
+                Ok(_) => Ok(Some(format!("Deleted {} command", command_name))),
+                Err(err) => Ok(Some(format!("Cannot delete {command_name} command: {err}"))),
+            };
         }
 
-        match self.contents.get(command) {
-            Some(command) => Ok(Some(command["contents"].as_str().unwrap().to_string())),
-            None => Err(HandleCommandError::MissingCommand(command.to_string())),
+        if let Some(command_name) = set_target {
+            return self.handle_set_command(command_name.to_string(), iter.collect::<Vec<&str>>().join(" "));
         }
+
+        if let Some(command_name) = command.strip_prefix(CREATE_COMMAND_SYMBOL) {
+            return self.handle_create_command(command_name.to_string(), iter.collect::<Vec<&str>>().join(" "));
+        }
+
+        let Some(command) = self.resolve(command) else {
+            return Err(HandleCommandError::MissingCommand(command.to_string()));
+        };
+        let command = command.as_str();
+        // Commands scoped somewhere else don't exist here
+        let Some(found) = self.commands.get(command).filter(|found| found.available_in(context.message)) else {
+            return Err(HandleCommandError::MissingCommand(command.to_string()));
+        };
+        let contents = found.response_for(&context.message.platform).to_string();
+        let BotCommand {
+            mut count,
+            cooldown,
+            permission,
+            ..
+        } = found.clone();
+
+        if level < permission {
+            return not_allowed(&sender);
+        }
+
+        // Moderators and above are not affected by cooldowns
+        if level < PermissionLevel::Moderator {
+            let user = format!("{}:{}", context.message.platform.id(), context.message.moderation_id());
+            if let Some(on_cooldown) = self.cooldowns.check(command, &user, &cooldown) {
+                // Telling them on every try would be the spam the cooldown is there to stop
+                return match cooldown.notify && on_cooldown.first {
+                    true => Ok(Some(format!(
+                        "{COMMAND_SYMBOL}{command} is on cooldown for {}s",
+                        on_cooldown.remaining.as_secs_f32().ceil()
+                    ))),
+                    false => Ok(None),
+                };
+            }
+        }
+
+        let args: Vec<&str> = iter.filter(|arg| !arg.is_empty()).collect();
+        // Only commands showing their counter have one, for the rest `+` or `reset` are arguments
+        let has_counter = Template::parse(&contents).is_ok_and(|template| template.uses_count());
+        if let Some(new_count) = counter_change(count, &args).filter(|_| has_counter) {
+            if !is_admin {
+                return Ok(Some(format!(
+                    "I'm sorry {}. You are not allowed to change the {command} counter.",
+                    sender
+                )));
+            }
+            count = new_count;
+            let mut updated = self.get_command(&command.to_string());
+            updated.count = count;
+            if let Err(err) = self.save(updated) {
+                return Ok(Some(format!("Cannot change the {command} counter: {err}")));
+            }
+        }
+        Ok(Some(render(context, command, contents, &args, count)))
+    }
+
+    /// Response of the first command with a trigger the message fires, if any. Triggers that are
+    /// not allowed for the sender or on cooldown are ignored silently.
+    pub fn handle_triggers(&mut self, context: &CommandContext) -> Option<String> {
+        let message = context.message;
+        let level = self.permissions.level(message);
+        let commands: Vec<BotCommand> = self
+            .commands
+            .values()
+            .filter(|command| !command.triggers.is_empty() && command.available_in(message))
+            .cloned()
+            .collect();
+
+        for command in commands {
+            let fired = command
+                .triggers
+                .iter()
+                .filter(|trigger| !(trigger.ignore_bot && message.is_bot))
+                .any(|trigger| trigger.is_match(&message.msg, &mut self.regexes));
+            if !fired || level < command.permission {
+                continue;
+            }
+            if level < PermissionLevel::Moderator {
+                let user = format!("{}:{}", message.platform.id(), message.moderation_id());
+                if self.cooldowns.check(&command.name, &user, &command.cooldown).is_some() {
+                    return None;
+                }
+            }
+            let contents = command.response_for(&message.platform).to_string();
+            return Some(render(context, &command.name, contents, &[], command.count));
+        }
+        None
     }
 
     /// This method expects the msg to be `command <args>`, where <args> represents the rest of the
@@ -243,67 +539,285 @@ impl CommandHandler {
     /// ```
     /// self.handle_set_command("today", "Today we will build bla bla bla".to_string())
     /// ```
-    fn handle_set_command(
-        &mut self,
-        sender: String,
-        command_name: String,
-        new_contents: String,
-    ) -> HandleCommandResult<Option<String>> {
-        if !is_sender_allowed(&sender) {
-            return Ok(Some(format!(
-                "I'm sorry {}. You are not allowed to execute this command.",
-                sender
-            )));
-        }
-
-        match self.contents.get_mut(&command_name) {
-            Some(contents) => {
-                contents["contents"] = Value::String(new_contents);
-                self.write_file();
-                Ok(Some(format!("Updated {} command", command_name)))
-            }
-            None => Err(HandleCommandError::MissingCommand(command_name.to_string())),
+    fn handle_set_command(&mut self, command_name: String, new_contents: String) -> HandleCommandResult<Option<String>> {
+        let command_name = self.resolve(&command_name).unwrap_or(command_name);
+        if let Err(err) = Template::parse(&new_contents) {
+            return Ok(Some(format!("Cannot update {command_name} command: {err}")));
         }
-    }
 
-    fn handle_create_command(
-        &mut self,
-        sender: String,
-        command_name: String,
-        new_contents: String,
-    ) -> Result<Option<String>, HandleCommandError> {
-        if !is_sender_allowed(&sender) {
-            return Ok(Some(format!(
-                "I'm sorry {}. You are not allowed to execute this command.",
-                sender
-            )));
+        let Some(mut command) = self.commands.get(&command_name).cloned() else {
+            return Err(HandleCommandError::MissingCommand(command_name.to_string()));
+        };
+        command.contents = new_contents;
+        match self.save(command) {
+            Ok(_) => Ok(Some(format!("Updated {} command", command_name))),
+            Err(err) => Ok(Some(format!("Cannot update {command_name} command: {err}"))),
         }
+    }
 
-        if self.contents.get(&command_name).is_some() {
+    fn handle_create_command(&mut self, command_name: String, new_contents: String) -> Result<Option<String>, HandleCommandError> {
+        if self.resolve(&command_name).is_some() {
             let msg = format!("ERROR - Command {command_name} already exists");
-            return Err(HandleCommandError::CreateCommand(CreateCommandError {
-                name: command_name,
-                msg,
-            }));
+            return Err(HandleCommandError::CreateCommand(CreateCommandError { name: command_name, msg }));
         }
 
-        if new_contents.eq("") {
+        if new_contents.is_empty() {
             let msg = format!("ERROR - No content for new command {command_name}");
-            return Err(HandleCommandError::CreateCommand(CreateCommandError {
-                name: command_name,
-                msg,
-            }));
+            return Err(HandleCommandError::CreateCommand(CreateCommandError { name: command_name, msg }));
+        }
+
+        if let Err(err) = Template::parse(&new_contents) {
+            return Ok(Some(format!("Cannot create {command_name} command: {err}")));
         }
 
         let new_command = BotCommand {
             name: command_name,
             contents: new_contents,
+            ..Default::default()
         };
 
-        self.create_command(&new_command)
-            .expect("To be able to create the command");
-        self.write_file();
+        match self.create_command(&new_command) {
+            Ok(_) => Ok(Some(format!("Created {} command", new_command.name))),
+            Err(err) => Ok(Some(format!("Cannot create {} command: {err}", new_command.name))),
+        }
+    }
+
+    /// `help` lists the commands the sender can use, `help <page>` the rest of them if they don't
+    /// fit in one message and `help <command>` describes one.
+    fn handle_help_command(&self, context: &CommandContext, level: PermissionLevel, arg: &str) -> String {
+        let limit = context.message.platform.message_limit();
+        let arg = arg.trim_start_matches(COMMAND_SYMBOL);
+        let command = self.resolve(arg).and_then(|name| self.commands.get(&name));
+        match (command, arg.parse::<usize>()) {
+            // Commands the sender can't use don't exist as far as they know
+            (Some(command), _) if command.permission <= level && command.available_in(context.message) => help::describe(command, limit),
+            (_, Ok(page)) => help::list(&self.usable_by(context.message, level), page, limit),
+            _ if arg.is_empty() => help::list(&self.usable_by(context.message, level), 1, limit),
+            _ => format!("There is no command {COMMAND_SYMBOL}{arg}"),
+        }
+    }
+
+    /// Commands the sender of the message can use where it was sent.
+    fn usable_by(&self, message: &PlatformMessage, level: PermissionLevel) -> Vec<&BotCommand> {
+        self.commands
+            .values()
+            .filter(|command| command.permission <= level && command.available_in(message))
+            .collect()
+    }
+
+    /// `alias <alias> <command>` adds an alias to a command, `alias <alias>` removes it.
+    fn handle_alias_command(&mut self, args: Vec<&str>) -> HandleCommandResult<Option<String>> {
+        match args[..] {
+            [alias, command_name] => {
+                let Some(command_name) = self.resolve(command_name) else {
+                    return Err(HandleCommandError::MissingCommand(command_name.to_string()));
+                };
+                if let Some(owner) = self.resolve(alias) {
+                    return Ok(Some(format!("Cannot add alias {alias}: it is already used by command {owner}")));
+                }
+                let mut command = self.get_command(&command_name);
+                command.aliases.push(alias.to_string());
+                if let Err(err) = self.update_command(&command) {
+                    return Ok(Some(format!("Cannot add alias {alias}: {err}")));
+                }
+                Ok(Some(format!(
+                    "{COMMAND_SYMBOL}{alias} now answers like {COMMAND_SYMBOL}{command_name}"
+                )))
+            }
+            [alias] => {
+                let Some(command_name) = self.resolve(alias).filter(|command_name| !self.same_name(command_name, alias)) else {
+                    return Ok(Some(format!("There is no alias {alias}")));
+                };
+                let mut command = self.get_command(&command_name);
+                command.aliases.retain(|existing| !self.same_name(existing, alias));
+                match self.update_command(&command) {
+                    Ok(_) => Ok(Some(format!("Removed alias {alias} of {command_name} command"))),
+                    Err(err) => Ok(Some(format!("Cannot remove alias {alias}: {err}"))),
+                }
+            }
+            _ => Ok(Some(format!(
+                "Usage: {COMMAND_SYMBOL}alias <alias> <command> to add an alias, {COMMAND_SYMBOL}alias <alias> to remove it"
+            ))),
+        }
+    }
+}
+
+/// Reloads the commands changed outside the bot every `RELOAD_INTERVAL`.
+pub fn watch(command_handler: Arc<Mutex<CommandHandler>>) {
+    thread::spawn(move || loop {
+        thread::sleep(RELOAD_INTERVAL);
+        command_handler.lock().expect("To lock the command handler").reload();
+    });
+}
+
+fn render(context: &CommandContext, command: &str, contents: String, args: &[&str], count: i64) -> String {
+    let template_context = TemplateContext {
+        user: context.message.display_name(),
+        args,
+        channel: context.message.channel.as_deref(),
+        platform: Some(context.message.platform.id()),
+        count,
+        live_since: context.live_since,
+        now: Utc::now(),
+    };
+    match Template::parse(&contents) {
+        Ok(template) => template.render(&template_context),
+        // Commands written before templates existed may have braces in them
+        Err(err) => {
+            eprintln!("WARN - Command {command} is not a valid template, sending it as is: {err}");
+            contents
+        }
+    }
+}
+
+/// New counter value if the arguments are a counter subcommand: `+`, `-`, `= N` or `reset`.
+fn counter_change(count: i64, args: &[&str]) -> Option<i64> {
+    match args {
+        ["+"] => Some(count.saturating_add(1)),
+        ["-"] => Some(count.saturating_sub(1)),
+        ["=", value] => value.parse().ok(),
+        ["reset"] => Some(0),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cooldown::ManualClock, messages::Badge, store::MemoryStore};
+
+    fn handler(commands: &[(&str, &str)]) -> CommandHandler {
+        let mut handler = CommandHandler::new(Box::new(MemoryStore::default())).unwrap();
+        handler.case_insensitive = true;
+        for (name, contents) in commands {
+            let command = BotCommand {
+                name: name.to_string(),
+                contents: contents.to_string(),
+                ..Default::default()
+            };
+            handler.create_command(&command).unwrap();
+        }
+        handler
+    }
+
+    fn answer(handler: &mut CommandHandler, msg: &str) -> HandleCommandResult<Option<String>> {
+        answer_as(handler, "viewer", &[], msg)
+    }
+
+    fn answer_as(handler: &mut CommandHandler, sender: &str, badges: &[&str], msg: &str) -> HandleCommandResult<Option<String>> {
+        let message = PlatformMessage {
+            badges: badges.iter().map(|badge| Badge::new(badge, None)).collect(),
+            ..PlatformMessage::new(Platform::TWITCH, sender.to_string(), format!("{COMMAND_SYMBOL}{msg}"))
+        };
+        let context = CommandContext {
+            message: &message,
+            live_since: &|| None,
+        };
+        handler.handle_command(&context, msg.to_string())
+    }
+
+    #[test]
+    fn builtins_follow_the_case_rule() {
+        let mut handler = handler(&[("discord", "discord.gg/abc")]);
+        let help = answer(&mut handler, "help").unwrap();
+        assert!(help.as_deref().is_some_and(|help| help.contains("!discord")));
+        assert_eq!(answer(&mut handler, "Help").unwrap(), help);
+        assert_eq!(answer(&mut handler, "HELP").unwrap(), help);
+        assert_eq!(answer(&mut handler, "QUOTE").unwrap().as_deref(), Some("No quote found"));
+
+        handler.case_insensitive = false;
+        assert!(matches!(answer(&mut handler, "Help"), Err(HandleCommandError::MissingCommand(_))));
+    }
+
+    #[test]
+    fn commands_named_like_builtins_win() {
+        let mut handler = handler(&[("Help", "Ask a moderator"), ("addquote", "Quotes are on the website")]);
+        assert_eq!(answer(&mut handler, "help").unwrap().as_deref(), Some("Ask a moderator"));
+        assert_eq!(
+            answer(&mut handler, "addquote something funny").unwrap().as_deref(),
+            Some("Quotes are on the website")
+        );
+    }
+
+    #[test]
+    fn quotes_are_added_by_admins_and_read_by_everyone() {
+        let mut handler = handler(&[]);
+        assert_eq!(
+            answer(&mut handler, "addquote something funny").unwrap().as_deref(),
+            Some("I'm sorry viewer. You are not allowed to execute this command.")
+        );
+        assert_eq!(
+            answer_as(&mut handler, "mod", &["moderator"], "addquote something funny")
+                .unwrap()
+                .as_deref(),
+            Some("Added quote #1")
+        );
+        assert_eq!(answer(&mut handler, "quote #1").unwrap().as_deref(), Some("#1: something funny"));
+        assert_eq!(answer(&mut handler, "quote 2").unwrap().as_deref(), Some("No quote found"));
+    }
+
+    #[test]
+    fn cooldowns_spare_moderators_and_notify_once() {
+        let mut handler = handler(&[("today", "Just chatting")]);
+        let clock = ManualClock::new();
+        handler.cooldowns = Cooldowns::new(Box::new(clock.clone()));
+        let mut command = handler.get_command(&"today".to_string());
+        command.cooldown = Cooldown {
+            global: 30,
+            per_user: 0,
+            notify: true,
+        };
+        handler.save(command).unwrap();
+
+        assert_eq!(answer(&mut handler, "today").unwrap().as_deref(), Some("Just chatting"));
+        assert_eq!(
+            answer_as(&mut handler, "other", &[], "today").unwrap().as_deref(),
+            Some("!today is on cooldown for 30s")
+        );
+        assert_eq!(answer_as(&mut handler, "other", &[], "today").unwrap(), None);
+        assert_eq!(
+            answer_as(&mut handler, "mod", &["moderator"], "today").unwrap().as_deref(),
+            Some("Just chatting")
+        );
+        clock.advance(30);
+        assert_eq!(
+            answer_as(&mut handler, "other", &[], "today").unwrap().as_deref(),
+            Some("Just chatting")
+        );
+    }
+
+    #[test]
+    fn only_commands_showing_their_counter_have_one() {
+        let mut handler = handler(&[("deaths", "Died {count} times"), ("echo", "You said {args}")]);
+        handler.permissions.admin_verbs = PermissionLevel::Everyone;
+
+        assert_eq!(answer(&mut handler, "deaths +").unwrap().as_deref(), Some("Died 1 times"));
+        assert_eq!(answer(&mut handler, "deaths = 41").unwrap().as_deref(), Some("Died 41 times"));
+        assert_eq!(handler.get_command(&"deaths".to_string()).count, 41);
+
+        assert_eq!(answer(&mut handler, "echo reset").unwrap().as_deref(), Some("You said reset"));
+        assert_eq!(answer(&mut handler, "echo +").unwrap().as_deref(), Some("You said +"));
+        assert_eq!(handler.get_command(&"echo".to_string()).count, 0);
+    }
 
-        Ok(Some(format!("Created {} command", new_command.name)))
+    #[test]
+    fn set_prefix_only_edits_existing_commands() {
+        let mut handler = handler(&[("today", "Just chatting"), ("settings", "1080p60")]);
+        assert_eq!(answer(&mut handler, "settings").unwrap().as_deref(), Some("1080p60"));
+        assert!(matches!(answer(&mut handler, "setup"), Err(HandleCommandError::MissingCommand(_))));
+        assert_eq!(
+            answer(&mut handler, "settoday Coding").unwrap().as_deref(),
+            Some("I'm sorry viewer. You are not allowed to execute this command.")
+        );
+        answer_as(&mut handler, "mod", &["moderator"], "settoday Coding").unwrap();
+        assert_eq!(answer(&mut handler, "today").unwrap().as_deref(), Some("Coding"));
+        answer_as(&mut handler, "mod", &["moderator"], "SetToday Gaming").unwrap();
+        assert_eq!(answer(&mut handler, "today").unwrap().as_deref(), Some("Gaming"));
+
+        handler.case_insensitive = false;
+        assert!(matches!(
+            answer_as(&mut handler, "mod", &["moderator"], "SetToday Coding"),
+            Err(HandleCommandError::MissingCommand(_))
+        ));
     }
 }
@@ -0,0 +1,325 @@
+use std::{
+    env,
+    fs::{self, File},
+    io::{ErrorKind, Write},
+    path::Path,
+    time::SystemTime,
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::command::BotCommand;
+
+/// Bumped every time the layout of the file changes in a way `#[serde(default)]` can't cover, with
+/// a migration from the previous version added to `MIGRATIONS`.
+pub static CURRENT_VERSION: u64 = 2;
+
+static DEFAULT_BACKUPS: usize = 5;
+
+/// `MIGRATIONS[n]` takes a version `n` file to version `n + 1`.
+static MIGRATIONS: [fn(Value) -> Result<Value>; 2] = [migrate_v0, migrate_v1];
+
+/// What the commands file contains. Fields added to `BotCommand` need `#[serde(default)]` so older
+/// files keep loading.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CommandsFile {
+    pub version: u64,
+    pub commands: Vec<BotCommand>,
+}
+
+/// Reads the commands at `path`, migrating the file to the current version if needed. A missing or
+/// empty file is created with no commands.
+pub fn read(path: &str) -> Result<Vec<BotCommand>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(anyhow::Error::msg(format!("Cannot read commands file {path}: {err}"))),
+    };
+    if contents.trim().is_empty() {
+        eprintln!("INFO - No commands in {path}, creating it");
+        write(path, &[])?;
+        return Ok(Vec::new());
+    }
+
+    let (commands, version) = parse(path, &contents)?;
+    if version < CURRENT_VERSION {
+        eprintln!("INFO - Migrated commands file {path} from version {version} to {CURRENT_VERSION}");
+        write(path, &commands)?;
+    }
+    Ok(commands)
+}
+
+/// Commands in `contents` and the version they were written in.
+pub fn parse(path: &str, contents: &str) -> Result<(Vec<BotCommand>, u64)> {
+    let value: Value =
+        serde_json::from_str(contents).map_err(|err| anyhow::Error::msg(format!("Commands file {path} is not valid JSON: {err}")))?;
+    let version = match value.get("version") {
+        // Before versions existed the file was a map of command names to commands
+        None => 0,
+        Some(version) => version.as_u64().ok_or(anyhow::Error::msg(format!(
+            "Commands file {path} has an invalid version: {version}"
+        )))?,
+    };
+    if version > CURRENT_VERSION {
+        return Err(anyhow::Error::msg(format!(
+            "Commands file {path} is version {version}, but this bot only knows up to version {CURRENT_VERSION}. Update the bot"
+        )));
+    }
+
+    let mut value = value;
+    for migration in &MIGRATIONS[version as usize..] {
+        value = migration(value).map_err(|err| anyhow::Error::msg(format!("Cannot migrate commands file {path}: {err}")))?;
+    }
+    let file: CommandsFile =
+        serde_json::from_value(value).map_err(|err| anyhow::Error::msg(format!("Commands file {path} is invalid: {err}")))?;
+
+    Ok((file.commands, version))
+}
+
+/// Replaces the file at `path` without ever leaving it half written, see `replace`. The previous
+/// contents are kept as a backup, the last `COMMANDS_BACKUPS` (5 by default) of them.
+pub fn write(path: &str, commands: &[BotCommand]) -> Result<()> {
+    let file = CommandsFile {
+        version: CURRENT_VERSION,
+        commands: commands.to_vec(),
+    };
+    let contents = serde_json::to_string_pretty(&file)?;
+    if Path::new(path).exists() {
+        rotate_backups(path)?;
+    }
+    replace(path, &contents)
+}
+
+/// Writes `contents` to a temporary file that is synced and then renamed over `path`, so a crash
+/// leaves either the old file or the new one.
+pub fn replace(path: &str, contents: &str) -> Result<()> {
+    let tmp_path = format!("{path}.tmp");
+    let mut tmp = File::create(&tmp_path)?;
+    tmp.write_all(contents.as_bytes())?;
+    tmp.sync_all()?;
+    drop(tmp);
+
+    fs::rename(&tmp_path, path)?;
+    // Makes the rename itself survive a crash
+    #[cfg(unix)]
+    if let Some(dir) = Path::new(path).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// A previous version of the commands file.
+#[derive(Debug, Clone)]
+pub struct Backup {
+    pub path: String,
+    pub modified: SystemTime,
+}
+
+/// Backups of the file at `path`, the most recent first.
+pub fn backups(path: &str) -> Vec<Backup> {
+    (1..=backup_count())
+        .filter_map(|n| {
+            let backup = backup_path(path, n);
+            let modified = fs::metadata(&backup).and_then(|metadata| metadata.modified()).ok()?;
+            Some(Backup { path: backup, modified })
+        })
+        .collect()
+}
+
+/// Writes the commands in `backup` to `path`, which becomes a backup itself so the restore can be
+/// undone.
+pub fn restore(path: &str, backup: &Backup) -> Result<Vec<BotCommand>> {
+    let contents =
+        fs::read_to_string(&backup.path).map_err(|err| anyhow::Error::msg(format!("Cannot read backup {}: {err}", backup.path)))?;
+    let (commands, _) = parse(&backup.path, &contents)?;
+    write(path, &commands)?;
+    Ok(commands)
+}
+
+fn backup_count() -> usize {
+    env::var("COMMANDS_BACKUPS")
+        .ok()
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(DEFAULT_BACKUPS)
+}
+
+fn backup_path(path: &str, n: usize) -> String {
+    format!("{path}.{n}.bak")
+}
+
+/// Copies the current file to the first backup, moving the others one place down and dropping
+/// the oldest.
+fn rotate_backups(path: &str) -> Result<()> {
+    let count = backup_count();
+    if count == 0 {
+        return Ok(());
+    }
+    let oldest = backup_path(path, count);
+    if Path::new(&oldest).exists() {
+        fs::remove_file(oldest)?;
+    }
+    for n in (1..count).rev() {
+        let backup = backup_path(path, n);
+        if Path::new(&backup).exists() {
+            fs::rename(&backup, backup_path(path, n + 1))?;
+        }
+    }
+    fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
+
+/// `{"name": {"name": ..., "contents": ...}}` to `{"version": 1, "commands": [...]}`
+fn migrate_v0(value: Value) -> Result<Value> {
+    let Value::Object(commands) = value else {
+        return Err(anyhow::Error::msg("expected a map of command names to commands"));
+    };
+    let commands: Vec<Value> = commands
+        .into_iter()
+        .map(|(name, command)| match command {
+            Value::Object(mut command) => {
+                command.entry("name").or_insert(Value::String(name));
+                Ok(Value::Object(command))
+            }
+            _ => Err(anyhow::Error::msg(format!("command {name} is not an object"))),
+        })
+        .collect::<Result<_>>()?;
+    Ok(json!({ "version": 1, "commands": commands }))
+}
+
+/// Drops the `help` command the bot used to generate and save, now it is built in. One written by
+/// hand is kept.
+fn migrate_v1(value: Value) -> Result<Value> {
+    let Value::Object(mut file) = value else {
+        return Err(anyhow::Error::msg("expected an object with the commands"));
+    };
+    if let Some(Value::Array(commands)) = file.get_mut("commands") {
+        commands.retain(|command| {
+            let generated = command.get("name").and_then(Value::as_str) == Some("help")
+                && command
+                    .get("contents")
+                    .and_then(Value::as_str)
+                    .is_some_and(|contents| contents.starts_with("Available commands:"));
+            !generated
+        });
+    }
+    file.insert("version".to_string(), json!(2));
+    Ok(Value::Object(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, process};
+
+    use super::*;
+
+    /// An empty directory of its own for each test.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("ttv-bot-{}-{name}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn names(commands: &[BotCommand]) -> Vec<&str> {
+        commands.iter().map(|command| command.name.as_str()).collect()
+    }
+
+    #[test]
+    fn migrates_every_old_version() {
+        let path = test_dir("migrate").join("commands.json").to_string_lossy().to_string();
+        let v0 = json!({
+            "discord": { "contents": "discord.gg/abc" },
+            "help": { "name": "help", "contents": "Available commands: !discord" }
+        });
+        fs::write(&path, v0.to_string()).unwrap();
+
+        let commands = read(&path).unwrap();
+        assert_eq!(names(&commands), ["discord"]);
+        assert_eq!(commands[0].contents, "discord.gg/abc");
+        let written: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written["version"], json!(CURRENT_VERSION));
+        // The file as it was before migrating is kept
+        assert_eq!(backups(&path).len(), 1);
+    }
+
+    #[test]
+    fn keeps_a_help_command_written_by_hand() {
+        let v1 = json!({
+            "version": 1,
+            "commands": [{ "name": "help", "contents": "Ask a moderator" }]
+        });
+        let (commands, version) = parse("commands.json", &v1.to_string()).unwrap();
+        assert_eq!((names(&commands), version), (vec!["help"], 1));
+    }
+
+    #[test]
+    fn refuses_files_it_does_not_understand() {
+        let future = json!({ "version": CURRENT_VERSION + 1, "commands": [] });
+        assert!(parse("commands.json", &future.to_string()).is_err());
+        assert!(parse("commands.json", "{ \"version\": \"two\" }").is_err());
+        assert!(parse("commands.json", "[1, 2]").is_err());
+        assert!(parse("commands.json", "{ \"today\": \"Just chatting\" }").is_err());
+    }
+
+    #[test]
+    fn creates_a_missing_file() {
+        let path = test_dir("missing").join("commands.json").to_string_lossy().to_string();
+        assert!(read(&path).unwrap().is_empty());
+        let (commands, version) = parse(&path, &fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!((commands.len(), version), (0, CURRENT_VERSION));
+    }
+
+    fn command(name: &str, contents: &str) -> BotCommand {
+        BotCommand {
+            name: name.to_string(),
+            contents: contents.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn replacing_leaves_no_temporary_file() {
+        let dir = test_dir("replace");
+        let path = dir.join("timers.json").to_string_lossy().to_string();
+        replace(&path, "[]").unwrap();
+        replace(&path, "[1]").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[1]");
+        let files: Vec<_> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(files, ["timers.json"]);
+    }
+
+    #[test]
+    fn keeps_the_last_backups_most_recent_first() {
+        let path = test_dir("backups").join("commands.json").to_string_lossy().to_string();
+        for n in 0..=DEFAULT_BACKUPS + 1 {
+            write(&path, &[command("today", &format!("version {n}"))]).unwrap();
+        }
+        let backups = backups(&path);
+        assert_eq!(backups.len(), DEFAULT_BACKUPS);
+        let contents: Vec<String> = backups
+            .iter()
+            .map(|backup| {
+                parse(&backup.path, &fs::read_to_string(&backup.path).unwrap()).unwrap().0[0]
+                    .contents
+                    .clone()
+            })
+            .collect();
+        assert_eq!(contents, ["version 5", "version 4", "version 3", "version 2", "version 1"]);
+        assert!(!Path::new(&backup_path(&path, DEFAULT_BACKUPS + 1)).exists());
+    }
+
+    #[test]
+    fn restoring_a_backup_can_be_undone() {
+        let path = test_dir("restore").join("commands.json").to_string_lossy().to_string();
+        write(&path, &[command("today", "Just chatting")]).unwrap();
+        write(&path, &[command("today", "Coding")]).unwrap();
+
+        let restored = restore(&path, &backups(&path)[0]).unwrap();
+        assert_eq!(restored[0].contents, "Just chatting");
+        assert_eq!(read(&path).unwrap()[0].contents, "Just chatting");
+        let undone = restore(&path, &backups(&path)[0]).unwrap();
+        assert_eq!(undone[0].contents, "Coding");
+    }
+}
@@ -0,0 +1,401 @@
+use std::{
+    fmt,
+    io::ErrorKind,
+    net::TcpStream,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tungstenite::{stream::MaybeTlsStream, WebSocket};
+
+use crate::{
+    bus::EventBus,
+    command::{CommandContext, CommandHandler, HandleCommandError, COMMAND_SYMBOL},
+    discord, irc, kick,
+    messages::{Platform, PlatformEvent, PlatformInfo},
+    moderation::{ModerationHandler, Moderator},
+    twitch, twitter, youtube,
+};
+
+/// Builds a connector from its configuration, `None` if the platform is not configured.
+pub type ConnectorFactory = fn() -> Option<Box<dyn PlatformConnector>>;
+
+/// Every supported platform. Adding one means implementing `PlatformConnector` and listing its
+/// factory here.
+static CONNECTORS: &[ConnectorFactory] = &[
+    twitch::connector,
+    youtube::connector,
+    kick::connector,
+    twitter::connector,
+    discord::connector,
+    irc::connector,
+];
+
+pub static POLL_TIMEOUT: Duration = Duration::from_millis(500);
+static RECONNECT_DELAY: Duration = Duration::from_secs(10);
+/// Longest a platform API call made while handling chat may take.
+pub static API_TIMEOUT: Duration = Duration::from_secs(5);
+static LIVE_SINCE_TTL: Duration = Duration::from_secs(60);
+
+/// An event received by a connector together with where answers to it should go (a channel, a
+/// tweet...). `None` means the default destination of the connector.
+pub struct IncomingEvent {
+    pub event: PlatformEvent,
+    pub reply_to: Option<String>,
+}
+
+impl IncomingEvent {
+    pub fn new(event: impl Into<PlatformEvent>, reply_to: Option<String>) -> Self {
+        Self {
+            event: event.into(),
+            reply_to,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OutgoingMessage {
+    pub channel: Option<String>,
+    pub msg: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ConnectorStatus {
+    Connecting,
+    Connected,
+    Failed(String),
+}
+
+impl fmt::Display for ConnectorStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConnectorStatus::Connecting => write!(f, "connecting"),
+            ConnectorStatus::Connected => write!(f, "connected"),
+            ConnectorStatus::Failed(err) => write!(f, "failed: {err}"),
+        }
+    }
+}
+
+pub trait PlatformConnector: Send {
+    fn info(&self) -> PlatformInfo;
+
+    /// Opens the connection. Called again after `poll` fails, so it must be able to reconnect.
+    fn connect(&mut self) -> Result<()>;
+
+    /// Returns whatever arrived since the last call. It must not block for much longer than
+    /// `POLL_TIMEOUT`, outgoing messages are only sent between polls.
+    fn poll(&mut self) -> Result<Vec<IncomingEvent>>;
+
+    /// Sends `msg` to `channel`, or to the default destination of the connector if `None`.
+    fn send_message(&mut self, channel: Option<&str>, msg: &str) -> Result<()>;
+
+    /// When the current stream went live, `None` if offline or the platform can't tell.
+    fn live_since(&self) -> Option<DateTime<Utc>> {
+        None
+    }
+
+    /// Moderation capabilities of the platform, if any are configured.
+    fn moderator(&self) -> Option<Box<dyn Moderator>> {
+        None
+    }
+
+    /// Connects and keeps the connector going: forwards events, answers commands, sends what
+    /// comes through `context.outgoing` and reconnects on failure.
+    fn run(&mut self, mut context: ConnectorContext) {
+        let label = self.info().label;
+        let mut live_since = LiveSinceCache::default();
+        loop {
+            context.set_status(ConnectorStatus::Connecting);
+            if let Err(err) = self.connect() {
+                eprintln!("ERROR - Cannot connect to {label}: {:?}", err);
+                context.set_status(ConnectorStatus::Failed(err.to_string()));
+                thread::sleep(RECONNECT_DELAY);
+                continue;
+            }
+            context.set_status(ConnectorStatus::Connected);
+
+            loop {
+                while let Ok(outgoing) = context.outgoing.try_recv() {
+                    if let Err(err) = self.send_message(outgoing.channel.as_deref(), &outgoing.msg) {
+                        eprintln!("ERROR - Cannot send message to {label}: {:?}", err);
+                    }
+                }
+
+                let incoming = match self.poll() {
+                    Ok(incoming) => incoming,
+                    Err(err) => {
+                        eprintln!("ERROR - Lost connection to {label}: {:?}", err);
+                        context.set_status(ConnectorStatus::Failed(err.to_string()));
+                        break;
+                    }
+                };
+
+                for IncomingEvent { event, reply_to } in incoming {
+                    // Everyone else sees the message before its answer
+                    context.bus.publish(event.clone());
+                    let PlatformEvent::Chat(message) = &event else {
+                        continue;
+                    };
+                    // Asked before locking, a slow API must not hold up the other connectors
+                    let live_since = live_since.get(|| self.live_since());
+                    let command_context = CommandContext {
+                        message,
+                        live_since: &|| live_since,
+                    };
+                    let response = respond(
+                        &mut context
+                            .command_handler
+                            .lock()
+                            .expect("To lock command_handler for connector thread"),
+                        &command_context,
+                        label,
+                    );
+                    if let Some(response) = response {
+                        if let Err(err) = self.send_message(reply_to.as_deref(), &response) {
+                            eprintln!("ERROR - Cannot answer {label}: {:?}", err);
+                        }
+                    }
+                }
+            }
+
+            thread::sleep(RECONNECT_DELAY);
+        }
+    }
+}
+
+/// Answer to a chat message: the command it calls or, if it calls none, the first trigger it fires.
+/// Messages of the bot itself never call commands, they only fire the triggers that don't ignore it.
+fn respond(command_handler: &mut CommandHandler, context: &CommandContext, label: &str) -> Option<String> {
+    let message = context.message;
+    if message.is_bot {
+        return command_handler.handle_triggers(context);
+    }
+    let Some(command) = message.msg.strip_prefix(COMMAND_SYMBOL) else {
+        return command_handler.handle_triggers(context);
+    };
+    match command_handler.handle_command(context, command.to_string()) {
+        Ok(response) => response,
+        Err(err @ HandleCommandError::MissingCommand(_)) => {
+            eprintln!("INFO - {label}: {err}");
+            None
+        }
+        Err(err) => {
+            eprintln!("WARN - Cannot handle command on {label}: {err}");
+            None
+        }
+    }
+}
+
+/// Last answer of `PlatformConnector::live_since`, asked again once it is `LIVE_SINCE_TTL` old.
+#[derive(Default)]
+struct LiveSinceCache {
+    value: Option<DateTime<Utc>>,
+    fetched_at: Option<Instant>,
+}
+
+impl LiveSinceCache {
+    fn get(&mut self, fetch: impl FnOnce() -> Option<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+        if self.fetched_at.is_none_or(|fetched_at| fetched_at.elapsed() >= LIVE_SINCE_TTL) {
+            self.value = fetch();
+            self.fetched_at = Some(Instant::now());
+        }
+        self.value
+    }
+}
+
+pub struct ConnectorContext {
+    pub command_handler: Arc<Mutex<CommandHandler>>,
+    pub bus: EventBus,
+    pub outgoing: UnboundedReceiver<OutgoingMessage>,
+    platform: Platform,
+    status: Arc<Mutex<ConnectorStatus>>,
+}
+
+impl ConnectorContext {
+    /// Updates the status shown for the connector and, if it changed, reports it as an event.
+    pub fn set_status(&self, status: ConnectorStatus) {
+        let mut current = self.status.lock().expect("To lock connector status");
+        if *current == status {
+            return;
+        }
+        *current = status.clone();
+        let event = PlatformEvent::ConnectionState {
+            platform: self.platform.clone(),
+            status,
+        };
+        self.bus.publish(event);
+    }
+}
+
+/// What the rest of the app keeps from a running connector.
+pub struct ConnectorHandle {
+    pub info: PlatformInfo,
+    status: Arc<Mutex<ConnectorStatus>>,
+    outgoing: UnboundedSender<OutgoingMessage>,
+}
+
+impl ConnectorHandle {
+    pub fn status(&self) -> ConnectorStatus {
+        self.status.lock().expect("To lock connector status").clone()
+    }
+
+    pub fn send_message(&self, channel: Option<String>, msg: String) -> Result<()> {
+        self.outgoing
+            .send(OutgoingMessage { channel, msg })
+            .map_err(|_| anyhow::Error::msg(format!("{} connector is not running", self.info.label)))
+    }
+}
+
+pub struct Registry {
+    handles: Vec<ConnectorHandle>,
+    moderation: ModerationHandler,
+}
+
+impl Registry {
+    /// Builds every configured connector and starts each one in its own thread.
+    pub fn start(command_handler: Arc<Mutex<CommandHandler>>, bus: EventBus) -> Self {
+        let mut handles = Vec::new();
+        let mut moderators = Vec::new();
+
+        for factory in CONNECTORS {
+            let Some(mut connector) = factory() else {
+                continue;
+            };
+            let info = connector.info();
+            if let Some(moderator) = connector.moderator() {
+                moderators.push(moderator);
+            }
+
+            let status = Arc::new(Mutex::new(ConnectorStatus::Connecting));
+            let (outgoing_sender, outgoing) = unbounded_channel();
+            let context = ConnectorContext {
+                command_handler: command_handler.clone(),
+                bus: bus.clone(),
+                outgoing,
+                platform: info.platform.clone(),
+                status: status.clone(),
+            };
+            thread::spawn(move || connector.run(context));
+
+            handles.push(ConnectorHandle {
+                info,
+                status,
+                outgoing: outgoing_sender,
+            });
+        }
+
+        Self {
+            handles,
+            moderation: ModerationHandler::new(moderators),
+        }
+    }
+
+    pub fn handles(&self) -> &[ConnectorHandle] {
+        &self.handles
+    }
+
+    pub fn info(&self, platform: &Platform) -> Option<&PlatformInfo> {
+        self.handles
+            .iter()
+            .map(|handle| &handle.info)
+            .find(|info| info.platform == *platform)
+    }
+
+    pub fn moderation(&self) -> &ModerationHandler {
+        &self.moderation
+    }
+}
+
+/// Makes reads on a WebSocket give up after `POLL_TIMEOUT` so `poll` does not block forever.
+pub fn set_read_timeout(stream: &WebSocket<MaybeTlsStream<TcpStream>>) -> Result<()> {
+    match stream.get_ref() {
+        MaybeTlsStream::Plain(tcp) => tcp.set_read_timeout(Some(POLL_TIMEOUT))?,
+        MaybeTlsStream::NativeTls(tls) => tls.get_ref().set_read_timeout(Some(POLL_TIMEOUT))?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Whether a read failed only because nothing arrived before the timeout.
+pub fn is_timeout(err: &anyhow::Error) -> bool {
+    let io = match err.downcast_ref::<tungstenite::Error>() {
+        Some(tungstenite::Error::Io(io)) => Some(io),
+        _ => err.downcast_ref::<std::io::Error>(),
+    };
+    matches!(io.map(|io| io.kind()), Some(ErrorKind::WouldBlock | ErrorKind::TimedOut))
+}
+
+/// Sleep used by polling connectors between requests, short enough to keep `poll` responsive.
+pub fn idle() {
+    thread::sleep(POLL_TIMEOUT);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{command::BotCommand, messages::PlatformMessage, store::MemoryStore, trigger::Trigger};
+
+    fn command(name: &str, contents: &str, trigger: Option<(&str, bool)>) -> BotCommand {
+        BotCommand {
+            name: name.to_string(),
+            contents: contents.to_string(),
+            triggers: trigger
+                .map(|(pattern, ignore_bot)| Trigger {
+                    pattern: pattern.to_string(),
+                    ignore_bot,
+                    ..Default::default()
+                })
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn bot_messages_only_fire_triggers_that_allow_them() {
+        let mut handler = CommandHandler::new(Box::new(MemoryStore::default())).unwrap();
+        handler.create_command(&command("discord", "discord.gg/abc", None)).unwrap();
+        handler.create_command(&command("hype", "HYPE", Some(("hype", true)))).unwrap();
+        handler
+            .create_command(&command("relay", "relayed", Some(("discord", false))))
+            .unwrap();
+        let mut respond_to = |sender: &str, is_bot: bool, msg: &str| {
+            let message = PlatformMessage {
+                is_bot,
+                ..PlatformMessage::new(Platform::TWITCH, sender.to_string(), msg.to_string())
+            };
+            let context = CommandContext {
+                message: &message,
+                live_since: &|| None,
+            };
+            respond(&mut handler, &context, "Twitch")
+        };
+
+        assert_eq!(respond_to("viewer", false, "!discord hype").as_deref(), Some("discord.gg/abc"));
+        assert_eq!(respond_to("viewer", false, "hype").as_deref(), Some("HYPE"));
+        assert_eq!(respond_to("bot", true, "!discord hype").as_deref(), Some("relayed"));
+        assert_eq!(respond_to("bot", true, "hype"), None);
+    }
+
+    #[test]
+    fn live_since_is_asked_once_per_ttl() {
+        let mut cache = LiveSinceCache::default();
+        let started = Utc::now();
+        let mut calls = 0;
+        let mut fetch = |value| {
+            calls += 1;
+            value
+        };
+        assert_eq!(cache.get(|| fetch(Some(started))), Some(started));
+        assert_eq!(cache.get(|| fetch(None)), Some(started));
+
+        cache.fetched_at = Instant::now().checked_sub(LIVE_SINCE_TTL);
+        assert_eq!(cache.get(|| fetch(None)), None);
+        assert_eq!(calls, 2);
+    }
+}
@@ -0,0 +1,193 @@
+use std::{collections::HashMap, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Where cooldowns get the current time from, so they can be checked without waiting.
+pub trait Clock: Send {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that only moves when told to, for tests.
+#[cfg(test)]
+#[derive(Clone)]
+pub struct ManualClock(std::sync::Arc<std::sync::Mutex<DateTime<Utc>>>);
+
+#[cfg(test)]
+impl ManualClock {
+    pub fn new() -> Self {
+        Self(std::sync::Arc::new(std::sync::Mutex::new(
+            "2024-01-01T00:00:00Z".parse().expect("Valid date"),
+        )))
+    }
+
+    pub fn advance(&self, seconds: i64) {
+        *self.0.lock().unwrap() += chrono::Duration::seconds(seconds);
+    }
+}
+
+#[cfg(test)]
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.0.lock().unwrap()
+    }
+}
+
+/// How often a command can be used. Both are in seconds, 0 meaning no cooldown.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Cooldown {
+    /// Between any two uses of the command
+    pub global: u64,
+    /// Between two uses of the command by the same user
+    pub per_user: u64,
+    /// Answer "on cooldown for Ns" (once per user and cooldown) instead of ignoring the command
+    pub notify: bool,
+}
+
+/// A command that cannot be used yet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OnCooldown {
+    pub remaining: Duration,
+    /// Whether it is the first time the user runs into this cooldown, the only time they are told
+    pub first: bool,
+}
+
+/// Last time each command was used, globally and by each user.
+pub struct Cooldowns {
+    clock: Box<dyn Clock>,
+    last_use: HashMap<String, DateTime<Utc>>,
+    last_user_use: HashMap<(String, String), DateTime<Utc>>,
+    /// When the cooldown each user was last told about ends, by command and user
+    notified: HashMap<(String, String), DateTime<Utc>>,
+}
+
+impl Cooldowns {
+    pub fn new(clock: Box<dyn Clock>) -> Self {
+        Self {
+            clock,
+            last_use: HashMap::new(),
+            last_user_use: HashMap::new(),
+            notified: HashMap::new(),
+        }
+    }
+
+    /// How long until `user` can use the command again. If it can be used now the use is recorded
+    /// and `None` is returned.
+    pub fn check(&mut self, command: &str, user: &str, cooldown: &Cooldown) -> Option<OnCooldown> {
+        let now = self.clock.now();
+        let user_key = (command.to_string(), user.to_string());
+        let ready_at = |last_use: Option<&DateTime<Utc>>, seconds: u64| {
+            Some(*last_use? + chrono::Duration::seconds(seconds as i64)).filter(|ready_at| *ready_at > now)
+        };
+
+        let global = ready_at(self.last_use.get(command), cooldown.global);
+        let per_user = ready_at(self.last_user_use.get(&user_key), cooldown.per_user);
+        if let Some(ready_at) = global.max(per_user) {
+            let first = self.notified.insert(user_key, ready_at) != Some(ready_at);
+            let remaining = (ready_at - now).to_std().unwrap_or_default();
+            return Some(OnCooldown { remaining, first });
+        }
+
+        self.last_use.insert(command.to_string(), now);
+        self.notified.remove(&user_key);
+        self.last_user_use.insert(user_key, now);
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cooldowns() -> (Cooldowns, ManualClock) {
+        let clock = ManualClock::new();
+        (Cooldowns::new(Box::new(clock.clone())), clock)
+    }
+
+    fn cooldown(global: u64, per_user: u64) -> Cooldown {
+        Cooldown {
+            global,
+            per_user,
+            notify: true,
+        }
+    }
+
+    fn remaining(on_cooldown: Option<OnCooldown>) -> Option<u64> {
+        on_cooldown.map(|on_cooldown| on_cooldown.remaining.as_secs())
+    }
+
+    #[test]
+    fn global_cooldown_applies_to_everyone_until_it_expires() {
+        let (mut cooldowns, clock) = cooldowns();
+        let cooldown = cooldown(30, 0);
+
+        assert_eq!(cooldowns.check("today", "twitch:alice", &cooldown), None);
+        clock.advance(10);
+        assert_eq!(remaining(cooldowns.check("today", "twitch:bob", &cooldown)), Some(20));
+        assert_eq!(remaining(cooldowns.check("today", "twitch:alice", &cooldown)), Some(20));
+        // Other commands are not affected
+        assert_eq!(cooldowns.check("discord", "twitch:bob", &cooldown), None);
+        clock.advance(20);
+        assert_eq!(cooldowns.check("today", "twitch:bob", &cooldown), None);
+    }
+
+    #[test]
+    fn per_user_cooldown_only_applies_to_that_user() {
+        let (mut cooldowns, clock) = cooldowns();
+        let cooldown = cooldown(0, 60);
+
+        assert_eq!(cooldowns.check("today", "twitch:alice", &cooldown), None);
+        assert_eq!(cooldowns.check("today", "twitch:bob", &cooldown), None);
+        clock.advance(59);
+        assert_eq!(remaining(cooldowns.check("today", "twitch:alice", &cooldown)), Some(1));
+        clock.advance(1);
+        assert_eq!(cooldowns.check("today", "twitch:alice", &cooldown), None);
+    }
+
+    #[test]
+    fn longest_cooldown_wins() {
+        let (mut cooldowns, clock) = cooldowns();
+        let cooldown = cooldown(10, 60);
+
+        assert_eq!(cooldowns.check("today", "twitch:alice", &cooldown), None);
+        clock.advance(20);
+        assert_eq!(cooldowns.check("today", "twitch:bob", &cooldown), None);
+        assert_eq!(remaining(cooldowns.check("today", "twitch:alice", &cooldown)), Some(40));
+    }
+
+    #[test]
+    fn users_are_told_once_per_cooldown() {
+        let (mut cooldowns, clock) = cooldowns();
+        let cooldown = cooldown(0, 30);
+
+        assert_eq!(cooldowns.check("today", "twitch:alice", &cooldown), None);
+        let first = |on_cooldown: Option<OnCooldown>| on_cooldown.map(|on_cooldown| on_cooldown.first);
+        assert_eq!(first(cooldowns.check("today", "twitch:alice", &cooldown)), Some(true));
+        assert_eq!(first(cooldowns.check("today", "twitch:alice", &cooldown)), Some(false));
+        clock.advance(30);
+        assert_eq!(cooldowns.check("today", "twitch:alice", &cooldown), None);
+        assert_eq!(first(cooldowns.check("today", "twitch:alice", &cooldown)), Some(true));
+    }
+
+    #[test]
+    fn a_new_global_cooldown_is_told_again() {
+        let (mut cooldowns, clock) = cooldowns();
+        let cooldown = cooldown(30, 0);
+        let first = |on_cooldown: Option<OnCooldown>| on_cooldown.map(|on_cooldown| on_cooldown.first);
+
+        assert_eq!(cooldowns.check("today", "twitch:alice", &cooldown), None);
+        assert_eq!(first(cooldowns.check("today", "twitch:bob", &cooldown)), Some(true));
+        clock.advance(30);
+        assert_eq!(cooldowns.check("today", "twitch:alice", &cooldown), None);
+        assert_eq!(first(cooldowns.check("today", "twitch:bob", &cooldown)), Some(true));
+    }
+}
@@ -0,0 +1,530 @@
+use std::{
+    env,
+    net::TcpStream,
+    ops::RangeInclusive,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use rand::Rng;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::{from_value, json, Value};
+use tungstenite::{connect, protocol::frame::coding::CloseCode, stream::MaybeTlsStream, Message, WebSocket};
+
+use crate::{
+    connector::{is_timeout, set_read_timeout, IncomingEvent, PlatformConnector},
+    messages::{parse_timestamp, Platform, PlatformEvent, PlatformInfo, PlatformMessage, ReplyContext},
+};
+
+static DEFAULT_DISCORD_GATEWAY_URL: &str = "wss://gateway.discord.gg/?v=10&encoding=json";
+static DEFAULT_DISCORD_API_URL: &str = "https://discord.com/api/v10";
+// GUILD_MESSAGES | MESSAGE_CONTENT
+static INTENTS: u64 = (1 << 9) | (1 << 15);
+/// Discord asks to wait a random 1 to 5 seconds after an invalid session before identifying again
+static INVALID_SESSION_WAIT: RangeInclusive<Duration> = Duration::from_secs(1)..=Duration::from_secs(5);
+
+impl Platform {
+    pub const DISCORD: Platform = Platform::new("discord");
+}
+
+mod opcode {
+    pub const DISPATCH: u8 = 0;
+    pub const HEARTBEAT: u8 = 1;
+    pub const IDENTIFY: u8 = 2;
+    pub const RESUME: u8 = 6;
+    pub const RECONNECT: u8 = 7;
+    pub const INVALID_SESSION: u8 = 9;
+    pub const HELLO: u8 = 10;
+    pub const HEARTBEAT_ACK: u8 = 11;
+}
+
+#[derive(Debug, Deserialize)]
+struct GatewayPayload {
+    op: u8,
+    #[serde(default)]
+    d: Value,
+    s: Option<u64>,
+    t: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Hello {
+    heartbeat_interval: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Ready {
+    session_id: String,
+    resume_gateway_url: String,
+    user: DiscordAuthor,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DiscordMessage {
+    pub id: String,
+    pub channel_id: String,
+    pub author: DiscordAuthor,
+    pub content: String,
+    #[serde(default)]
+    pub timestamp: String,
+    /// The message this one replies to
+    #[serde(default)]
+    pub referenced_message: Option<Box<DiscordMessage>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeletedMessage {
+    pub id: String,
+    pub channel_id: String,
+}
+
+/// The dispatches we care about.
+#[derive(Debug, Clone)]
+pub enum DiscordEvent {
+    MessageCreate(DiscordMessage),
+    MessageDelete(DeletedMessage),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DiscordAuthor {
+    pub id: String,
+    pub username: String,
+    #[serde(default)]
+    pub global_name: Option<String>,
+    #[serde(default)]
+    pub bot: bool,
+}
+
+impl From<DiscordMessage> for PlatformMessage {
+    fn from(message: DiscordMessage) -> Self {
+        PlatformMessage {
+            platform_id: Some(message.id),
+            sent_at: parse_timestamp(&message.timestamp),
+            sender_id: Some(message.author.id),
+            display_name: message.author.global_name,
+            channel: Some(message.channel_id),
+            reply: message.referenced_message.map(|parent| ReplyContext {
+                platform_id: parent.id,
+                sender: Some(parent.author.username),
+                msg: Some(parent.content),
+            }),
+            ..PlatformMessage::new(Platform::DISCORD, message.author.username, message.content)
+        }
+    }
+}
+
+pub struct DiscordConfig {
+    pub gateway_url: String,
+    pub api_url: String,
+    pub token: String,
+    /// Only messages from these channels make it to the combined chat
+    pub channel_ids: Vec<String>,
+}
+
+impl DiscordConfig {
+    pub fn from_env() -> Option<Self> {
+        Some(Self {
+            gateway_url: env::var("DISCORD_GATEWAY_URL").unwrap_or(DEFAULT_DISCORD_GATEWAY_URL.to_string()),
+            api_url: env::var("DISCORD_API_URL").unwrap_or(DEFAULT_DISCORD_API_URL.to_string()),
+            token: env::var("DISCORD_TOKEN").ok()?,
+            channel_ids: env::var("DISCORD_CHANNEL_IDS")
+                .ok()?
+                .split(',')
+                .map(|id| id.trim().to_string())
+                .filter(|id| !id.is_empty())
+                .collect(),
+        })
+    }
+}
+
+/// Gateway connection. Keeps the heartbeat going and reconnects (resuming the session when
+/// Discord allows it) while `read` is called in a loop.
+pub struct DiscordGateway {
+    config: Arc<DiscordConfig>,
+    stream: Option<WebSocket<MaybeTlsStream<TcpStream>>>,
+    heartbeat_interval: Duration,
+    last_heartbeat: Instant,
+    heartbeat_acked: bool,
+    seq: Option<u64>,
+    session_id: Option<String>,
+    resume_gateway_url: Option<String>,
+    /// Our own user, to tell our messages apart
+    user_id: Option<String>,
+    invalid_session_wait: RangeInclusive<Duration>,
+}
+
+impl DiscordGateway {
+    pub fn new(config: Arc<DiscordConfig>) -> Self {
+        Self {
+            config,
+            stream: None,
+            heartbeat_interval: Duration::from_secs(45),
+            last_heartbeat: Instant::now(),
+            heartbeat_acked: true,
+            seq: None,
+            session_id: None,
+            resume_gateway_url: None,
+            user_id: None,
+            invalid_session_wait: INVALID_SESSION_WAIT.clone(),
+        }
+    }
+
+    /// Opens the socket, waits for Hello and then identifies or, if there is a session to
+    /// continue, resumes it.
+    pub fn connect(&mut self) -> Result<()> {
+        let url = match (&self.session_id, &self.resume_gateway_url) {
+            (Some(_), Some(resume_gateway_url)) => format!("{resume_gateway_url}/?v=10&encoding=json"),
+            _ => self.config.gateway_url.clone(),
+        };
+        let (mut stream, _) = connect(url)?;
+
+        let hello = loop {
+            let payload = read_payload(&mut stream)?;
+            if payload.op == opcode::HELLO {
+                break from_value::<Hello>(payload.d)?;
+            }
+        };
+        self.heartbeat_interval = Duration::from_millis(hello.heartbeat_interval);
+        self.last_heartbeat = Instant::now();
+        self.heartbeat_acked = true;
+
+        // Wake up `read` regularly to check if a heartbeat is due
+        set_read_timeout(&stream)?;
+        self.stream = Some(stream);
+
+        match &self.session_id {
+            Some(session_id) => {
+                let resume =
+                    json!({ "op": opcode::RESUME, "d": { "token": self.config.token, "session_id": session_id, "seq": self.seq } });
+                self.send(resume)
+            }
+            None => {
+                let identify = json!({
+                    "op": opcode::IDENTIFY,
+                    "d": {
+                        "token": self.config.token,
+                        "intents": INTENTS,
+                        "properties": { "os": env::consts::OS, "browser": "omni-chatter", "device": "omni-chatter" }
+                    }
+                });
+                self.send(identify)
+            }
+        }
+    }
+
+    fn reconnect(&mut self, resume: bool) -> Result<()> {
+        if let Some(mut stream) = self.stream.take() {
+            let _ = stream.close(None);
+        }
+        if !resume {
+            self.session_id = None;
+            self.seq = None;
+        }
+        self.connect()
+    }
+
+    fn send(&mut self, payload: Value) -> Result<()> {
+        let stream = self.stream.as_mut().ok_or(anyhow::Error::msg("Discord gateway is not connected"))?;
+        stream.send(Message::Text(payload.to_string()))?;
+        Ok(())
+    }
+
+    fn heartbeat(&mut self) -> Result<()> {
+        self.last_heartbeat = Instant::now();
+        self.heartbeat_acked = false;
+        self.send(json!({ "op": opcode::HEARTBEAT, "d": self.seq }))
+    }
+
+    /// Processes whatever the gateway sent (or the heartbeat, if it is due) and returns the new
+    /// event if it was a message created or deleted in one of the configured channels.
+    pub fn read(&mut self) -> Result<Option<DiscordEvent>> {
+        if self.stream.is_none() {
+            self.connect()?;
+        }
+
+        if self.last_heartbeat.elapsed() >= self.heartbeat_interval {
+            if !self.heartbeat_acked {
+                eprintln!("WARN - Discord did not acknowledge the last heartbeat, reconnecting");
+                self.reconnect(true)?;
+                return Ok(None);
+            }
+            self.heartbeat()?;
+        }
+
+        let stream = self.stream.as_mut().expect("Stream to be connected");
+        let payload = match read_payload(stream) {
+            Ok(payload) => payload,
+            Err(err) if is_timeout(&err) => return Ok(None),
+            Err(err) => {
+                return match err.downcast_ref::<tungstenite::Error>() {
+                    Some(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed | tungstenite::Error::Io(_)) => {
+                        self.reconnect(true)?;
+                        Ok(None)
+                    }
+                    _ => Err(err),
+                };
+            }
+        };
+
+        if payload.s.is_some() {
+            self.seq = payload.s;
+        }
+
+        match payload.op {
+            opcode::DISPATCH => match payload.t.as_deref() {
+                Some("READY") => {
+                    let ready: Ready = from_value(payload.d)?;
+                    self.session_id = Some(ready.session_id);
+                    self.resume_gateway_url = Some(ready.resume_gateway_url);
+                    self.user_id = Some(ready.user.id);
+                }
+                Some("MESSAGE_CREATE") => {
+                    let message: DiscordMessage = from_value(payload.d)?;
+                    if self.config.channel_ids.contains(&message.channel_id) {
+                        return Ok(Some(DiscordEvent::MessageCreate(message)));
+                    }
+                }
+                Some("MESSAGE_DELETE") => {
+                    let message: DeletedMessage = from_value(payload.d)?;
+                    if self.config.channel_ids.contains(&message.channel_id) {
+                        return Ok(Some(DiscordEvent::MessageDelete(message)));
+                    }
+                }
+                _ => {}
+            },
+            opcode::HEARTBEAT => self.heartbeat()?,
+            opcode::HEARTBEAT_ACK => self.heartbeat_acked = true,
+            opcode::RECONNECT => self.reconnect(true)?,
+            opcode::INVALID_SESSION => {
+                thread::sleep(rand::thread_rng().gen_range(self.invalid_session_wait.clone()));
+                self.reconnect(payload.d.as_bool().unwrap_or(false))?
+            }
+            _ => {}
+        }
+        Ok(None)
+    }
+}
+
+/// Reads the next gateway payload, failing with the close frame if Discord closed the connection.
+fn read_payload(stream: &mut WebSocket<MaybeTlsStream<TcpStream>>) -> Result<GatewayPayload> {
+    loop {
+        match stream.read()? {
+            Message::Text(text) => return Ok(serde_json::from_str(&text)?),
+            Message::Close(frame) => {
+                let code = frame.as_ref().map(|frame| frame.code);
+                // Authentication failed, invalid intents... Reconnecting won't help with these
+                if let Some(CloseCode::Library(4004 | 4010..=4014)) = code {
+                    return Err(anyhow::Error::msg(format!("Discord closed the gateway: {:?}", frame)));
+                }
+                return Err(tungstenite::Error::ConnectionClosed.into());
+            }
+            _ => {}
+        }
+    }
+}
+
+pub struct DiscordRest {
+    config: Arc<DiscordConfig>,
+    client: Client,
+}
+
+impl DiscordRest {
+    pub fn new(config: Arc<DiscordConfig>) -> Self {
+        Self {
+            config,
+            client: Client::new(),
+        }
+    }
+
+    pub fn send_message(&self, channel_id: &str, content: &str) -> Result<()> {
+        self.client
+            .post(format!("{}/channels/{}/messages", self.config.api_url, channel_id))
+            .header("Authorization", format!("Bot {}", self.config.token))
+            .json(&json!({ "content": content }))
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+pub fn connector() -> Option<Box<dyn PlatformConnector>> {
+    let config = Arc::new(DiscordConfig::from_env()?);
+    Some(Box::new(DiscordConnector {
+        gateway: DiscordGateway::new(config.clone()),
+        rest: DiscordRest::new(config),
+    }))
+}
+
+/// Answers in the channel the message came from. Messages sent without a channel go to the first
+/// configured one.
+pub struct DiscordConnector {
+    gateway: DiscordGateway,
+    rest: DiscordRest,
+}
+
+impl PlatformConnector for DiscordConnector {
+    fn info(&self) -> PlatformInfo {
+        PlatformInfo {
+            platform: Platform::DISCORD,
+            label: "Discord",
+            color: [88, 101, 242],
+        }
+    }
+
+    fn connect(&mut self) -> Result<()> {
+        self.gateway.reconnect(true)
+    }
+
+    fn poll(&mut self) -> Result<Vec<IncomingEvent>> {
+        let message = match self.gateway.read()? {
+            Some(DiscordEvent::MessageCreate(message)) => message,
+            Some(DiscordEvent::MessageDelete(message)) => {
+                let event = PlatformEvent::MessageDeleted {
+                    platform: Platform::DISCORD,
+                    platform_id: message.id,
+                };
+                return Ok(vec![IncomingEvent::new(event, Some(message.channel_id))]);
+            }
+            None => return Ok(Vec::new()),
+        };
+        let is_bot = self.gateway.user_id.as_ref() == Some(&message.author.id);
+        // Other bots would only add noise
+        if message.author.bot && !is_bot {
+            return Ok(Vec::new());
+        }
+        let reply_to = Some(message.channel_id.clone());
+        Ok(vec![IncomingEvent::new(PlatformMessage { is_bot, ..message.into() }, reply_to)])
+    }
+
+    fn send_message(&mut self, channel_id: Option<&str>, msg: &str) -> Result<()> {
+        let channel_id = match channel_id {
+            Some(channel_id) => channel_id,
+            None => self
+                .rest
+                .config
+                .channel_ids
+                .first()
+                .ok_or(anyhow::Error::msg("DISCORD_CHANNEL_IDS to have at least one channel"))?,
+        };
+        self.rest.send_message(channel_id, msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use serde_json::from_str;
+    use tungstenite::accept;
+
+    use super::*;
+
+    fn expect(socket: &mut WebSocket<TcpStream>, op: u8) -> Value {
+        loop {
+            let frame = socket.read().unwrap();
+            let payload: Value = from_str(frame.to_text().unwrap()).unwrap();
+            // Heartbeats can come at any time
+            if payload["op"] == opcode::HEARTBEAT && op != opcode::HEARTBEAT {
+                continue;
+            }
+            assert_eq!(payload["op"], op, "{payload}");
+            return payload["d"].clone();
+        }
+    }
+
+    fn send(socket: &mut WebSocket<TcpStream>, payload: Value) {
+        socket.send(Message::Text(payload.to_string())).unwrap();
+    }
+
+    fn hello(listener: &TcpListener) -> WebSocket<TcpStream> {
+        let mut socket = accept(listener.accept().unwrap().0).unwrap();
+        send(&mut socket, json!({ "op": opcode::HELLO, "d": { "heartbeat_interval": 300 } }));
+        socket
+    }
+
+    fn message_create(seq: u64, id: &str, channel_id: &str) -> Value {
+        json!({ "op": opcode::DISPATCH, "s": seq, "t": "MESSAGE_CREATE", "d": {
+            "id": id,
+            "channel_id": channel_id,
+            "author": { "id": "42", "username": "viewer" },
+            "content": "!today",
+            "timestamp": "2024-01-01T00:00:00+00:00"
+        } })
+    }
+
+    #[test]
+    fn waits_before_identifying_after_an_invalid_session() {
+        for _ in 0..20 {
+            let wait = rand::thread_rng().gen_range(INVALID_SESSION_WAIT.clone());
+            assert!((Duration::from_secs(1)..=Duration::from_secs(5)).contains(&wait));
+        }
+    }
+
+    #[test]
+    fn identifies_heartbeats_and_resumes() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://127.0.0.1:{}", listener.local_addr().unwrap().port());
+        let resume_url = url.clone();
+        let server = thread::spawn(move || {
+            let mut socket = hello(&listener);
+            let identify = expect(&mut socket, opcode::IDENTIFY);
+            assert_eq!(identify["token"], "token");
+            assert_eq!(identify["intents"], INTENTS);
+            send(
+                &mut socket,
+                json!({ "op": opcode::DISPATCH, "s": 1, "t": "READY", "d": {
+                    "session_id": "session",
+                    "resume_gateway_url": resume_url,
+                    "user": { "id": "1", "username": "bot", "bot": true }
+                } }),
+            );
+            assert_eq!(expect(&mut socket, opcode::HEARTBEAT), 1);
+            send(&mut socket, json!({ "op": opcode::HEARTBEAT_ACK }));
+            send(&mut socket, message_create(2, "m1", "100"));
+            send(&mut socket, message_create(3, "m2", "other channel"));
+            send(&mut socket, json!({ "op": opcode::RECONNECT }));
+
+            let mut socket = hello(&listener);
+            let resume = expect(&mut socket, opcode::RESUME);
+            assert_eq!(resume, json!({ "token": "token", "session_id": "session", "seq": 3 }));
+            send(&mut socket, message_create(4, "m3", "100"));
+            send(&mut socket, json!({ "op": opcode::INVALID_SESSION, "d": false }));
+
+            // The session can't be resumed, so it starts over
+            let mut socket = hello(&listener);
+            expect(&mut socket, opcode::IDENTIFY);
+        });
+
+        let config = Arc::new(DiscordConfig {
+            gateway_url: url,
+            api_url: DEFAULT_DISCORD_API_URL.to_string(),
+            token: "token".to_string(),
+            channel_ids: vec!["100".to_string()],
+        });
+        let mut gateway = DiscordGateway::new(config.clone());
+        gateway.invalid_session_wait = Duration::ZERO..=Duration::ZERO;
+        let mut connector = DiscordConnector {
+            gateway,
+            rest: DiscordRest::new(config),
+        };
+        connector.connect().unwrap();
+        let mut ids = Vec::new();
+        // Fails once the server is gone and there is nothing to reconnect to
+        while let Ok(incoming) = connector.poll() {
+            for IncomingEvent { event, reply_to } in incoming {
+                let PlatformEvent::Chat(message) = event else {
+                    panic!("Unexpected event {event:?}");
+                };
+                assert_eq!(reply_to.as_deref(), Some("100"));
+                ids.push(message.platform_id.unwrap());
+            }
+        }
+        server.join().unwrap();
+
+        assert_eq!(ids, ["m1", "m3"]);
+        assert_eq!(connector.gateway.session_id, None);
+    }
+}
@@ -0,0 +1,382 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+
+use crate::{
+    command::{BotCommand, CommandHandler, COMMAND_SYMBOL},
+    commands_file::{CommandsFile, CURRENT_VERSION},
+    cooldown::Cooldown,
+    messages::ChatTarget,
+    permission::PermissionLevel,
+    store::COMMANDS_FILE,
+};
+
+static TITLE: &str = "Bot commands";
+
+/// What the commands can be exported as.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// Same as the commands file, so it can be put back as one
+    Json,
+    Csv,
+    /// A table for a README or a wiki
+    Markdown,
+    /// A page that needs nothing else to be published
+    Html,
+}
+
+impl Format {
+    pub const ALL: [Format; 4] = [Format::Json, Format::Csv, Format::Markdown, Format::Html];
+
+    /// Format by its name on the command line, like `html`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "json" => Some(Format::Json),
+            "csv" => Some(Format::Csv),
+            "markdown" | "md" => Some(Format::Markdown),
+            "html" => Some(Format::Html),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Format::Json => write!(f, "JSON"),
+            Format::Csv => write!(f, "CSV"),
+            Format::Markdown => write!(f, "Markdown"),
+            Format::Html => write!(f, "HTML"),
+        }
+    }
+}
+
+/// The commands `level` can use, in `format`.
+pub fn export<'a>(commands: impl Iterator<Item = &'a BotCommand>, format: Format, level: PermissionLevel) -> Result<String> {
+    let commands: Vec<&BotCommand> = commands.filter(|command| command.permission <= level).collect();
+    match format {
+        Format::Json => {
+            let file = CommandsFile {
+                version: CURRENT_VERSION,
+                commands: commands.into_iter().cloned().collect(),
+            };
+            Ok(serde_json::to_string_pretty(&file)?)
+        }
+        Format::Csv => csv(&commands),
+        Format::Markdown => Ok(markdown(&commands)),
+        Format::Html => Ok(html(&commands)),
+    }
+}
+
+/// Writes the commands `level` can use to `path`, which can't be the commands file itself.
+pub fn write(command_handler: &CommandHandler, format: Format, level: PermissionLevel, path: &str) -> Result<usize> {
+    if path.trim().is_empty() {
+        return Err(anyhow::Error::msg("Choose a file to export to"));
+    }
+    if same_file(path, COMMANDS_FILE) {
+        return Err(anyhow::Error::msg(format!(
+            "Exporting to {COMMANDS_FILE} would replace the commands"
+        )));
+    }
+    let exported = command_handler.commands().filter(|command| command.permission <= level).count();
+    fs::write(path, export(command_handler.commands(), format, level)?)
+        .map_err(|err| anyhow::Error::msg(format!("Cannot write {path}: {err}")))?;
+    Ok(exported)
+}
+
+/// `export <format> <file> [level]` on the command line. Without a level every command is exported.
+pub fn cli(command_handler: &CommandHandler, args: &[String]) -> Result<()> {
+    let usage = "Usage: ttv-bot export <json|csv|markdown|html> <file> [level]";
+    let (format, path, level) = match args {
+        [format, path] => (format, path, PermissionLevel::BotAdmin),
+        [format, path, level] => {
+            let Some(level) = PermissionLevel::parse(level) else {
+                return Err(anyhow::Error::msg(format!("Unknown level {level}. {usage}")));
+            };
+            (format, path, level)
+        }
+        _ => return Err(anyhow::Error::msg(usage)),
+    };
+    let Some(format) = Format::parse(format) else {
+        return Err(anyhow::Error::msg(format!("Unknown format {format}. {usage}")));
+    };
+    let exported = write(command_handler, format, level, path)?;
+    println!("Exported {exported} commands to {path}");
+    Ok(())
+}
+
+/// Whether both paths lead to the same file, even through `.`, `..` or symlinks. Files that don't
+/// exist yet are compared by their directory and name.
+fn same_file(a: &str, b: &str) -> bool {
+    fn resolve(path: &str) -> Option<PathBuf> {
+        if let Ok(path) = fs::canonicalize(path) {
+            return Some(path);
+        }
+        let path = Path::new(path);
+        let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        Some(fs::canonicalize(dir).ok()?.join(path.file_name()?))
+    }
+    match (resolve(a), resolve(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => Path::new(a) == Path::new(b),
+    }
+}
+
+fn csv(commands: &[&BotCommand]) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record([
+        "name",
+        "response",
+        "description",
+        "aliases",
+        "permission",
+        "available_in",
+        "cooldown",
+        "user_cooldown",
+        "count",
+        "overrides",
+    ])?;
+    for command in commands {
+        writer.write_record([
+            command.name.clone(),
+            command.contents.clone(),
+            command.description.clone(),
+            command.aliases.join(", "),
+            command.permission.to_string(),
+            available_in(command),
+            command.cooldown.global.to_string(),
+            command.cooldown.per_user.to_string(),
+            command.count.to_string(),
+            overrides(command).join("\n"),
+        ])?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+fn markdown(commands: &[&BotCommand]) -> String {
+    // Pipes end a cell and line breaks the row
+    let cell = |text: &str| text.replace('|', "\\|").replace(['\r', '\n'], " ");
+    let mut page = format!(
+        "# {TITLE}\n\n| Command | Description | Aliases | Who can use it | Where | Cooldown | Response |\n|---|---|---|---|---|---|---|\n"
+    );
+    for command in commands {
+        let responses: Vec<String> = responses(command).iter().map(|response| cell(response)).collect();
+        page.push_str(&format!(
+            "| {} | {} | {} | {} | {} | {} | {} |\n",
+            cell(&format!("{COMMAND_SYMBOL}{}", command.name)),
+            cell(&command.description),
+            cell(&aliases(command)),
+            command.permission,
+            cell(&available_in(command)),
+            cooldown(&command.cooldown),
+            responses.join("<br>"),
+        ));
+    }
+    page
+}
+
+fn html(commands: &[&BotCommand]) -> String {
+    let mut rows = String::new();
+    for command in commands {
+        let responses: Vec<String> = responses(command).iter().map(|response| escape_html(response)).collect();
+        rows.push_str(&format!(
+            "<tr><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            escape_html(&format!("{COMMAND_SYMBOL}{}", command.name)),
+            escape_html(&command.description),
+            escape_html(&aliases(command)),
+            command.permission,
+            escape_html(&available_in(command)),
+            cooldown(&command.cooldown),
+            responses.join("<br>"),
+        ));
+    }
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{TITLE}</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em auto; max-width: 60em; padding: 0 1em; }}
+table {{ border-collapse: collapse; width: 100%; }}
+th, td {{ border-bottom: 1px solid #ddd; padding: 0.5em; text-align: left; vertical-align: top; }}
+th {{ background: #f4f4f4; }}
+td:last-child {{ white-space: pre-wrap; }}
+</style>
+</head>
+<body>
+<h1>{TITLE}</h1>
+<table>
+<tr><th>Command</th><th>Description</th><th>Aliases</th><th>Who can use it</th><th>Where</th><th>Cooldown</th><th>Response</th></tr>
+{rows}</table>
+</body>
+</html>
+"#
+    )
+}
+
+fn aliases(command: &BotCommand) -> String {
+    let aliases: Vec<String> = command.aliases.iter().map(|alias| format!("{COMMAND_SYMBOL}{alias}")).collect();
+    aliases.join(", ")
+}
+
+/// Platforms and channels the command works in.
+fn available_in(command: &BotCommand) -> String {
+    match command.scope.is_empty() {
+        true => "everywhere".to_string(),
+        false => command.scope.iter().map(ChatTarget::to_string).collect::<Vec<String>>().join(", "),
+    }
+}
+
+/// `platform: response` for each platform answered differently.
+fn overrides(command: &BotCommand) -> Vec<String> {
+    command
+        .overrides
+        .iter()
+        .map(|(platform, contents)| format!("{platform}: {contents}"))
+        .collect()
+}
+
+/// The response followed by the ones for specific platforms.
+fn responses(command: &BotCommand) -> Vec<String> {
+    let mut responses = vec![command.contents.clone()];
+    responses.extend(overrides(command));
+    responses
+}
+
+fn cooldown(cooldown: &Cooldown) -> String {
+    match (cooldown.global, cooldown.per_user) {
+        (0, 0) => "none".to_string(),
+        (global, 0) => format!("{global}s"),
+        (0, per_user) => format!("{per_user}s per user"),
+        (global, per_user) => format!("{global}s, {per_user}s per user"),
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+    use crate::{commands_file, store::MemoryStore};
+
+    fn commands() -> Vec<BotCommand> {
+        vec![
+            BotCommand {
+                name: "discord".to_string(),
+                contents: "Join at discord.gg/abc | <3".to_string(),
+                description: "Our \"server\"".to_string(),
+                aliases: vec!["dc".to_string()],
+                cooldown: Cooldown {
+                    global: 30,
+                    per_user: 0,
+                    notify: false,
+                },
+                scope: vec![ChatTarget::parse("twitch"), ChatTarget::parse("discord:general")],
+                overrides: [("discord".to_string(), "See #links".to_string())].into(),
+                ..Default::default()
+            },
+            BotCommand {
+                name: "ban".to_string(),
+                contents: "Banned".to_string(),
+                permission: PermissionLevel::Moderator,
+                ..Default::default()
+            },
+        ]
+    }
+
+    fn exported(format: Format, level: PermissionLevel) -> String {
+        export(commands().iter(), format, level).unwrap()
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("ttv-bot-{}-{name}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn json_is_a_commands_file() {
+        let json = exported(Format::Json, PermissionLevel::BotAdmin);
+        let (commands, version) = commands_file::parse("export.json", &json).unwrap();
+        assert_eq!(version, CURRENT_VERSION);
+        assert_eq!(commands, self::commands());
+        let json = exported(Format::Json, PermissionLevel::Everyone);
+        assert_eq!(commands_file::parse("export.json", &json).unwrap().0.len(), 1);
+    }
+
+    #[test]
+    fn csv_has_a_row_per_command() {
+        let csv = exported(Format::Csv, PermissionLevel::BotAdmin);
+        let mut reader = csv::Reader::from_reader(csv.as_bytes());
+        let headers = reader.headers().unwrap().clone();
+        let rows: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+        assert_eq!(rows.len(), 2);
+        let field = |name: &str| rows[0].get(headers.iter().position(|header| header == name).unwrap()).unwrap();
+        assert_eq!(field("name"), "discord");
+        assert_eq!(field("response"), "Join at discord.gg/abc | <3");
+        assert_eq!(field("aliases"), "dc");
+        assert_eq!(field("available_in"), "twitch, discord:general");
+        assert_eq!(field("cooldown"), "30");
+        assert_eq!(field("overrides"), "discord: See #links");
+    }
+
+    #[test]
+    fn markdown_escapes_cells() {
+        let markdown = exported(Format::Markdown, PermissionLevel::Everyone);
+        assert!(markdown.starts_with("# Bot commands\n"));
+        assert!(markdown.contains(
+            "| !discord | Our \"server\" | !dc | everyone | twitch, discord:general | 30s | Join at discord.gg/abc \\| <3<br>discord: See #links |"
+        ));
+        assert!(!markdown.contains("!ban"));
+    }
+
+    #[test]
+    fn html_escapes_everything() {
+        let html = exported(Format::Html, PermissionLevel::Moderator);
+        assert!(html.contains(
+            "<tr><td><code>!discord</code></td><td>Our &quot;server&quot;</td><td>!dc</td><td>everyone</td><td>twitch, discord:general</td><td>30s</td><td>Join at discord.gg/abc | &lt;3<br>discord: See #links</td></tr>"
+        ));
+        assert!(html.contains("<td>moderator</td><td>everywhere</td><td>none</td><td>Banned</td>"));
+    }
+
+    #[test]
+    fn never_exports_over_the_commands_file() {
+        let handler = CommandHandler::new(Box::new(MemoryStore::default())).unwrap();
+        for path in [COMMANDS_FILE, "./commands.json", "src/../commands.json"] {
+            assert!(write(&handler, Format::Json, PermissionLevel::BotAdmin, path).is_err(), "{path}");
+        }
+
+        let dir = test_dir("export");
+        let live = dir.join("commands.json");
+        let live = live.to_str().unwrap();
+        assert!(same_file(live, &format!("{}/./commands.json", dir.display())));
+        assert!(!same_file(live, dir.join("export.json").to_str().unwrap()));
+        fs::write(live, "{}").unwrap();
+        #[cfg(unix)]
+        {
+            let link = dir.join("link.json");
+            std::os::unix::fs::symlink(live, &link).unwrap();
+            assert!(same_file(live, link.to_str().unwrap()));
+        }
+
+        let path = dir.join("export.md");
+        assert_eq!(
+            write(&handler, Format::Markdown, PermissionLevel::BotAdmin, path.to_str().unwrap()).unwrap(),
+            0
+        );
+        assert!(fs::read_to_string(path).unwrap().starts_with("# Bot commands"));
+    }
+}
@@ -0,0 +1,59 @@
+//! An HTTP server answering with canned responses, to test the clients of platform APIs.
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    sync::mpsc::{channel, Receiver},
+    thread::{self, JoinHandle},
+};
+
+use serde_json::Value;
+
+pub struct FakeResponse {
+    pub status: u16,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Value,
+}
+
+pub fn ok(body: Value) -> FakeResponse {
+    FakeResponse {
+        status: 200,
+        headers: Vec::new(),
+        body,
+    }
+}
+
+/// Answers each request with the next response and hands out the request lines. Returns the URL
+/// of the server, without a trailing slash.
+pub fn serve(responses: Vec<FakeResponse>) -> (String, Receiver<String>, JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://127.0.0.1:{}", listener.local_addr().unwrap().port());
+    let (requests, received) = channel();
+    let server = thread::spawn(move || {
+        for response in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request = String::new();
+            reader.read_line(&mut request).unwrap();
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+            }
+            requests.send(request.trim().to_string()).unwrap();
+            let body = response.body.to_string();
+            let mut head = format!(
+                "HTTP/1.1 {} Fake\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+                response.status,
+                body.len()
+            );
+            for (name, value) in response.headers {
+                head.push_str(&format!("{name}: {value}\r\n"));
+            }
+            stream.write_all(format!("{head}\r\n{body}").as_bytes()).unwrap();
+        }
+    });
+    (url, received, server)
+}
@@ -1,24 +1,37 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
 use std::{
+    collections::{HashMap, HashSet},
     sync::{Arc, Mutex},
     time::{Duration, SystemTime},
 };
 
+use chrono::Local;
 use eframe::egui;
 use egui::{
-    include_image, scroll_area::ScrollBarVisibility, Button, Color32, FontFamily, FontId, Image, Label, RichText, ScrollArea, Separator,
-    TextEdit, ViewportBuilder,
+    include_image, scroll_area::ScrollBarVisibility, text::LayoutJob, Align, Button, Color32, FontFamily, FontId, FontSelection, Image,
+    Label, RichText, ScrollArea, Separator, TextEdit, ViewportBuilder,
 };
 
-use tokio::sync::mpsc::UnboundedReceiver;
-
 use crate::{
-    command::{BotCommand, CommandHandler},
-    messages::{Platform, PlatformMessage},
+    bus::{Received, Subscription},
+    command::{BotCommand, CommandHandler, CommandsNotice},
+    connector::{ConnectorStatus, Registry},
+    export::{self, Format},
+    import::{self, Source},
+    messages::{ChatTarget, Platform, PlatformEvent, PlatformMessage},
+    moderation::ModerationAction,
+    permission::PermissionLevel,
+    timer::{Timer, Timers},
+    trigger::Trigger,
 };
 
-pub fn run(command_handler: Arc<Mutex<CommandHandler>>, receiver: UnboundedReceiver<PlatformMessage>) -> Result<(), eframe::Error> {
+pub fn run(
+    command_handler: Arc<Mutex<CommandHandler>>,
+    registry: Arc<Registry>,
+    timers: Arc<Mutex<Timers>>,
+    subscription: Subscription,
+) -> Result<(), eframe::Error> {
     let options = eframe::NativeOptions {
         viewport: ViewportBuilder::default()
             .with_inner_size([800.0, 600.0])
@@ -31,7 +44,7 @@ pub fn run(command_handler: Arc<Mutex<CommandHandler>>, receiver: UnboundedRecei
         options,
         Box::new(|cc| {
             egui_extras::install_image_loaders(&cc.egui_ctx);
-            Box::<OmniChatter>::new(OmniChatter::new(command_handler, receiver))
+            Box::<OmniChatter>::new(OmniChatter::new(command_handler, registry, timers, subscription))
         }),
     )
 }
@@ -83,39 +96,101 @@ enum State {
     Idle,
     ChatFullScreen,
     Config,
+    Timers,
 }
 
 struct OmniChatter {
     command_handler: Arc<Mutex<CommandHandler>>,
+    registry: Arc<Registry>,
+    timers: Arc<Mutex<Timers>>,
     current_command: BotCommand,
+    /// The current command as it was opened or last saved, to tell whether it has unsaved edits
+    saved_command: BotCommand,
+    /// Aliases of the current command being edited, comma separated
+    aliases_input: String,
+    /// Where the current command works, comma separated `platform` or `platform:channel`
+    scope_input: String,
+    /// Platform whose response is being edited, `None` for the one of every platform
+    command_tab: Option<Platform>,
+    /// Sample chat message the triggers of the current command are tested against
+    trigger_test: String,
     toasts: Vec<Toast>,
     state: State,
     command_search: String,
-    receiver: UnboundedReceiver<PlatformMessage>,
-    platform_messages: Vec<PlatformMessage>,
+    subscription: Subscription,
+    platform_events: Vec<PlatformEvent>,
+    /// Platform ids of the messages deleted on their platform
+    deleted_messages: HashSet<(Platform, String)>,
     scrolling_chat: bool,
+    chat_input: String,
     text_size: f32,
+    /// Timer being created or edited in the timers tab
+    current_timer: Timer,
+    /// Name the edited timer had when it was opened, `None` for a new one
+    editing_timer: Option<String>,
+    /// Targets of the current timer, comma separated `platform` or `platform:channel`
+    timer_targets_input: String,
+    /// The import commands window is open
+    import_open: bool,
+    import_source: Source,
+    /// File with the commands exported from `import_source`
+    import_path: String,
+    /// What the last import did
+    import_report: Vec<String>,
+    export_format: Format,
+    /// Highest level of the commands exported
+    export_level: PermissionLevel,
+    export_path: String,
 }
 
 impl OmniChatter {
-    fn new(command_handler: Arc<Mutex<CommandHandler>>, receiver: UnboundedReceiver<PlatformMessage>) -> Self {
+    fn new(
+        command_handler: Arc<Mutex<CommandHandler>>,
+        registry: Arc<Registry>,
+        timers: Arc<Mutex<Timers>>,
+        subscription: Subscription,
+    ) -> Self {
         Self {
             command_search: "".to_string(),
             command_handler,
-            current_command: BotCommand {
-                name: String::new(),
-                contents: String::new(),
-            },
+            registry,
+            timers,
+            current_command: BotCommand::default(),
+            saved_command: BotCommand::default(),
+            aliases_input: String::new(),
+            scope_input: String::new(),
+            command_tab: None,
+            trigger_test: String::new(),
             toasts: Vec::new(),
             state: State::Idle,
-            receiver,
-            platform_messages: Vec::new(),
+            subscription,
+            platform_events: Vec::new(),
+            deleted_messages: HashSet::new(),
             scrolling_chat: false,
+            chat_input: String::new(),
             text_size: 12.,
+            current_timer: Timer::default(),
+            editing_timer: None,
+            timer_targets_input: String::new(),
+            import_open: false,
+            import_source: Source::Nightbot,
+            import_path: String::new(),
+            import_report: Vec::new(),
+            export_format: Format::Html,
+            export_level: PermissionLevel::Everyone,
+            export_path: String::new(),
         }
     }
 }
 
+impl OmniChatter {
+    fn has_unsaved_edits(&self) -> bool {
+        self.current_command != self.saved_command
+            || self.aliases_input != self.saved_command.aliases.join(", ")
+            || self.scope_input != targets_input(&self.saved_command.scope)
+    }
+}
+
 impl eframe::App for OmniChatter {
     // Called each time the UI needs repainting, which may be many times per second.
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
@@ -128,17 +203,73 @@ impl eframe::App for OmniChatter {
             family: FontFamily::Proportional,
         };
 
-        match self.receiver.try_recv() {
-            Ok(msg) => {
-                self.platform_messages.push(msg);
+        while let Some(received) = self.subscription.try_recv() {
+            match received {
+                Received::Event(PlatformEvent::MessageDeleted { platform, platform_id }) => {
+                    self.deleted_messages.insert((platform, platform_id));
+                }
+                Received::Event(event) => self.platform_events.push(event),
+                Received::Lagged(missed) => self.toasts.push(Toast::new(
+                    Duration::from_secs_f32(2.5),
+                    format!("Chat fell behind, {missed} events were skipped"),
+                    ToastLevel::Warn,
+                    font_id.clone(),
+                )),
             }
-            Err(err) => match err {
-                tokio::sync::mpsc::error::TryRecvError::Empty => {}
-                tokio::sync::mpsc::error::TryRecvError::Disconnected => {
-                    eprintln!("ERROR - should not happen");
+        }
+
+        for notice in command_handler.take_notices() {
+            let toast = match notice {
+                CommandsNotice::Invalid(err) => Toast::new(
+                    Duration::from_secs(5),
+                    format!("Commands changed on disk but can't be loaded: {err}"),
+                    ToastLevel::Error,
+                    font_id.clone(),
+                ),
+                CommandsNotice::Reloaded(changed) => {
+                    let name = self.current_command.name.clone();
+                    let editing = matches!(self.state, State::DisplayCommand) && changed.contains(&name);
+                    if !editing {
+                        Toast::new(
+                            Duration::from_secs_f32(2.5),
+                            format!("Commands changed on disk: {}", changed.join(", ")),
+                            ToastLevel::Info,
+                            font_id.clone(),
+                        )
+                    } else if self.has_unsaved_edits() {
+                        // Keep the edits, updating will overwrite what changed on disk
+                        Toast::new(
+                            Duration::from_secs(5),
+                            format!("Command {name} changed on disk while you were editing it, Update will overwrite it"),
+                            ToastLevel::Warn,
+                            font_id.clone(),
+                        )
+                    } else if command_handler.resolve(&name).as_ref() == Some(&name) {
+                        self.current_command = command_handler.get_command(&name);
+                        self.saved_command = self.current_command.clone();
+                        self.aliases_input = self.current_command.aliases.join(", ");
+                        self.scope_input = targets_input(&self.current_command.scope);
+                        Toast::new(
+                            Duration::from_secs_f32(2.5),
+                            format!("Commands changed on disk: {}", changed.join(", ")),
+                            ToastLevel::Info,
+                            font_id.clone(),
+                        )
+                    } else {
+                        self.state = State::Idle;
+                        Toast::new(
+                            Duration::from_secs_f32(2.5),
+                            format!("Command {name} was removed on disk"),
+                            ToastLevel::Warn,
+                            font_id.clone(),
+                        )
+                    }
                 }
-            },
-        };
+            };
+            self.toasts.push(toast);
+        }
+
+        let mut moderation_request: Option<(Platform, ModerationAction)> = None;
 
         if let State::ChatFullScreen = self.state {
             egui::CentralPanel::default().show(ctx, |ui| {
@@ -150,28 +281,16 @@ impl eframe::App for OmniChatter {
                         _ => State::ChatFullScreen,
                     };
                 }
-                ScrollArea::vertical()
-                    .scroll_bar_visibility(ScrollBarVisibility::AlwaysHidden)
-                    .show(ui, |ui| {
-                        for PlatformMessage { msg, platform, sender } in &self.platform_messages {
-                            ui.horizontal(|ui| {
-                                let color = match platform {
-                                    Platform::Youtube => Color32::RED,
-                                    Platform::Twitch => Color32::from_rgb(191, 148, 255),
-                                };
-                                ui.label(RichText::new(" ").font(font_id.clone()).background_color(color));
-                                let tmp =
-                                    ui.add(Label::new(RichText::new(format!("[{}] {}", sender, msg)).font(font_id.clone())).wrap(true));
-                                if !self.scrolling_chat {
-                                    tmp.scroll_to_me(None);
-                                }
-                            });
-                        }
-                    });
-                if self.scrolling_chat {
-                    if ui.button(RichText::new("resume scrolling").font(font_id.clone())).clicked() {
-                        self.scrolling_chat = true
-                    }
+                moderation_request = show_chat(
+                    ui,
+                    &self.platform_events,
+                    &self.deleted_messages,
+                    &font_id,
+                    self.scrolling_chat,
+                    &self.registry,
+                );
+                if self.scrolling_chat && ui.button(RichText::new("resume scrolling").font(font_id.clone())).clicked() {
+                    self.scrolling_chat = true
                 }
             });
         } else {
@@ -191,29 +310,50 @@ impl eframe::App for OmniChatter {
             //  -------------------------------------------
             egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
                 ui.spacing_mut().item_spacing.y = 20.0;
-                ui.label(RichText::new("Omnichatter").heading().font(font_id.clone()).strong())
+                ui.label(RichText::new("Omnichatter").heading().font(font_id.clone()).strong());
+                ui.horizontal(|ui| {
+                    for handle in self.registry.handles() {
+                        let status = handle.status();
+                        let color = match status {
+                            ConnectorStatus::Connecting => Color32::LIGHT_YELLOW,
+                            ConnectorStatus::Connected => Color32::LIGHT_GREEN,
+                            ConnectorStatus::Failed(_) => Color32::LIGHT_RED,
+                        };
+                        ui.label(RichText::new(handle.info.label).color(color).font(font_id.clone()))
+                            .on_hover_text(status.to_string());
+                    }
+                });
             });
 
             egui::SidePanel::left("left_panel").resizable(false).show(ctx, |ui| {
                 ui.spacing_mut().item_spacing.y = 5.0;
                 ui.add(TextEdit::multiline(&mut self.command_search).font(font_id.clone()));
                 for command_name in command_handler.get_command_names() {
-                    if self.command_search.len() == 0 || command_name.starts_with(&self.command_search) {
-                        if ui.button(RichText::new(&*command_name).font(font_id.clone())).clicked() {
-                            self.command_search = String::new();
-                            self.current_command = command_handler.get_command(&command_name);
-                            self.state = State::DisplayCommand;
-                        };
+                    if (self.command_search.is_empty() || command_name.starts_with(&self.command_search))
+                        && ui.button(RichText::new(&*command_name).font(font_id.clone())).clicked()
+                    {
+                        self.command_search = String::new();
+                        self.current_command = command_handler.get_command(&command_name);
+                        self.saved_command = self.current_command.clone();
+                        self.aliases_input = self.current_command.aliases.join(", ");
+                        self.scope_input = targets_input(&self.current_command.scope);
+                        self.command_tab = None;
+                        self.state = State::DisplayCommand;
                     };
                 }
                 ui.add(Separator::default().horizontal());
                 if ui.button(RichText::new("Create command").font(font_id.clone())).clicked() {
-                    self.current_command = BotCommand {
-                        name: String::new(),
-                        contents: String::new(),
-                    };
+                    self.current_command = BotCommand::default();
+                    self.aliases_input = String::new();
+                    self.scope_input = String::new();
                     self.state = State::CreateCommand;
                 };
+                if ui.button(RichText::new("Import commands").font(font_id.clone())).clicked() {
+                    self.import_open = true;
+                };
+                if ui.button(RichText::new("Timers").font(font_id.clone())).clicked() {
+                    self.state = State::Timers;
+                };
                 let config_button = Button::image(
                     Image::new(include_image!("../images/gear.png"))
                         .rounding(5.)
@@ -231,40 +371,276 @@ impl eframe::App for OmniChatter {
                 ui.scope(|ui| match self.state {
                     State::Config => {
                         ui.add(egui::Slider::new(&mut self.text_size, (10.)..=40.).text(RichText::new("Font size").font(font_id.clone())));
+                        ui.add(Separator::default().horizontal());
+                        ui.strong(RichText::new("Export commands").font(font_id.clone()));
+                        ui.horizontal(|ui| {
+                            egui::ComboBox::from_id_source("export_format")
+                                .selected_text(RichText::new(self.export_format.to_string()).font(font_id.clone()))
+                                .show_ui(ui, |ui| {
+                                    for format in Format::ALL {
+                                        ui.selectable_value(&mut self.export_format, format, format.to_string());
+                                    }
+                                });
+                            ui.label(RichText::new("usable by:").font(font_id.clone()));
+                            egui::ComboBox::from_id_source("export_level")
+                                .selected_text(RichText::new(self.export_level.to_string()).font(font_id.clone()))
+                                .show_ui(ui, |ui| {
+                                    for level in PermissionLevel::ALL {
+                                        ui.selectable_value(&mut self.export_level, level, level.to_string());
+                                    }
+                                });
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label(RichText::new("file:").font(font_id.clone()));
+                            ui.add(TextEdit::singleline(&mut self.export_path).font(font_id.clone()));
+                            if ui.button(RichText::new("Export").font(font_id.clone())).clicked() {
+                                let path = self.export_path.trim();
+                                let toast = match export::write(&command_handler, self.export_format, self.export_level, path) {
+                                    Ok(exported) => Toast::new(
+                                        Duration::from_secs_f32(2.5),
+                                        format!("Exported {exported} commands to {path}"),
+                                        ToastLevel::Success,
+                                        font_id.clone(),
+                                    ),
+                                    Err(err) => Toast::new(
+                                        Duration::from_secs_f32(2.5),
+                                        format!("Error exporting commands: {err}"),
+                                        ToastLevel::Error,
+                                        font_id.clone(),
+                                    ),
+                                };
+                                self.toasts.push(toast);
+                            }
+                        });
+                        ui.add(Separator::default().horizontal());
+                        ui.strong(RichText::new("Command backups").font(font_id.clone()));
+                        let backups = command_handler.backups();
+                        if backups.is_empty() {
+                            ui.label(RichText::new("No backups yet").font(font_id.clone()));
+                        }
+                        for backup in backups {
+                            ui.horizontal(|ui| {
+                                let modified: chrono::DateTime<Local> = backup.modified.into();
+                                ui.label(RichText::new(modified.format("%Y-%m-%d %H:%M:%S").to_string()).font(font_id.clone()))
+                                    .on_hover_text(&backup.path);
+                                if ui.button(RichText::new("Restore").font(font_id.clone())).clicked() {
+                                    let toast = match command_handler.restore_backup(&backup) {
+                                        Ok(_) => Toast::new(
+                                            Duration::from_secs_f32(2.5),
+                                            format!("Commands restored from {}", backup.path),
+                                            ToastLevel::Success,
+                                            font_id.clone(),
+                                        ),
+                                        Err(err) => Toast::new(
+                                            Duration::from_secs_f32(2.5),
+                                            format!("Error restoring commands: {err}"),
+                                            ToastLevel::Error,
+                                            font_id.clone(),
+                                        ),
+                                    };
+                                    self.toasts.push(toast);
+                                }
+                            });
+                        }
                     }
                     State::DisplayCommand => {
                         ui.strong(RichText::new(&self.current_command.name).font(font_id.clone()));
                         let available_rect = ctx.available_rect();
-                        let command_contents = TextEdit::multiline(&mut self.current_command.contents)
-                            .font(font_id.clone())
-                            .min_size([(available_rect.right() / 10.) * 7., available_rect.bottom() / 10.].into());
-                        ui.add(command_contents);
+                        let contents_size = [(available_rect.right() / 10.) * 7., available_rect.bottom() / 10.];
+                        // Connected platforms, and any other one the command has a response for
+                        let mut platforms: Vec<Platform> =
+                            self.registry.handles().iter().map(|handle| handle.info.platform.clone()).collect();
+                        for id in self.current_command.overrides.keys() {
+                            let platform = Platform::from(id.as_str());
+                            if !platforms.contains(&platform) {
+                                platforms.push(platform);
+                            }
+                        }
+                        ui.horizontal(|ui| {
+                            ui.selectable_value(&mut self.command_tab, None, RichText::new("All platforms").font(font_id.clone()));
+                            for platform in &platforms {
+                                let label = RichText::new(platform_label(&self.registry, platform)).font(font_id.clone());
+                                ui.selectable_value(&mut self.command_tab, Some(platform.clone()), label);
+                            }
+                        });
+                        match self.command_tab.clone() {
+                            None => {
+                                let command_contents = TextEdit::multiline(&mut self.current_command.contents)
+                                    .font(font_id.clone())
+                                    .min_size(contents_size.into());
+                                ui.add(command_contents);
+                                ui.horizontal(|ui| {
+                                    ui.label(RichText::new("only on:").font(font_id.clone()));
+                                    ui.add(
+                                        TextEdit::singleline(&mut self.scope_input)
+                                            .hint_text("twitch, discord:1234 (empty for everywhere)")
+                                            .font(font_id.clone()),
+                                    );
+                                });
+                            }
+                            Some(platform) => {
+                                let label = platform_label(&self.registry, &platform);
+                                let scope = parse_targets(&self.scope_input);
+                                if !scope.is_empty() && !scope.iter().any(|target| target.platform == platform) {
+                                    ui.label(
+                                        RichText::new(format!("Not available on {label}, see \"only on\" in All platforms"))
+                                            .color(Color32::LIGHT_YELLOW)
+                                            .font(font_id.clone()),
+                                    );
+                                }
+                                let mut overridden = self.current_command.overrides.contains_key(platform.id());
+                                let checkbox = ui.checkbox(
+                                    &mut overridden,
+                                    RichText::new(format!("Different response on {label}")).font(font_id.clone()),
+                                );
+                                if checkbox.changed() {
+                                    match overridden {
+                                        true => {
+                                            let contents = self.current_command.contents.clone();
+                                            self.current_command.overrides.insert(platform.id().to_string(), contents);
+                                        }
+                                        false => {
+                                            self.current_command.overrides.remove(platform.id());
+                                        }
+                                    }
+                                }
+                                match self.current_command.overrides.get_mut(platform.id()) {
+                                    Some(contents) => {
+                                        ui.add(TextEdit::multiline(contents).font(font_id.clone()).min_size(contents_size.into()));
+                                    }
+                                    // The response of every platform, which is the one used here
+                                    None => {
+                                        let mut contents = self.current_command.contents.clone();
+                                        let contents = TextEdit::multiline(&mut contents)
+                                            .font(font_id.clone())
+                                            .min_size(contents_size.into());
+                                        ui.add_enabled(false, contents);
+                                    }
+                                }
+                            }
+                        }
+                        ui.horizontal(|ui| {
+                            ui.label(RichText::new("description:").font(font_id.clone()))
+                                .on_hover_text("What !help tells about the command");
+                            ui.add(TextEdit::singleline(&mut self.current_command.description).font(font_id.clone()));
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label(RichText::new("aliases:").font(font_id.clone()));
+                            ui.add(TextEdit::singleline(&mut self.aliases_input).font(font_id.clone()));
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label(RichText::new("count:").font(font_id.clone()));
+                            ui.add(egui::DragValue::new(&mut self.current_command.count));
+                        });
+                        ui.horizontal(|ui| {
+                            let cooldown = &mut self.current_command.cooldown;
+                            ui.label(RichText::new("cooldown (s):").font(font_id.clone()));
+                            ui.add(egui::DragValue::new(&mut cooldown.global));
+                            ui.label(RichText::new("per user (s):").font(font_id.clone()));
+                            ui.add(egui::DragValue::new(&mut cooldown.per_user));
+                            ui.checkbox(&mut cooldown.notify, RichText::new("answer when on cooldown").font(font_id.clone()));
+                        });
+                        ui.label(RichText::new("triggers:").font(font_id.clone()));
+                        let mut removed_trigger = None;
+                        for (i, trigger) in self.current_command.triggers.iter_mut().enumerate() {
+                            ui.horizontal(|ui| {
+                                ui.add(TextEdit::singleline(&mut trigger.pattern).font(font_id.clone()));
+                                ui.checkbox(&mut trigger.regex, RichText::new("regex").font(font_id.clone()));
+                                if !trigger.regex {
+                                    ui.checkbox(&mut trigger.substring, RichText::new("inside words").font(font_id.clone()));
+                                }
+                                ui.checkbox(&mut trigger.ignore_bot, RichText::new("ignore bot").font(font_id.clone()));
+                                if ui.button(RichText::new("Remove").font(font_id.clone())).clicked() {
+                                    removed_trigger = Some(i);
+                                }
+                            });
+                        }
+                        if let Some(i) = removed_trigger {
+                            self.current_command.triggers.remove(i);
+                        }
+                        ui.horizontal(|ui| {
+                            if ui.button(RichText::new("Add trigger").font(font_id.clone())).clicked() {
+                                self.current_command.triggers.push(Trigger::default());
+                            }
+                            ui.add(
+                                TextEdit::singleline(&mut self.trigger_test)
+                                    .hint_text("Sample chat message")
+                                    .font(font_id.clone()),
+                            );
+                            if !self.trigger_test.is_empty() {
+                                let mut regexes = HashMap::new();
+                                let triggers = &self.current_command.triggers;
+                                let result = match triggers.iter().find_map(|trigger| trigger.validate().err()) {
+                                    Some(err) => RichText::new(err.to_string()).color(Color32::LIGHT_RED),
+                                    None => match triggers.iter().find(|trigger| trigger.is_match(&self.trigger_test, &mut regexes)) {
+                                        Some(trigger) => {
+                                            RichText::new(format!("Fires with {}", trigger.pattern)).color(Color32::LIGHT_GREEN)
+                                        }
+                                        None => RichText::new("Does not fire"),
+                                    },
+                                };
+                                ui.label(result.font(font_id.clone()));
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label(RichText::new("allowed for:").font(font_id.clone()));
+                            egui::ComboBox::from_id_source("command_permission")
+                                .selected_text(RichText::new(self.current_command.permission.to_string()).font(font_id.clone()))
+                                .show_ui(ui, |ui| {
+                                    for level in PermissionLevel::ALL {
+                                        ui.selectable_value(&mut self.current_command.permission, level, level.to_string());
+                                    }
+                                });
+                        });
                         ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
                             if ui.button(RichText::new("Update").font(font_id.clone())).clicked() {
-                                command_handler
-                                    .update_command_content(&self.current_command.name, &self.current_command.contents)
-                                    .expect("Command to exist");
-                                self.toasts.push(Toast::new(
-                                    Duration::from_secs_f32(1.25),
-                                    format!("Command {} updated successfully", self.current_command.name),
-                                    ToastLevel::Success,
-                                    font_id.clone(),
-                                ))
+                                self.current_command.aliases = self
+                                    .aliases_input
+                                    .split(',')
+                                    .map(|alias| alias.trim().to_string())
+                                    .filter(|alias| !alias.is_empty())
+                                    .collect();
+                                self.current_command.scope = parse_targets(&self.scope_input);
+                                let toast = match command_handler.update_command(&self.current_command) {
+                                    Ok(_) => {
+                                        self.saved_command = self.current_command.clone();
+                                        Toast::new(
+                                            Duration::from_secs_f32(1.25),
+                                            format!("Command {} updated successfully", self.current_command.name),
+                                            ToastLevel::Success,
+                                            font_id.clone(),
+                                        )
+                                    }
+                                    Err(err) => Toast::new(
+                                        Duration::from_secs_f32(2.5),
+                                        format!("Error updating command: {err}"),
+                                        ToastLevel::Error,
+                                        font_id.clone(),
+                                    ),
+                                };
+                                self.toasts.push(toast);
                             }
                             if ui
                                 .add(Button::new(RichText::new("Delete").font(font_id.clone())).fill(Color32::from_rgb(94, 25, 25)))
                                 .clicked()
                             {
-                                command_handler
-                                    .delete_command(&self.current_command.name)
-                                    .expect("Command to be deleted");
-                                self.toasts.push(Toast::new(
-                                    Duration::from_secs_f32(1.25),
-                                    format!("Command {} deleted successfully", self.current_command.name),
-                                    ToastLevel::Success,
-                                    font_id.clone(),
-                                ));
-                                self.state = State::Idle;
+                                match command_handler.delete_command(&self.current_command.name) {
+                                    Ok(_) => {
+                                        self.toasts.push(Toast::new(
+                                            Duration::from_secs_f32(1.25),
+                                            format!("Command {} deleted successfully", self.current_command.name),
+                                            ToastLevel::Success,
+                                            font_id.clone(),
+                                        ));
+                                        self.state = State::Idle;
+                                    }
+                                    Err(err) => self.toasts.push(Toast::new(
+                                        Duration::from_secs_f32(2.5),
+                                        format!("Error deleting command: {err}"),
+                                        ToastLevel::Error,
+                                        font_id.clone(),
+                                    )),
+                                }
                             }
                         });
                     }
@@ -278,9 +654,14 @@ impl eframe::App for OmniChatter {
                             ui.label(RichText::new("contents:").font(font_id.clone()));
                             ui.add(TextEdit::multiline(&mut self.current_command.contents).font(font_id.clone()));
                         });
+                        ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+                            ui.label(RichText::new("description:").font(font_id.clone()));
+                            ui.add(TextEdit::singleline(&mut self.current_command.description).font(font_id.clone()));
+                        });
                         if ui.button(RichText::new("Create").font(font_id.clone())).clicked() {
                             match command_handler.create_command(&self.current_command) {
                                 Ok(_) => {
+                                    self.saved_command = self.current_command.clone();
                                     self.toasts.push(Toast::new(
                                         Duration::from_secs_f32(2.5),
                                         format!("Command {} created successfully!", &self.current_command.name),
@@ -298,6 +679,97 @@ impl eframe::App for OmniChatter {
                             }
                         }
                     }
+                    State::Timers => {
+                        let mut timers = self.timers.lock().unwrap();
+                        let mut result: Option<(anyhow::Result<()>, String)> = None;
+                        ui.strong(RichText::new("Timers").font(font_id.clone()));
+                        for timer in timers.timers().to_vec() {
+                            ui.horizontal(|ui| {
+                                let targets: Vec<String> = timer.targets.iter().map(ChatTarget::to_string).collect();
+                                let targets = match targets.is_empty() {
+                                    true => "everywhere".to_string(),
+                                    false => targets.join(", "),
+                                };
+                                ui.label(
+                                    RichText::new(format!(
+                                        "{}: every {}s, after {} messages, on {targets}",
+                                        timer.name, timer.interval, timer.min_messages
+                                    ))
+                                    .font(font_id.clone()),
+                                )
+                                .on_hover_text(&timer.message);
+                                let mut paused = timer.paused;
+                                if ui.checkbox(&mut paused, RichText::new("paused").font(font_id.clone())).changed() {
+                                    result = Some((timers.set_paused(&timer.name, paused), format!("Timer {} updated", timer.name)));
+                                }
+                                if ui.button(RichText::new("Edit").font(font_id.clone())).clicked() {
+                                    self.timer_targets_input = targets_input(&timer.targets);
+                                    self.editing_timer = Some(timer.name.clone());
+                                    self.current_timer = timer.clone();
+                                }
+                                if ui
+                                    .add(Button::new(RichText::new("Delete").font(font_id.clone())).fill(Color32::from_rgb(94, 25, 25)))
+                                    .clicked()
+                                {
+                                    if self.editing_timer.as_ref() == Some(&timer.name) {
+                                        self.editing_timer = None;
+                                    }
+                                    result = Some((timers.remove_timer(&timer.name), format!("Timer {} deleted", timer.name)));
+                                }
+                            });
+                        }
+                        ui.add(Separator::default().horizontal());
+                        ui.horizontal(|ui| {
+                            ui.label(RichText::new("name:").font(font_id.clone()));
+                            ui.add(TextEdit::singleline(&mut self.current_timer.name).font(font_id.clone()));
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label(RichText::new("message:").font(font_id.clone()));
+                            ui.add(TextEdit::multiline(&mut self.current_timer.message).font(font_id.clone()));
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label(RichText::new("every (s):").font(font_id.clone()));
+                            ui.add(egui::DragValue::new(&mut self.current_timer.interval));
+                            ui.label(RichText::new("min messages:").font(font_id.clone()));
+                            ui.add(egui::DragValue::new(&mut self.current_timer.min_messages));
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label(RichText::new("targets:").font(font_id.clone()));
+                            ui.add(
+                                TextEdit::singleline(&mut self.timer_targets_input)
+                                    .hint_text("twitch, discord:1234 (empty for everywhere)")
+                                    .font(font_id.clone()),
+                            );
+                        });
+                        ui.horizontal(|ui| {
+                            if ui.button(RichText::new("Save timer").font(font_id.clone())).clicked() {
+                                self.current_timer.targets = parse_targets(&self.timer_targets_input);
+                                let name = self.current_timer.name.clone();
+                                let saved = timers.save_timer(self.editing_timer.as_deref(), self.current_timer.clone());
+                                if saved.is_ok() {
+                                    self.editing_timer = Some(name.clone());
+                                }
+                                result = Some((saved, format!("Timer {name} saved")));
+                            }
+                            if self.editing_timer.is_some() && ui.button(RichText::new("New timer").font(font_id.clone())).clicked() {
+                                self.editing_timer = None;
+                                self.current_timer = Timer::default();
+                                self.timer_targets_input.clear();
+                            }
+                        });
+                        if let Some((result, success)) = result {
+                            let toast = match result {
+                                Ok(_) => Toast::new(Duration::from_secs_f32(1.25), success, ToastLevel::Success, font_id.clone()),
+                                Err(err) => Toast::new(
+                                    Duration::from_secs_f32(2.5),
+                                    format!("Error updating timers: {err}"),
+                                    ToastLevel::Error,
+                                    font_id.clone(),
+                                ),
+                            };
+                            self.toasts.push(toast);
+                        }
+                    }
                     State::Idle => {
                         ui.label(RichText::new("Select any command or action").font(font_id.clone()));
                     }
@@ -324,52 +796,259 @@ impl eframe::App for OmniChatter {
                         self.state = State::ChatFullScreen
                     }
                     // TODO: set self.scrolling_chat to true when scroll detected
-                    ScrollArea::vertical()
-                        .scroll_bar_visibility(ScrollBarVisibility::AlwaysHidden)
-                        .show(ui, |ui| {
-                            for PlatformMessage { msg, platform, sender } in &self.platform_messages {
-                                ui.horizontal(|ui| {
-                                    let color = match platform {
-                                        Platform::Youtube => Color32::RED,
-                                        Platform::Twitch => Color32::from_rgb(191, 148, 255),
-                                    };
-                                    ui.label(RichText::new(" ").background_color(color).font(font_id.clone()));
-                                    let tmp =
-                                        ui.add(Label::new(RichText::new(format!("[{}] {}", sender, msg)).font(font_id.clone())).wrap(true));
-                                    if !self.scrolling_chat {
-                                        tmp.scroll_to_me(None);
-                                    }
-                                });
+                    moderation_request = show_chat(
+                        ui,
+                        &self.platform_events,
+                        &self.deleted_messages,
+                        &font_id,
+                        self.scrolling_chat,
+                        &self.registry,
+                    );
+                    if self.scrolling_chat && ui.button(RichText::new("resume scrolling").font(font_id.clone())).clicked() {
+                        self.scrolling_chat = true
+                    }
+                    ui.add(Separator::default().horizontal());
+                    ui.add(TextEdit::singleline(&mut self.chat_input).font(font_id.clone()));
+                    if ui.button(RichText::new("Send to all chats").font(font_id.clone())).clicked() && !self.chat_input.is_empty() {
+                        for handle in self.registry.handles() {
+                            if let Err(err) = handle.send_message(None, self.chat_input.clone()) {
+                                self.toasts.push(Toast::new(
+                                    Duration::from_secs_f32(2.5),
+                                    format!("Could not send message: {:?}", err),
+                                    ToastLevel::Error,
+                                    font_id.clone(),
+                                ));
                             }
-                        });
-                    if self.scrolling_chat {
-                        if ui.button(RichText::new("resume scrolling").font(font_id.clone())).clicked() {
-                            self.scrolling_chat = true
                         }
+                        self.chat_input = String::new();
                     }
                 });
 
+            let mut import_open = self.import_open;
+            egui::Window::new("Import commands").open(&mut import_open).show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("from:").font(font_id.clone()));
+                    egui::ComboBox::from_id_source("import_source")
+                        .selected_text(RichText::new(self.import_source.to_string()).font(font_id.clone()))
+                        .show_ui(ui, |ui| {
+                            for source in Source::ALL {
+                                ui.selectable_value(&mut self.import_source, source, source.to_string());
+                            }
+                        });
+                });
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("file:").font(font_id.clone()))
+                        .on_hover_text("A JSON or CSV export of the commands");
+                    ui.add(TextEdit::singleline(&mut self.import_path).font(font_id.clone()));
+                });
+                if ui.button(RichText::new("Import").font(font_id.clone())).clicked() {
+                    match import::read(self.import_source, self.import_path.trim()).and_then(|import| import.apply(&mut command_handler)) {
+                        Ok(report) => self.import_report = report,
+                        Err(err) => self.toasts.push(Toast::new(
+                            Duration::from_secs_f32(2.5),
+                            format!("Error importing commands: {err}"),
+                            ToastLevel::Error,
+                            font_id.clone(),
+                        )),
+                    }
+                }
+                ScrollArea::vertical().max_height(300.).show(ui, |ui| {
+                    for line in &self.import_report {
+                        ui.label(RichText::new(line).font(font_id.clone()));
+                    }
+                });
+            });
+            self.import_open = import_open;
+
             // TODO: Add a button to toggle fullscreen chat
         }
+
+        if let Some((platform, action)) = moderation_request {
+            let toast = match self.registry.moderation().apply(&platform, &action) {
+                Ok(_) => Toast::new(
+                    Duration::from_secs_f32(2.5),
+                    format!("Done: {action}"),
+                    ToastLevel::Success,
+                    font_id.clone(),
+                ),
+                Err(err) => Toast::new(
+                    Duration::from_secs_f32(2.5),
+                    format!("Could not {action}: {:?}", err),
+                    ToastLevel::Error,
+                    font_id.clone(),
+                ),
+            };
+            self.toasts.push(toast);
+        }
         // check if this needs to be limited to avoid any issues
         ctx.request_repaint()
     }
 }
 
-impl Into<String> for &PlatformMessage {
-    fn into(self) -> String {
-        match self.platform {
-            Platform::Twitch => {
-                RichText::new(" ")
-                    .background_color(Color32::from_rgb(191, 148, 255))
-                    .text()
-                    .to_owned()
-                    + &format!("[{}]", self.sender)
-                    + &self.msg
-            }
-            Platform::Youtube => {
-                RichText::new(" ").background_color(Color32::RED).text().to_owned() + &format!("[{}]", self.sender) + &self.msg
+/// Draws the combined chat. Right-clicking a message opens the moderation actions available for its
+/// platform, the picked one is returned so it can be applied outside of the UI closures.
+fn show_chat(
+    ui: &mut egui::Ui,
+    platform_events: &[PlatformEvent],
+    deleted_messages: &HashSet<(Platform, String)>,
+    font_id: &FontId,
+    scrolling_chat: bool,
+    registry: &Registry,
+) -> Option<(Platform, ModerationAction)> {
+    let mut moderation_request = None;
+    ScrollArea::vertical()
+        .scroll_bar_visibility(ScrollBarVisibility::AlwaysHidden)
+        .show(ui, |ui| {
+            for event in platform_events {
+                let PlatformEvent::Chat(message) = event else {
+                    ui.horizontal(|ui| {
+                        let platform = event.platform();
+                        ui.label(
+                            RichText::new(" ")
+                                .background_color(platform_color(registry, platform))
+                                .font(font_id.clone()),
+                        )
+                        .on_hover_text(platform_label(registry, platform));
+                        let tmp = ui.add(Label::new(RichText::new(event.to_string()).italics().font(font_id.clone())).wrap(true));
+                        if !scrolling_chat {
+                            tmp.scroll_to_me(None);
+                        }
+                    });
+                    continue;
+                };
+                let platform = &message.platform;
+                let deleted = message
+                    .platform_id
+                    .as_ref()
+                    .is_some_and(|platform_id| deleted_messages.contains(&(platform.clone(), platform_id.clone())));
+                let user_id = message.moderation_id().to_string();
+                ui.horizontal(|ui| {
+                    ui.label(
+                        RichText::new(" ")
+                            .background_color(platform_color(registry, platform))
+                            .font(font_id.clone()),
+                    )
+                    .on_hover_text(platform_label(registry, platform));
+                    let tmp = ui
+                        .add(Label::new(message_layout(ui, message, deleted, font_id)).wrap(true))
+                        .on_hover_text(message_details(message));
+                    if registry.moderation().supports(platform) {
+                        tmp.clone().context_menu(|ui| {
+                            let mut action = None;
+                            if let Some(message_id) = &message.platform_id {
+                                if ui.button("Delete message").clicked() {
+                                    action = Some(ModerationAction::DeleteMessage {
+                                        message_id: message_id.clone(),
+                                    });
+                                }
+                            }
+                            if ui.button("Timeout 10m").clicked() {
+                                action = Some(ModerationAction::Timeout {
+                                    user_id: user_id.clone(),
+                                    duration: Duration::from_secs(600),
+                                });
+                            }
+                            if ui.button("Ban").clicked() {
+                                action = Some(ModerationAction::Ban { user_id: user_id.clone() });
+                            }
+                            if ui
+                                .add_enabled(registry.moderation().can_unban(platform, &user_id), Button::new("Unban"))
+                                .on_disabled_hover_text("No ban for this user was issued in this session")
+                                .clicked()
+                            {
+                                action = Some(ModerationAction::Unban { user_id: user_id.clone() });
+                            }
+                            ui.separator();
+                            if ui.button("Make moderator").clicked() {
+                                action = Some(ModerationAction::AddModerator { user_id: user_id.clone() });
+                            }
+                            if ui.button("Remove moderator").clicked() {
+                                action = Some(ModerationAction::RemoveModerator { user_id: user_id.clone() });
+                            }
+                            if let Some(action) = action {
+                                moderation_request = Some((platform.clone(), action));
+                                ui.close_menu();
+                            }
+                        });
+                    }
+                    if !scrolling_chat {
+                        tmp.scroll_to_me(None);
+                    }
+                });
             }
-        }
+        });
+    moderation_request
+}
+
+/// Sender in its color (if the platform gives one) followed by the message, with who it replies to.
+/// Deleted messages are struck through.
+fn message_layout(ui: &egui::Ui, message: &PlatformMessage, deleted: bool, font_id: &FontId) -> LayoutJob {
+    let mut job = LayoutJob::default();
+    let mut append = |text: RichText| {
+        text.font(font_id.clone())
+            .append_to(&mut job, ui.style(), FontSelection::Default, Align::Center)
+    };
+    if let Some(sender) = message.reply.as_ref().and_then(|reply| reply.sender.as_ref()) {
+        append(RichText::new(format!("@{sender} ")).weak());
+    }
+    let name = RichText::new(format!("[{}] ", message.display_name())).strong();
+    append(match message.color {
+        Some([r, g, b]) => name.color(Color32::from_rgb(r, g, b)),
+        None => name,
+    });
+    append(match deleted {
+        true => RichText::new(&message.msg).strikethrough().weak(),
+        false => RichText::new(&message.msg),
+    });
+    job
+}
+
+fn message_details(message: &PlatformMessage) -> String {
+    let mut details = format!(
+        "{} ({})\nreceived {}",
+        message.display_name(),
+        message.sender,
+        message.received_at.with_timezone(&Local).format("%H:%M:%S")
+    );
+    if let Some(channel) = &message.channel {
+        details += &format!("\nin {channel}");
+    }
+    if !message.badges.is_empty() {
+        let badges: Vec<&str> = message.badges.iter().map(|badge| badge.name.as_str()).collect();
+        details += &format!("\nbadges: {}", badges.join(", "));
+    }
+    if let Some(reply) = &message.reply {
+        details += &format!("\nreplying to: {}", reply.msg.as_deref().unwrap_or(&reply.platform_id));
+    }
+    details
+}
+
+fn platform_color(registry: &Registry, platform: &Platform) -> Color32 {
+    registry
+        .info(platform)
+        .map(|info| Color32::from_rgb(info.color[0], info.color[1], info.color[2]))
+        .unwrap_or(Color32::GRAY)
+}
+
+/// Comma separated `platform` or `platform:channel`
+fn parse_targets(input: &str) -> Vec<ChatTarget> {
+    input
+        .split(',')
+        .filter(|target| !target.trim().is_empty())
+        .map(ChatTarget::parse)
+        .collect()
+}
+
+fn targets_input(targets: &[ChatTarget]) -> String {
+    targets.iter().map(ChatTarget::to_string).collect::<Vec<_>>().join(", ")
+}
+
+fn platform_label<'a>(registry: &'a Registry, platform: &'a Platform) -> &'a str {
+    registry.info(platform).map(|info| info.label).unwrap_or(platform.id())
+}
+
+impl From<&PlatformMessage> for String {
+    fn from(message: &PlatformMessage) -> Self {
+        format!("[{}] [{}]{}", message.platform, message.sender, message.msg)
     }
 }
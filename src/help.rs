@@ -0,0 +1,123 @@
+use crate::{
+    command::{BotCommand, COMMAND_SYMBOL},
+    permission::PermissionLevel,
+};
+
+static HEADER: &str = "Commands:";
+/// Room kept on each page for the longest footer
+static FOOTER_ROOM: usize = " (page 99/99, !help 99 for more)".len();
+
+/// The `page` (from 1) of the list of `commands`, split so each page fits in `limit` characters.
+pub fn list(commands: &[&BotCommand], page: usize, limit: usize) -> String {
+    if commands.is_empty() {
+        return "There are no commands you can use".to_string();
+    }
+    let mut pages = Vec::new();
+    let mut current = HEADER.to_string();
+    for command in commands {
+        let item = match command.aliases.is_empty() {
+            true => format!(" {COMMAND_SYMBOL}{}", command.name),
+            false => format!(" {COMMAND_SYMBOL}{} ({})", command.name, aliases(command)),
+        };
+        if current != HEADER && current.chars().count() + item.chars().count() + FOOTER_ROOM > limit {
+            pages.push(current);
+            current = HEADER.to_string();
+        }
+        current.push_str(&item);
+    }
+    pages.push(current);
+
+    let total = pages.len();
+    let Some(text) = page.checked_sub(1).and_then(|index| pages.get(index)) else {
+        return format!("There are {total} pages of commands, try {COMMAND_SYMBOL}help 1 to {total}");
+    };
+    let text = match total {
+        1 => text.clone(),
+        _ if page == total => format!("{text} (page {page}/{total})"),
+        _ => format!("{text} (page {page}/{total}, {COMMAND_SYMBOL}help {} for more)", page + 1),
+    };
+    truncate(&text, limit)
+}
+
+/// What `!help <command>` answers.
+pub fn describe(command: &BotCommand, limit: usize) -> String {
+    let mut text = format!("{COMMAND_SYMBOL}{}", command.name);
+    if !command.aliases.is_empty() {
+        text.push_str(&format!(" (also {})", aliases(command)));
+    }
+    match command.description.trim() {
+        "" => text.push_str(": no description yet"),
+        description => text.push_str(&format!(": {description}")),
+    }
+    if command.permission > PermissionLevel::Everyone {
+        text.push_str(&format!(" [{} and up]", command.permission));
+    }
+    truncate(&text, limit)
+}
+
+fn aliases(command: &BotCommand) -> String {
+    let aliases: Vec<String> = command.aliases.iter().map(|alias| format!("{COMMAND_SYMBOL}{alias}")).collect();
+    aliases.join(", ")
+}
+
+fn truncate(text: &str, limit: usize) -> String {
+    match text.char_indices().nth(limit.saturating_sub(1)) {
+        Some((end, _)) if text.chars().count() > limit => format!("{}…", &text[..end]),
+        _ => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(name: &str, aliases: &[&str]) -> BotCommand {
+        BotCommand {
+            name: name.to_string(),
+            aliases: aliases.iter().map(|alias| alias.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn lists_aliases_next_to_their_command() {
+        let discord = command("discord", &["dc", "server"]);
+        let today = command("today", &[]);
+        assert_eq!(list(&[&discord, &today], 1, 500), "Commands: !discord (!dc, !server) !today");
+    }
+
+    #[test]
+    fn pages_fit_the_limit_with_their_aliases() {
+        let commands: Vec<BotCommand> = (0..30).map(|n| command(&format!("command{n}"), &["alias", "other"])).collect();
+        let commands: Vec<&BotCommand> = commands.iter().collect();
+        let limit = 120;
+        let mut listed = 0;
+        for page in 1.. {
+            let text = list(&commands, page, limit);
+            if text.starts_with("There are") {
+                assert_eq!(
+                    text,
+                    format!("There are {} pages of commands, try !help 1 to {}", page - 1, page - 1)
+                );
+                break;
+            }
+            assert!(text.chars().count() <= limit, "{text}");
+            assert!(!text.contains('…'), "{text}");
+            listed += text.matches("(!alias, !other)").count();
+        }
+        assert_eq!(listed, 30);
+    }
+
+    #[test]
+    fn describes_a_command() {
+        let mut discord = command("discord", &["dc"]);
+        assert_eq!(describe(&discord, 500), "!discord (also !dc): no description yet");
+        discord.description = "Where we hang out".to_string();
+        discord.permission = PermissionLevel::Subscriber;
+        assert_eq!(
+            describe(&discord, 500),
+            "!discord (also !dc): Where we hang out [subscriber and up]"
+        );
+        assert_eq!(describe(&discord, 10), "!discord …");
+    }
+}
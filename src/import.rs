@@ -0,0 +1,611 @@
+use std::{fmt, fs};
+
+use anyhow::Result;
+use serde_json::{Map, Value};
+
+use crate::{
+    command::{BotCommand, CommandHandler, COMMAND_SYMBOL},
+    permission::PermissionLevel,
+    template::Template,
+    trigger::Trigger,
+};
+
+/// Bots whose exported commands can be imported.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Source {
+    /// Commands from its API, with `$(user)` variables
+    Nightbot,
+    /// Commands from its API, with `${user}` variables
+    StreamElements,
+    /// Streamlabs Chatbot CSV export, with `$user` variables
+    Streamlabs,
+}
+
+impl Source {
+    pub const ALL: [Source; 3] = [Source::Nightbot, Source::StreamElements, Source::Streamlabs];
+
+    /// Source by its name on the command line, like `nightbot`.
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|source| source.id() == name.to_lowercase())
+    }
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Source::Nightbot => "nightbot",
+            Source::StreamElements => "streamelements",
+            Source::Streamlabs => "streamlabs",
+        }
+    }
+
+    /// Fields with the name and the response of a command
+    fn fields(&self) -> (&'static str, &'static str) {
+        match self {
+            Source::Nightbot => ("name", "message"),
+            Source::StreamElements => ("command", "reply"),
+            Source::Streamlabs => ("command", "response"),
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Nightbot => write!(f, "Nightbot"),
+            Source::StreamElements => write!(f, "StreamElements"),
+            Source::Streamlabs => write!(f, "Streamlabs"),
+        }
+    }
+}
+
+/// Commands converted from an export, and everything about them that didn't convert exactly.
+#[derive(Debug, Default)]
+pub struct Import {
+    pub commands: Vec<BotCommand>,
+    pub report: Vec<String>,
+}
+
+impl Import {
+    /// Creates the commands, except those that clash with existing ones. Returns the report with
+    /// what was imported first.
+    pub fn apply(self, command_handler: &mut CommandHandler) -> Result<Vec<String>> {
+        let total = self.commands.len();
+        let rejected = command_handler.create_commands(self.commands)?;
+        let mut report = vec![format!("Imported {} of {total} commands", total - rejected.len())];
+        report.extend(self.report);
+        report.extend(
+            rejected
+                .into_iter()
+                .map(|(name, err)| format!("{COMMAND_SYMBOL}{name}: not imported, {err}")),
+        );
+        Ok(report)
+    }
+}
+
+/// Reads the commands exported from `source` at `path`, a JSON list of commands (or an object with
+/// a `commands` list) or a CSV file with a header row.
+pub fn read(source: Source, path: &str) -> Result<Import> {
+    let contents = fs::read_to_string(path).map_err(|err| anyhow::Error::msg(format!("Cannot read {path}: {err}")))?;
+    parse(source, &contents).map_err(|err| anyhow::Error::msg(format!("Cannot import {source} commands from {path}: {err}")))
+}
+
+pub fn parse(source: Source, contents: &str) -> Result<Import> {
+    let mut import = Import::default();
+    for entry in entries(contents)? {
+        if let Some(command) = convert(source, &entry, &mut import.report) {
+            import.commands.push(command);
+        }
+    }
+    Ok(import)
+}
+
+/// `import <source> <file>` on the command line.
+pub fn cli(command_handler: &mut CommandHandler, args: &[String]) -> Result<()> {
+    let sources: Vec<&str> = Source::ALL.iter().map(Source::id).collect();
+    let usage = format!("Usage: ttv-bot import <{}> <file>", sources.join("|"));
+    let [source, path] = args else {
+        return Err(anyhow::Error::msg(usage));
+    };
+    let Some(source) = Source::parse(source) else {
+        return Err(anyhow::Error::msg(format!("Unknown source {source}. {usage}")));
+    };
+    for line in read(source, path)?.apply(command_handler)? {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+/// Each exported command as its fields, with the names in lowercase.
+fn entries(contents: &str) -> Result<Vec<Map<String, Value>>> {
+    let contents = contents.trim_start_matches('\u{feff}').trim_start();
+    let entries: Vec<Map<String, Value>> = if contents.starts_with('[') || contents.starts_with('{') {
+        let list = match serde_json::from_str(contents)? {
+            Value::Array(list) => list,
+            Value::Object(mut object) => match object.remove("commands") {
+                Some(Value::Array(list)) => list,
+                _ => return Err(anyhow::Error::msg("expected a list of commands")),
+            },
+            _ => return Err(anyhow::Error::msg("expected a list of commands")),
+        };
+        list.into_iter()
+            .map(|entry| match entry {
+                Value::Object(entry) => Ok(entry),
+                _ => Err(anyhow::Error::msg("expected every command to be an object")),
+            })
+            .collect::<Result<_>>()?
+    } else {
+        let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(contents.as_bytes());
+        let headers = reader.headers()?.clone();
+        reader
+            .records()
+            .map(|record| {
+                let record = record?;
+                Ok(headers
+                    .iter()
+                    .zip(record.iter())
+                    .map(|(header, value)| (header.trim().to_string(), Value::String(value.to_string())))
+                    .collect())
+            })
+            .collect::<Result<_>>()?
+    };
+    Ok(entries
+        .into_iter()
+        .map(|entry| entry.into_iter().map(|(field, value)| (field.to_lowercase(), value)).collect())
+        .collect())
+}
+
+fn convert(source: Source, entry: &Map<String, Value>, report: &mut Vec<String>) -> Option<BotCommand> {
+    let (name_field, contents_field) = source.fields();
+    let Some(name) = text(entry.get(name_field)) else {
+        report.push(format!("Skipped a command without {name_field}"));
+        return None;
+    };
+    let name = name.trim().trim_start_matches(COMMAND_SYMBOL).to_string();
+    let Some(contents) = text(entry.get(contents_field)) else {
+        report.push(format!("{COMMAND_SYMBOL}{name}: skipped, it has no {contents_field}"));
+        return None;
+    };
+    if flag(entry.get("enabled")) == Some(false) {
+        report.push(format!("{COMMAND_SYMBOL}{name}: skipped, it is disabled in {source}"));
+        return None;
+    }
+
+    let mut problems = Vec::new();
+    let mut command = BotCommand {
+        name,
+        contents: convert_variables(source, &contents, &mut problems),
+        count: number(entry.get("count")).unwrap_or_default() as i64,
+        // Streamlabs calls it info
+        description: text(entry.get("description")).or(text(entry.get("info"))).unwrap_or_default(),
+        ..Default::default()
+    };
+    match source {
+        Source::Nightbot => {
+            command.cooldown.global = number(entry.get("cooldown")).unwrap_or_default() as u64;
+            command.permission = nightbot_level(text(entry.get("userlevel")), &mut problems);
+            if let Some(alias) = text(entry.get("alias")) {
+                problems.push(format!("runs {alias} in Nightbot, which was not imported with it"));
+            }
+        }
+        Source::StreamElements => {
+            match entry.get("cooldown") {
+                Some(Value::Object(cooldown)) => {
+                    command.cooldown.global = number(cooldown.get("global")).unwrap_or_default() as u64;
+                    command.cooldown.per_user = number(cooldown.get("user")).unwrap_or_default() as u64;
+                }
+                cooldown => command.cooldown.global = number(cooldown).unwrap_or_default() as u64,
+            }
+            command.permission = streamelements_level(number(entry.get("accesslevel")), &mut problems);
+            command.aliases = list(entry.get("aliases"));
+            command.triggers = list(entry.get("keywords"))
+                .into_iter()
+                .map(|pattern| Trigger {
+                    pattern,
+                    ..Default::default()
+                })
+                .collect();
+            if let Some(pattern) = text(entry.get("regex")) {
+                command.triggers.push(Trigger {
+                    pattern,
+                    regex: true,
+                    ..Default::default()
+                });
+            }
+            if let Some(cost) = number(entry.get("cost")).filter(|cost| *cost > 0.) {
+                problems.push(format!("costs {cost} points in StreamElements, here it is free"));
+            }
+        }
+        Source::Streamlabs => {
+            command.cooldown.global = number(entry.get("cooldown")).unwrap_or_default() as u64;
+            command.cooldown.per_user = number(entry.get("usercooldown")).unwrap_or_default() as u64;
+            command.permission = streamlabs_level(text(entry.get("permission")), &mut problems);
+        }
+    }
+
+    report.extend(
+        problems
+            .into_iter()
+            .map(|problem| format!("{COMMAND_SYMBOL}{}: {problem}", command.name)),
+    );
+    Some(command)
+}
+
+fn nightbot_level(level: Option<String>, problems: &mut Vec<String>) -> PermissionLevel {
+    match level.unwrap_or_default().to_lowercase().as_str() {
+        "" | "everyone" => PermissionLevel::Everyone,
+        "subscriber" => PermissionLevel::Subscriber,
+        "twitch_vip" => PermissionLevel::Vip,
+        "regular" => {
+            problems.push("was for Nightbot regulars, here it is for VIPs".to_string());
+            PermissionLevel::Vip
+        }
+        "moderator" => PermissionLevel::Moderator,
+        "owner" => PermissionLevel::Broadcaster,
+        other => unknown_level(other, problems),
+    }
+}
+
+/// StreamElements levels are numbers: 100 everyone, 250 subscriber, 300 regular, 400 VIP, 500
+/// moderator, 1000 super moderator and 1500 broadcaster.
+fn streamelements_level(level: Option<f64>, problems: &mut Vec<String>) -> PermissionLevel {
+    match level.unwrap_or(100.) as u64 {
+        1500.. => PermissionLevel::Broadcaster,
+        500.. => PermissionLevel::Moderator,
+        400.. => PermissionLevel::Vip,
+        300.. => {
+            problems.push("was for StreamElements regulars, here it is for VIPs".to_string());
+            PermissionLevel::Vip
+        }
+        250.. => PermissionLevel::Subscriber,
+        _ => PermissionLevel::Everyone,
+    }
+}
+
+fn streamlabs_level(level: Option<String>, problems: &mut Vec<String>) -> PermissionLevel {
+    match level.unwrap_or_default().to_lowercase().as_str() {
+        "" | "everyone" => PermissionLevel::Everyone,
+        "subscriber" => PermissionLevel::Subscriber,
+        "vip" | "vip exclusive" => PermissionLevel::Vip,
+        "regular" => {
+            problems.push("was for Streamlabs regulars, here it is for VIPs".to_string());
+            PermissionLevel::Vip
+        }
+        "moderator" | "editor" => PermissionLevel::Moderator,
+        "caster" => PermissionLevel::Broadcaster,
+        other => unknown_level(other, problems),
+    }
+}
+
+/// Levels that can't be mapped keep the command to moderators rather than opening it to everyone.
+fn unknown_level(level: &str, problems: &mut Vec<String>) -> PermissionLevel {
+    problems.push(format!("has the unknown user level {level}, here it is for moderators"));
+    PermissionLevel::Moderator
+}
+
+/// Rewrites the variables of `source` in `text` as template placeholders. Those without an
+/// equivalent are kept as text.
+fn convert_variables(source: Source, text: &str, problems: &mut Vec<String>) -> String {
+    let mut converted = String::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let Some((length, name, args)) = variable(source, rest) else {
+            converted.push_str(&escape(&c.to_string()));
+            rest = &rest[c.len_utf8()..];
+            continue;
+        };
+        let original = &rest[..length];
+        let mut lost = Vec::new();
+        match placeholder(source, name, args.trim(), &mut lost).filter(|placeholder| Template::parse(placeholder).is_ok()) {
+            Some(placeholder) => {
+                converted.push_str(&placeholder);
+                problems.extend(lost.into_iter().map(|lost| format!("{original} {lost}")));
+            }
+            None => {
+                problems.push(format!("{original} has no equivalent, it was kept as text"));
+                converted.push_str(&escape(original));
+            }
+        }
+        rest = &rest[length..];
+    }
+    converted
+}
+
+/// Length, name and arguments of the variable `text` starts with, if it does.
+fn variable(source: Source, text: &str) -> Option<(usize, &str, &str)> {
+    let after = text.strip_prefix('$')?;
+    let (open, close) = match source {
+        Source::Nightbot => ('(', ')'),
+        Source::StreamElements => ('{', '}'),
+        // `$name` or `$name(args)`
+        Source::Streamlabs => {
+            let name_length = after.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(after.len());
+            let name = &after[..name_length];
+            if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                return None;
+            }
+            let rest = &after[name_length..];
+            return match rest.starts_with('(').then(|| closing(rest, '(', ')')).flatten() {
+                Some(end) => Some((1 + name_length + end, name, &rest[1..end - 1])),
+                None => Some((1 + name_length, name, "")),
+            };
+        }
+    };
+    if !after.starts_with(open) {
+        return None;
+    }
+    let end = closing(after, open, close)?;
+    let inner = after[1..end - 1].trim();
+    let (name, args) = inner.split_once(' ').unwrap_or((inner, ""));
+    Some((1 + end, name, args))
+}
+
+/// Index right after the `close` matching the `open` that `text` starts with.
+fn closing(text: &str, open: char, close: char) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        if c == open {
+            depth += 1;
+        } else if c == close {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i + c.len_utf8());
+            }
+        }
+    }
+    None
+}
+
+/// The placeholder for a variable. What the variable did that the placeholder doesn't goes to
+/// `lost`.
+fn placeholder(source: Source, name: &str, args: &str, lost: &mut Vec<String>) -> Option<String> {
+    let lowercase = name.to_lowercase();
+    let arg = |n: &str| n.parse::<usize>().ok().filter(|n| *n > 0).map(|n| format!("{{arg{n}}}"));
+    match source {
+        Source::Nightbot => match (lowercase.as_str(), args) {
+            ("user", "") => Some("{user}".to_string()),
+            ("touser", "") => Some("{target}".to_string()),
+            ("query", "") => Some("{args}".to_string()),
+            ("count", "") => Some("{count}".to_string()),
+            ("channel", "") => Some("{channel}".to_string()),
+            // The usual way of showing the uptime in Nightbot
+            ("urlfetch", url) if url.contains("decapi.me/twitch/uptime") => Some("{uptime}".to_string()),
+            ("time", args) => {
+                let (tz, format) = args.split_once(' ').unwrap_or((args, ""));
+                if !format.trim().is_empty() {
+                    lost.push(format!("lost its format {}, the time is shown as 18:30", format.trim()));
+                }
+                Some(time(Some(tz).filter(|tz| !tz.is_empty())))
+            }
+            (n, "") => arg(n),
+            _ => None,
+        },
+        Source::StreamElements => match (lowercase.as_str(), args) {
+            ("user" | "user.name" | "sender" | "sender.name", "") => Some("{user}".to_string()),
+            ("touser" | "touser.name", "") => Some("{target}".to_string()),
+            ("1:", "") => Some("{args}".to_string()),
+            ("count", "") => Some("{count}".to_string()),
+            ("channel" | "channel.name", "") => Some("{channel}".to_string()),
+            ("uptime", "") => Some("{uptime}".to_string()),
+            ("random.pick", options) => pick(options),
+            ("time", "") => Some(time(None)),
+            // Time zones are case sensitive
+            (variable, "") if variable.starts_with("time.") => Some(time(name.get("time.".len()..))),
+            // `${random.1-100}`
+            (variable, "") if variable.starts_with("random.") => {
+                let (min, max) = variable.strip_prefix("random.")?.split_once('-')?;
+                Some(format!("{{random {min} {max}}}"))
+            }
+            (n, "") => arg(n),
+            _ => None,
+        },
+        Source::Streamlabs => match (lowercase.as_str(), args) {
+            ("user" | "username", "") => Some("{user}".to_string()),
+            ("target" | "touser", "") => Some("{target}".to_string()),
+            ("msg", "") => Some("{args}".to_string()),
+            ("count", "") => Some("{count}".to_string()),
+            ("channel" | "channelname", "") => Some("{channel}".to_string()),
+            ("uptime", "") => Some("{uptime}".to_string()),
+            ("time", "") => Some(time(None)),
+            ("randnum", bounds) => {
+                let (min, max) = bounds.split_once(',')?;
+                Some(format!("{{random {} {}}}", min.trim(), max.trim()))
+            }
+            _ => None,
+        },
+    }
+}
+
+fn time(tz: Option<&str>) -> String {
+    match tz {
+        Some(tz) => format!("{{time {tz}}}"),
+        None => "{time}".to_string(),
+    }
+}
+
+/// `'a' 'b c'` or `a b` as `{pick a|b c}` or `{pick a|b}`, if no option has characters `{pick}`
+/// can't hold.
+fn pick(options: &str) -> Option<String> {
+    let options: Vec<&str> = match options.contains(['\'', '"']) {
+        true => options.split(['\'', '"']).skip(1).step_by(2).collect(),
+        false => options.split_whitespace().collect(),
+    };
+    if options.is_empty() || options.iter().any(|option| option.contains(['|', '{', '}'])) {
+        return None;
+    }
+    Some(format!("{{pick {}}}", options.join("|")))
+}
+
+/// Braces are literal in the exports but start placeholders in templates.
+fn escape(text: &str) -> String {
+    text.replace('{', "{{").replace('}', "}}")
+}
+
+fn text(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::String(text) if !text.trim().is_empty() => Some(text.clone()),
+        Value::Number(number) => Some(number.to_string()),
+        _ => None,
+    }
+}
+
+fn number(value: Option<&Value>) -> Option<f64> {
+    match value? {
+        Value::Number(number) => number.as_f64(),
+        Value::String(number) => number.trim().parse().ok(),
+        _ => None,
+    }
+    .map(|number| number.max(0.))
+}
+
+fn flag(value: Option<&Value>) -> Option<bool> {
+    match value? {
+        Value::Bool(flag) => Some(*flag),
+        Value::String(flag) => flag.trim().to_lowercase().parse().ok(),
+        _ => None,
+    }
+}
+
+/// A JSON list of strings, or a comma separated one in CSV files.
+fn list(value: Option<&Value>) -> Vec<String> {
+    match value {
+        Some(Value::Array(items)) => items.iter().filter_map(|item| text(Some(item))).collect(),
+        Some(Value::String(items)) => items
+            .split(',')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MemoryStore;
+
+    static NIGHTBOT: &str = r#"{
+        "_total": 3,
+        "status": 200,
+        "commands": [
+            {"_id": "1", "name": "!hug", "message": "$(user) hugs $(touser) {{ $(1) }}", "coolDown": 5, "userLevel": "everyone", "count": 2},
+            {"_id": "2", "name": "!time", "message": "It is $(time Europe/Madrid \"h:mm A\") here", "coolDown": 0, "userLevel": "regular", "count": 0},
+            {"_id": "3", "name": "!so", "message": "Go follow $(twitch $(touser) \"{{displayName}}\")", "coolDown": 0, "userLevel": "moderator", "count": 0}
+        ]
+    }"#;
+
+    static STREAMELEMENTS: &str = r#"[
+        {"command": "socials", "reply": "${sender} follow us! ${random.pick 'twitter' 'discord'} ${random.1-6}", "aliases": ["links"],
+         "keywords": ["where are you"], "regex": "", "cooldown": {"global": 30, "user": 10}, "accessLevel": 100, "enabled": true, "cost": 0, "count": 0},
+        {"command": "vip", "reply": "${user} is ${1}", "aliases": [], "keywords": [], "cooldown": 0, "accessLevel": 1500, "enabled": true, "cost": 50},
+        {"command": "old", "reply": "gone", "enabled": false}
+    ]"#;
+
+    static STREAMLABS: &str = "\u{feff}Command,Permission,Info,Group,Response,Cooldown,UserCooldown,Usage,Enabled,Count
+!uptime,Everyone,How long we have been live,GENERAL,$user we have been live for $uptime,5,0,Stream Chat,True,0
+!roll,Subscriber,,GENERAL,\"$user rolled $randnum(1,20) $checkpoints($userid)\",0,30,Stream Chat,True,4
+!secret,Owner,,GENERAL,ok,0,0,Stream Chat,True,0
+";
+
+    fn command<'a>(import: &'a Import, name: &str) -> &'a BotCommand {
+        import.commands.iter().find(|command| command.name == name).unwrap()
+    }
+
+    #[test]
+    fn imports_nightbot_commands() {
+        let import = parse(Source::Nightbot, NIGHTBOT).unwrap();
+        assert_eq!(import.commands.len(), 3);
+
+        let hug = command(&import, "hug");
+        assert_eq!(hug.contents, "{user} hugs {target} {{{{ {arg1} }}}}");
+        assert_eq!((hug.cooldown.global, hug.count), (5, 2));
+        assert_eq!(command(&import, "time").contents, "It is {time Europe/Madrid} here");
+        assert_eq!(command(&import, "time").permission, PermissionLevel::Vip);
+        assert_eq!(command(&import, "so").permission, PermissionLevel::Moderator);
+
+        assert_eq!(
+            import.report,
+            [
+                "!time: $(time Europe/Madrid \"h:mm A\") lost its format \"h:mm A\", the time is shown as 18:30",
+                "!time: was for Nightbot regulars, here it is for VIPs",
+                "!so: $(twitch $(touser) \"{{displayName}}\") has no equivalent, it was kept as text",
+            ]
+        );
+    }
+
+    #[test]
+    fn imports_streamelements_commands() {
+        let import = parse(Source::StreamElements, STREAMELEMENTS).unwrap();
+        assert_eq!(import.commands.len(), 2);
+
+        let socials = command(&import, "socials");
+        assert_eq!(socials.contents, "{user} follow us! {pick twitter|discord} {random 1 6}");
+        assert_eq!(socials.aliases, ["links"]);
+        assert_eq!(socials.triggers.len(), 1);
+        assert_eq!(socials.triggers[0].pattern, "where are you");
+        assert_eq!((socials.cooldown.global, socials.cooldown.per_user), (30, 10));
+        let vip = command(&import, "vip");
+        assert_eq!(vip.contents, "{user} is {arg1}");
+        assert_eq!(vip.permission, PermissionLevel::Broadcaster);
+
+        assert_eq!(
+            import.report,
+            [
+                "!vip: costs 50 points in StreamElements, here it is free",
+                "!old: skipped, it is disabled in StreamElements",
+            ]
+        );
+    }
+
+    #[test]
+    fn imports_streamlabs_csv() {
+        let import = parse(Source::Streamlabs, STREAMLABS).unwrap();
+        assert_eq!(import.commands.len(), 3);
+
+        let uptime = command(&import, "uptime");
+        assert_eq!(uptime.contents, "{user} we have been live for {uptime}");
+        assert_eq!(uptime.description, "How long we have been live");
+        let roll = command(&import, "roll");
+        assert_eq!(roll.contents, "{user} rolled {random 1 20} $checkpoints($userid)");
+        assert_eq!(
+            (roll.cooldown.per_user, roll.count, roll.permission),
+            (30, 4, PermissionLevel::Subscriber)
+        );
+        assert_eq!(command(&import, "secret").permission, PermissionLevel::Moderator);
+
+        assert_eq!(
+            import.report,
+            [
+                "!roll: $checkpoints($userid) has no equivalent, it was kept as text",
+                "!secret: has the unknown user level owner, here it is for moderators",
+            ]
+        );
+    }
+
+    #[test]
+    fn applying_keeps_existing_commands() {
+        let mut handler = CommandHandler::new(Box::new(MemoryStore::default())).unwrap();
+        handler
+            .create_command(&BotCommand {
+                name: "uptime".to_string(),
+                contents: "Ask the streamer".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        let report = parse(Source::Streamlabs, STREAMLABS).unwrap().apply(&mut handler).unwrap();
+        assert_eq!(report[0], "Imported 2 of 3 commands");
+        assert!(report.last().unwrap().starts_with("!uptime: not imported"));
+        assert_eq!(handler.get_command(&"uptime".to_string()).contents, "Ask the streamer");
+        assert_eq!(handler.commands().count(), 3);
+    }
+
+    #[test]
+    fn rejects_what_is_not_an_export() {
+        assert!(parse(Source::Nightbot, "{\"status\": 200}").is_err());
+        assert!(parse(Source::StreamElements, "[1, 2]").is_err());
+        let import = parse(Source::Nightbot, "[{\"message\": \"hi\"}, {\"name\": \"empty\"}]").unwrap();
+        assert!(import.commands.is_empty());
+        assert_eq!(
+            import.report,
+            ["Skipped a command without name", "!empty: skipped, it has no message"]
+        );
+    }
+}
@@ -0,0 +1,739 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    env,
+    fmt::Display,
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpStream,
+};
+
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use native_tls::{TlsConnector, TlsStream};
+use tungstenite::{stream::MaybeTlsStream, Message, WebSocket};
+
+use crate::{
+    connector::{is_timeout, set_read_timeout, IncomingEvent, PlatformConnector, POLL_TIMEOUT},
+    messages::{parse_timestamp, Platform, PlatformInfo, PlatformMessage},
+};
+
+impl Platform {
+    pub const IRC: Platform = Platform::new("irc");
+}
+
+/// A single IRC line: `[@tags] [:prefix] COMMAND [params...] [:trailing]`. The trailing parameter
+/// is stored as the last element of `params`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IrcMessage {
+    pub tags: HashMap<String, String>,
+    pub prefix: Option<String>,
+    pub command: String,
+    pub params: Vec<String>,
+}
+
+impl IrcMessage {
+    pub fn new(command: &str, params: &[&str]) -> Self {
+        Self {
+            tags: HashMap::new(),
+            prefix: None,
+            command: command.to_string(),
+            params: params.iter().map(|param| param.to_string()).collect(),
+        }
+    }
+
+    pub fn privmsg(target: &str, text: &str) -> Self {
+        Self::new("PRIVMSG", &[target, text])
+    }
+
+    /// Parses a line without its `\r\n`. Returns `None` for empty or malformed lines.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut rest = line.trim_end_matches(['\r', '\n']);
+
+        let mut tags = HashMap::new();
+        if let Some(tagged) = rest.strip_prefix('@') {
+            let (raw_tags, remaining) = tagged.split_once(' ')?;
+            for tag in raw_tags.split(';') {
+                let (key, value) = tag.split_once('=').unwrap_or((tag, ""));
+                tags.insert(key.to_string(), unescape_tag_value(value));
+            }
+            rest = remaining.trim_start();
+        }
+
+        let mut prefix = None;
+        if let Some(prefixed) = rest.strip_prefix(':') {
+            let (raw_prefix, remaining) = prefixed.split_once(' ')?;
+            prefix = Some(raw_prefix.to_string());
+            rest = remaining.trim_start();
+        }
+
+        let (middle, trailing) = match rest.split_once(" :") {
+            Some((middle, trailing)) => (middle, Some(trailing)),
+            None => (rest, None),
+        };
+        let mut words = middle.split(' ').filter(|word| !word.is_empty());
+        let command = words.next()?.to_uppercase();
+        let mut params: Vec<String> = words.map(|word| word.to_string()).collect();
+        if let Some(trailing) = trailing {
+            params.push(trailing.to_string());
+        }
+
+        Some(Self {
+            tags,
+            prefix,
+            command,
+            params,
+        })
+    }
+
+    /// Nick of the sender, taken from a `nick!user@host` prefix
+    pub fn nick(&self) -> Option<&str> {
+        self.prefix.as_deref().map(|prefix| prefix.split('!').next().unwrap_or(prefix))
+    }
+
+    pub fn param(&self, index: usize) -> Option<&str> {
+        self.params.get(index).map(|param| param.as_str())
+    }
+
+    /// Text of a CTCP ACTION (`/me`) PRIVMSG
+    pub fn ctcp_action(&self) -> Option<&str> {
+        self.param(1)?.strip_prefix("\x01ACTION ")?.strip_suffix('\x01')
+    }
+}
+
+fn unescape_tag_value(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(':') => unescaped.push(';'),
+            Some('s') => unescaped.push(' '),
+            Some('r') => unescaped.push('\r'),
+            Some('n') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => {}
+        }
+    }
+    unescaped
+}
+
+impl Display for IrcMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(prefix) = &self.prefix {
+            write!(f, ":{prefix} ")?;
+        }
+        write!(f, "{}", self.command)?;
+        if let Some((last, middle)) = self.params.split_last() {
+            for param in middle {
+                write!(f, " {param}")?;
+            }
+            if last.is_empty() || last.contains(' ') || last.starts_with(':') {
+                write!(f, " :{last}")?;
+            } else {
+                write!(f, " {last}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum IrcAuth {
+    None,
+    /// `PRIVMSG NickServ :IDENTIFY <password>` once registered
+    NickServ {
+        password: String,
+    },
+    /// SASL PLAIN during registration
+    Sasl {
+        username: String,
+        password: String,
+    },
+}
+
+/// Where the IRC server is and how to talk to it.
+#[derive(Debug, Clone)]
+pub enum IrcServer {
+    Tcp {
+        host: String,
+        port: u16,
+        tls: bool,
+    },
+    /// IRC lines in WebSocket text frames, like Twitch chat
+    WebSocket {
+        url: String,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct IrcConfig {
+    pub server: IrcServer,
+    pub nick: String,
+    pub realname: String,
+    /// Server password (`PASS`), not the account one
+    pub password: Option<String>,
+    pub auth: IrcAuth,
+    pub channels: Vec<String>,
+    /// IRCv3 capabilities to request, `sasl` is added when needed
+    pub capabilities: Vec<String>,
+}
+
+impl IrcConfig {
+    pub fn from_env() -> Option<Self> {
+        let nick = env::var("IRC_NICK").ok()?;
+        let tls = env::var("IRC_TLS").map(|tls| tls != "false" && tls != "0").unwrap_or(true);
+        let auth = match (env::var("IRC_SASL_PASSWORD"), env::var("IRC_NICKSERV_PASSWORD")) {
+            (Ok(password), _) => IrcAuth::Sasl {
+                username: env::var("IRC_SASL_USERNAME").unwrap_or(nick.clone()),
+                password,
+            },
+            (_, Ok(password)) => IrcAuth::NickServ { password },
+            _ => IrcAuth::None,
+        };
+        Some(Self {
+            server: IrcServer::Tcp {
+                host: env::var("IRC_HOST").ok()?,
+                port: env::var("IRC_PORT")
+                    .ok()
+                    .and_then(|port| port.parse().ok())
+                    .unwrap_or(if tls { 6697 } else { 6667 }),
+                tls,
+            },
+            realname: env::var("IRC_REALNAME").unwrap_or(nick.clone()),
+            nick,
+            password: env::var("IRC_PASSWORD").ok(),
+            auth,
+            channels: env::var("IRC_CHANNELS")
+                .ok()?
+                .split(',')
+                .map(|channel| channel.trim().to_string())
+                .filter(|channel| !channel.is_empty())
+                .collect(),
+            // Tells which services account a sender is logged in to, the only thing admins are matched on
+            capabilities: vec!["account-tag".to_string()],
+        })
+    }
+}
+
+enum IrcStream {
+    Plain(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+impl Read for IrcStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            IrcStream::Plain(stream) => stream.read(buf),
+            IrcStream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl IrcStream {
+    fn set_read_timeout(&self) -> std::io::Result<()> {
+        match self {
+            IrcStream::Plain(stream) => stream.set_read_timeout(Some(POLL_TIMEOUT)),
+            IrcStream::Tls(stream) => stream.get_ref().set_read_timeout(Some(POLL_TIMEOUT)),
+        }
+    }
+}
+
+impl Write for IrcStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            IrcStream::Plain(stream) => stream.write(buf),
+            IrcStream::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            IrcStream::Plain(stream) => stream.flush(),
+            IrcStream::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+/// Reads and writes whole IRC lines, without their `\r\n`.
+enum IrcTransport {
+    Stream {
+        reader: BufReader<IrcStream>,
+        /// What was read of the current line before a read timed out
+        line: Vec<u8>,
+    },
+    WebSocket {
+        socket: Box<WebSocket<MaybeTlsStream<TcpStream>>>,
+        /// Each frame can carry several lines
+        lines: VecDeque<String>,
+    },
+}
+
+impl IrcTransport {
+    fn connect(server: &IrcServer) -> Result<Self> {
+        match server {
+            IrcServer::Tcp { host, port, tls } => {
+                let tcp = TcpStream::connect((host.as_str(), *port))?;
+                let stream = match tls {
+                    true => IrcStream::Tls(Box::new(TlsConnector::new()?.connect(host, tcp)?)),
+                    false => IrcStream::Plain(tcp),
+                };
+                // Only now, the TLS handshake has to block until it is done
+                stream.set_read_timeout()?;
+                Ok(IrcTransport::Stream {
+                    reader: BufReader::new(stream),
+                    line: Vec::new(),
+                })
+            }
+            IrcServer::WebSocket { url } => {
+                let (socket, _) = tungstenite::connect(url)?;
+                set_read_timeout(&socket)?;
+                Ok(IrcTransport::WebSocket {
+                    socket: Box::new(socket),
+                    lines: VecDeque::new(),
+                })
+            }
+        }
+    }
+
+    fn read_line(&mut self) -> Result<String> {
+        match self {
+            IrcTransport::Stream { reader, line } => {
+                if reader.read_until(b'\n', line)? == 0 {
+                    return Err(anyhow::Error::msg("IRC server closed the connection"));
+                }
+                let read = String::from_utf8_lossy(line).to_string();
+                line.clear();
+                Ok(read)
+            }
+            IrcTransport::WebSocket { socket, lines } => loop {
+                if let Some(line) = lines.pop_front() {
+                    return Ok(line);
+                }
+                match socket.read()? {
+                    Message::Text(text) => lines.extend(text.lines().map(str::to_string)),
+                    Message::Close(_) => return Err(anyhow::Error::msg("IRC server closed the connection")),
+                    _ => {}
+                }
+            },
+        }
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        match self {
+            IrcTransport::Stream { reader, .. } => {
+                let stream = reader.get_mut();
+                stream.write_all(format!("{line}\r\n").as_bytes())?;
+                stream.flush()?;
+            }
+            IrcTransport::WebSocket { socket, .. } => socket.send(Message::Text(line.to_string()))?,
+        }
+        Ok(())
+    }
+}
+
+/// Client for any IRC network. `read` takes care of the protocol (registration, nick collisions,
+/// SASL, PING) and only hands out the messages the caller may care about.
+pub struct IrcClient {
+    config: IrcConfig,
+    transport: IrcTransport,
+    nick: String,
+    registered: bool,
+    /// Capabilities requested and not yet acknowledged or refused
+    pending_capabilities: usize,
+    authenticating: bool,
+}
+
+impl IrcClient {
+    pub fn connect(config: IrcConfig) -> Result<Self> {
+        let mut client = Self {
+            nick: config.nick.clone(),
+            transport: IrcTransport::connect(&config.server)?,
+            config,
+            registered: false,
+            pending_capabilities: 0,
+            authenticating: false,
+        };
+        client.register()?;
+        Ok(client)
+    }
+
+    fn register(&mut self) -> Result<()> {
+        let mut capabilities = self.config.capabilities.clone();
+        if let IrcAuth::Sasl { .. } = self.config.auth {
+            capabilities.push("sasl".to_string());
+        }
+        // One at a time, a server refuses the whole request if it lacks any of them
+        self.pending_capabilities = capabilities.len();
+        for capability in capabilities {
+            self.send(&IrcMessage::new("CAP", &["REQ", &capability]))?;
+        }
+        if let Some(password) = self.config.password.clone() {
+            self.send(&IrcMessage::new("PASS", &[&password]))?;
+        }
+        let nick = self.nick.clone();
+        self.send(&IrcMessage::new("NICK", &[&nick]))?;
+        let realname = self.config.realname.clone();
+        self.send(&IrcMessage::new("USER", &[&nick, "0", "*", &realname]))
+    }
+
+    /// Line breaks in the parameters are sent as spaces, they would otherwise end the command and
+    /// start a new one.
+    pub fn send(&mut self, message: &IrcMessage) -> Result<()> {
+        let line = message.to_string().replace(['\r', '\n', '\0'], " ");
+        self.transport.write_line(&line)
+    }
+
+    /// Current nick, which may differ from the configured one after a collision.
+    pub fn nick(&self) -> &str {
+        &self.nick
+    }
+
+    /// IRC has no multiline messages, each line of `text` is sent as its own PRIVMSG.
+    pub fn privmsg(&mut self, target: &str, text: &str) -> Result<()> {
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            self.send(&IrcMessage::privmsg(target, line))?;
+        }
+        Ok(())
+    }
+
+    /// Reads until there is a message that is not part of the protocol housekeeping. Fails with
+    /// a timeout after `POLL_TIMEOUT` without a full line, the partial one is kept for next call.
+    pub fn read(&mut self) -> Result<IrcMessage> {
+        loop {
+            let line = self.transport.read_line()?;
+            let Some(message) = IrcMessage::parse(&line) else {
+                continue;
+            };
+            if !self.handle_protocol(&message)? {
+                return Ok(message);
+            }
+        }
+    }
+
+    /// Registration goes on once every capability is settled and SASL is done.
+    fn end_capabilities(&mut self) -> Result<()> {
+        if self.pending_capabilities == 0 && !self.authenticating {
+            self.send(&IrcMessage::new("CAP", &["END"]))?;
+        }
+        Ok(())
+    }
+
+    /// Returns whether the message was fully handled here.
+    fn handle_protocol(&mut self, message: &IrcMessage) -> Result<bool> {
+        match message.command.as_str() {
+            "PING" => {
+                let token = message.param(0).unwrap_or("").to_string();
+                self.send(&IrcMessage::new("PONG", &[&token]))?;
+            }
+            // ERR_NICKNAMEINUSE, only ours to solve while registering
+            "433" if !self.registered => {
+                self.nick.push('_');
+                let nick = self.nick.clone();
+                self.send(&IrcMessage::new("NICK", &[&nick]))?;
+            }
+            "CAP" if matches!(message.param(1), Some("ACK") | Some("NAK")) => {
+                let capability = message.param(2).unwrap_or("").trim();
+                self.pending_capabilities = self.pending_capabilities.saturating_sub(1);
+                match message.param(1) {
+                    Some("ACK") if capability == "sasl" => {
+                        self.authenticating = true;
+                        self.send(&IrcMessage::new("AUTHENTICATE", &["PLAIN"]))?;
+                    }
+                    Some("ACK") => {}
+                    _ => eprintln!("WARN - IRC server does not support {capability}"),
+                }
+                self.end_capabilities()?;
+            }
+            "AUTHENTICATE" if message.param(0) == Some("+") => {
+                if let IrcAuth::Sasl { username, password } = &self.config.auth {
+                    let credentials = STANDARD.encode(format!("{username}\0{username}\0{password}"));
+                    self.send(&IrcMessage::new("AUTHENTICATE", &[&credentials]))?;
+                }
+            }
+            // RPL_SASLSUCCESS
+            "903" => {
+                self.authenticating = false;
+                self.end_capabilities()?;
+            }
+            // ERR_SASLFAIL, ERR_SASLTOOLONG
+            "904" | "905" => {
+                eprintln!("WARN - IRC SASL authentication failed: {}", message);
+                self.authenticating = false;
+                self.end_capabilities()?;
+            }
+            // RPL_WELCOME
+            "001" => {
+                self.registered = true;
+                if let Some(nick) = message.param(0) {
+                    self.nick = nick.to_string();
+                }
+                if let IrcAuth::NickServ { password } = self.config.auth.clone() {
+                    self.privmsg("NickServ", &format!("IDENTIFY {password}"))?;
+                }
+                for channel in self.config.channels.clone() {
+                    self.send(&IrcMessage::new("JOIN", &[&channel]))?;
+                }
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+pub fn connector() -> Option<Box<dyn PlatformConnector>> {
+    Some(Box::new(IrcConnector {
+        config: IrcConfig::from_env()?,
+        client: None,
+    }))
+}
+
+/// Answers in the channel the message came from. Messages sent without a channel go to the first
+/// configured one.
+pub struct IrcConnector {
+    config: IrcConfig,
+    client: Option<IrcClient>,
+}
+
+impl IrcConnector {
+    fn client(&mut self) -> Result<&mut IrcClient> {
+        self.client.as_mut().ok_or(anyhow::Error::msg("IRC client is not connected"))
+    }
+}
+
+impl PlatformConnector for IrcConnector {
+    fn info(&self) -> PlatformInfo {
+        PlatformInfo {
+            platform: Platform::IRC,
+            label: "IRC",
+            color: [230, 126, 34],
+        }
+    }
+
+    fn connect(&mut self) -> Result<()> {
+        self.client = Some(IrcClient::connect(self.config.clone())?);
+        Ok(())
+    }
+
+    fn poll(&mut self) -> Result<Vec<IncomingEvent>> {
+        let message = match self.client()?.read() {
+            Ok(message) => message,
+            Err(err) if is_timeout(&err) => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        if message.command != "PRIVMSG" {
+            return Ok(Vec::new());
+        }
+        let own_nick = self.client()?.nick().to_string();
+        let (Some(sender), Some(channel), Some(text)) = (message.nick(), message.param(0), message.param(1)) else {
+            return Ok(Vec::new());
+        };
+        // Private messages have our nick as target
+        if !channel.starts_with(['#', '&']) {
+            return Ok(Vec::new());
+        }
+
+        let msg = match message.ctcp_action() {
+            Some(action) => format!("* {action}"),
+            None => text.to_string(),
+        };
+        let chat = PlatformMessage {
+            // Only there if the server supports the `message-tags` and `server-time` capabilities
+            platform_id: message.tags.get("msgid").cloned(),
+            sent_at: message.tags.get("time").and_then(|time| parse_timestamp(time)),
+            // Nicks can be taken by anyone, the services account is what identifies a user
+            sender_id: message.tags.get("account").cloned(),
+            channel: Some(channel.to_string()),
+            is_bot: sender == own_nick,
+            ..PlatformMessage::new(Platform::IRC, sender.to_string(), msg)
+        };
+        Ok(vec![IncomingEvent::new(chat, Some(channel.to_string()))])
+    }
+
+    fn send_message(&mut self, channel: Option<&str>, msg: &str) -> Result<()> {
+        let channel = match channel {
+            Some(channel) => channel.to_string(),
+            None => self
+                .config
+                .channels
+                .first()
+                .cloned()
+                .ok_or(anyhow::Error::msg("IRC_CHANNELS to have at least one channel"))?,
+        };
+        self.client()?.privmsg(&channel, msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::{TcpListener, TcpStream},
+        thread,
+    };
+
+    use super::*;
+    use crate::messages::PlatformEvent;
+
+    /// The server side of a connection, line by line.
+    struct Server {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+    }
+
+    impl Server {
+        fn expect(&mut self, expected: &str) {
+            let mut line = String::new();
+            self.reader.read_line(&mut line).unwrap();
+            assert_eq!(line, format!("{expected}\r\n"));
+        }
+
+        fn send(&mut self, line: &str) {
+            self.writer.write_all(format!("{line}\r\n").as_bytes()).unwrap();
+        }
+    }
+
+    fn config(port: u16, auth: IrcAuth) -> IrcConfig {
+        IrcConfig {
+            server: IrcServer::Tcp {
+                host: "127.0.0.1".to_string(),
+                port,
+                tls: false,
+            },
+            nick: "bot".to_string(),
+            realname: "Bot".to_string(),
+            password: None,
+            auth,
+            channels: vec!["#chat".to_string()],
+            capabilities: vec!["account-tag".to_string()],
+        }
+    }
+
+    /// Runs `script` as the server of a single connection.
+    fn serve(script: impl FnOnce(&mut Server) + Send + 'static) -> (u16, thread::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut server = Server {
+                reader: BufReader::new(stream.try_clone().unwrap()),
+                writer: stream,
+            };
+            script(&mut server);
+        });
+        (port, server)
+    }
+
+    /// Polls until `count` events came in.
+    fn events(connector: &mut IrcConnector, count: usize) -> Vec<IncomingEvent> {
+        let mut events = Vec::new();
+        for _ in 0..20 {
+            events.extend(connector.poll().unwrap());
+            if events.len() >= count {
+                break;
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn parses_and_writes_lines() {
+        let message = IrcMessage::parse("@account=alice;msgid=1 :alice!a@host PRIVMSG #chat :hello there\r\n").unwrap();
+        assert_eq!(message.tags.get("account").map(String::as_str), Some("alice"));
+        assert_eq!(message.nick(), Some("alice"));
+        assert_eq!(message.command, "PRIVMSG");
+        assert_eq!(message.params, ["#chat", "hello there"]);
+        assert_eq!(
+            IrcMessage::privmsg("#chat", "hello there").to_string(),
+            "PRIVMSG #chat :hello there"
+        );
+        assert_eq!(IrcMessage::new("PONG", &["token"]).to_string(), "PONG token");
+    }
+
+    #[test]
+    fn registers_joins_and_chats() {
+        let (port, server) = serve(|server| {
+            server.expect("CAP REQ account-tag");
+            server.expect("NICK bot");
+            server.expect("USER bot 0 * Bot");
+            server.send(":server CAP * ACK :account-tag");
+            server.expect("CAP END");
+            server.send(":server 433 * bot :Nickname is already in use");
+            server.expect("NICK bot_");
+            server.send(":server 001 bot_ :Welcome");
+            server.expect("JOIN #chat");
+            server.send("PING :token");
+            server.expect("PONG token");
+            server.send("@account=Alice :alice!a@host PRIVMSG #chat :!today");
+            server.send(":mallory!m@host PRIVMSG #chat :\x01ACTION waves\x01");
+            server.send(":alice!a@host PRIVMSG bot_ :private");
+            server.send(":bot_!b@host PRIVMSG #chat :echo");
+            // The answer can't smuggle in commands of its own
+            server.expect("PRIVMSG #chat first");
+            server.expect("PRIVMSG #chat :QUIT :bye");
+            server.expect("PRIVMSG #chat :third KICK #chat alice");
+        });
+
+        let mut connector = IrcConnector {
+            config: config(port, IrcAuth::None),
+            client: None,
+        };
+        connector.connect().unwrap();
+        let events = events(&mut connector, 3);
+        let chats: Vec<_> = events
+            .iter()
+            .map(|incoming| match &incoming.event {
+                PlatformEvent::Chat(message) => (
+                    message.sender.as_str(),
+                    message.sender_id.as_deref(),
+                    message.msg.as_str(),
+                    message.is_bot,
+                ),
+                other => panic!("Unexpected event {other:?}"),
+            })
+            .collect();
+        assert_eq!(
+            chats,
+            [
+                ("alice", Some("Alice"), "!today", false),
+                ("mallory", None, "* waves", false),
+                ("bot_", None, "echo", true),
+            ]
+        );
+        assert_eq!(events[0].reply_to.as_deref(), Some("#chat"));
+
+        connector
+            .send_message(Some("#chat"), "first\r\nQUIT :bye\n\nthird\rKICK #chat alice")
+            .unwrap();
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn authenticates_with_sasl() {
+        let (port, server) = serve(|server| {
+            server.expect("CAP REQ account-tag");
+            server.expect("CAP REQ sasl");
+            server.expect("NICK bot");
+            server.expect("USER bot 0 * Bot");
+            server.send(":server CAP * NAK :account-tag");
+            server.send(":server CAP * ACK :sasl");
+            server.expect("AUTHENTICATE PLAIN");
+            server.send("AUTHENTICATE +");
+            server.expect(&format!("AUTHENTICATE {}", STANDARD.encode("bot\0bot\0secret")));
+            server.send(":server 903 bot :SASL authentication successful");
+            server.expect("CAP END");
+            server.send(":server 001 bot :Welcome");
+            server.expect("JOIN #chat");
+        });
+
+        let auth = IrcAuth::Sasl {
+            username: "bot".to_string(),
+            password: "secret".to_string(),
+        };
+        let mut client = IrcClient::connect(config(port, auth)).unwrap();
+        // Everything the server sends is protocol, the read ends when it hangs up
+        while client.read().is_err_and(|err| is_timeout(&err)) {}
+        server.join().unwrap();
+    }
+}
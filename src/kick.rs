@@ -0,0 +1,723 @@
+use std::{env, fmt::Display, net::TcpStream};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::{from_str, from_value, json, Value};
+use tungstenite::{connect, stream::MaybeTlsStream, Message, WebSocket};
+
+use crate::{
+    connector::{is_timeout, set_read_timeout, IncomingEvent, PlatformConnector, API_TIMEOUT},
+    messages::{parse_hex_color, parse_timestamp, Badge, Emote, Platform, PlatformEvent, PlatformInfo, PlatformMessage, ReplyContext},
+    moderation::ModerationAction,
+};
+
+// Public Pusher app key used by the Kick web client
+static DEFAULT_KICK_PUSHER_URL: &str =
+    "wss://ws-us2.pusher.com/app/32cbd69e4b950bf97679?protocol=7&client=js&version=8.4.0-rc2&flash=false";
+static DEFAULT_KICK_API_URL: &str = "https://kick.com/api/v2";
+static DEFAULT_KICK_USER_URL: &str = "https://kick.com/api/v1/user";
+
+impl Platform {
+    pub const KICK: Platform = Platform::new("kick");
+}
+
+#[derive(Debug, Clone)]
+pub enum KickMessage {
+    ConnectionEstablished,
+    Ping,
+    Chat(ChatMessageData),
+    Subscription {
+        username: String,
+        months: u32,
+    },
+    GiftedSubscriptions {
+        gifter: String,
+        gifted: Vec<String>,
+    },
+    Host {
+        host: String,
+        viewers: u32,
+        msg: String,
+    },
+    MessageDeleted {
+        message_id: String,
+    },
+    UserBanned {
+        username: String,
+        banned_by: String,
+        expires_at: Option<DateTime<Utc>>,
+    },
+    Follow {
+        username: String,
+    },
+    Unimplemented {
+        event: String,
+    },
+}
+
+impl KickMessage {
+    /// The event to report for this message, `None` for the Pusher protocol ones.
+    pub fn into_event(self) -> Option<PlatformEvent> {
+        let platform = Platform::KICK;
+        let event = match self {
+            KickMessage::Chat(chat) => PlatformMessage::from(chat).into(),
+            KickMessage::Subscription { username, months } => PlatformEvent::Subscription {
+                platform,
+                user: username,
+                months: Some(months),
+                tier: None,
+                msg: None,
+            },
+            KickMessage::GiftedSubscriptions { gifter, gifted } => PlatformEvent::GiftedSubscriptions {
+                platform,
+                gifter,
+                count: gifted.len() as u32,
+                recipients: gifted,
+            },
+            KickMessage::Host { host, viewers, .. } => PlatformEvent::Raid {
+                platform,
+                user: host,
+                viewers,
+            },
+            KickMessage::MessageDeleted { message_id } => PlatformEvent::MessageDeleted {
+                platform,
+                platform_id: message_id,
+            },
+            KickMessage::UserBanned {
+                username,
+                banned_by,
+                expires_at,
+            } => PlatformEvent::Moderation {
+                platform,
+                moderator: Some(banned_by),
+                action: match expires_at.and_then(|expires_at| (expires_at - Utc::now()).to_std().ok()) {
+                    Some(duration) => ModerationAction::Timeout {
+                        user_id: username,
+                        duration,
+                    },
+                    None => ModerationAction::Ban { user_id: username },
+                },
+            },
+            KickMessage::Follow { username } => PlatformEvent::Follow { platform, user: username },
+            KickMessage::ConnectionEstablished | KickMessage::Ping | KickMessage::Unimplemented { .. } => return None,
+        };
+        Some(event)
+    }
+}
+
+impl From<ChatMessageData> for PlatformMessage {
+    fn from(chat: ChatMessageData) -> Self {
+        let emotes = parse_emotes(&chat.content);
+        let identity = chat.sender.identity;
+        PlatformMessage {
+            platform_id: Some(chat.id),
+            sent_at: parse_timestamp(&chat.created_at),
+            sender_id: Some(chat.sender.id.to_string()),
+            display_name: Some(chat.sender.username.clone()),
+            color: parse_hex_color(&identity.color),
+            badges: identity
+                .badges
+                .iter()
+                .map(|badge| Badge::new(&badge.badge_type, badge.count.map(|count| count.to_string()).as_deref()))
+                .collect(),
+            emotes,
+            channel: Some(chat.chatroom_id.to_string()),
+            reply: chat.metadata.and_then(|metadata| {
+                Some(ReplyContext {
+                    platform_id: metadata.original_message?.id,
+                    sender: metadata.original_sender.map(|sender| sender.username),
+                    msg: None,
+                })
+            }),
+            ..PlatformMessage::new(Platform::KICK, chat.sender.slug, chat.content)
+        }
+    }
+}
+
+/// Kick sends emotes inline in the text as `[emote:37226:KEKW]`.
+fn parse_emotes(content: &str) -> Vec<Emote> {
+    let mut emotes = Vec::new();
+    let mut rest = content;
+    let mut offset = 0;
+    while let Some(start) = rest.find("[emote:") {
+        let Some(len) = rest[start..].find(']') else {
+            break;
+        };
+        let emote = &rest[start..start + len + 1];
+        if let Some((id, name)) = emote["[emote:".len()..emote.len() - 1].split_once(':') {
+            let char_start = offset + rest[..start].chars().count();
+            emotes.push(Emote {
+                id: id.to_string(),
+                name: name.to_string(),
+                range: char_start..char_start + emote.chars().count(),
+            });
+        }
+        offset += rest[..start + len + 1].chars().count();
+        rest = &rest[start + len + 1..];
+    }
+    emotes
+}
+
+impl Display for KickMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KickMessage::ConnectionEstablished => write!(f, "ConnectionEstablished"),
+            KickMessage::Ping => write!(f, "Ping"),
+            KickMessage::Chat(chat) => write!(f, "Chat ( sender: {}, msg: {} )", chat.sender.slug, chat.content),
+            KickMessage::Subscription { username, months } => write!(f, "Subscription ( username: {username}, months: {months} )"),
+            KickMessage::GiftedSubscriptions { gifter, gifted } => {
+                write!(f, "GiftedSubscriptions ( gifter: {gifter}, gifted: {} )", gifted.join(", "))
+            }
+            KickMessage::Host { host, viewers, msg } => write!(f, "Host ( host: {host}, viewers: {viewers}, msg: {msg} )"),
+            KickMessage::MessageDeleted { message_id } => write!(f, "MessageDeleted ( message_id: {message_id} )"),
+            KickMessage::UserBanned { username, banned_by, .. } => {
+                write!(f, "UserBanned ( username: {username}, banned_by: {banned_by} )")
+            }
+            KickMessage::Follow { username } => write!(f, "Follow ( username: {username} )"),
+            KickMessage::Unimplemented { event } => write!(f, "Unimplemented ( event: {event} )"),
+        }
+    }
+}
+
+/// A Pusher frame. `data` is usually a JSON document encoded as a string.
+#[derive(Debug, Deserialize)]
+struct PusherFrame {
+    event: String,
+    #[serde(default)]
+    data: Value,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatMessageData {
+    id: String,
+    chatroom_id: u64,
+    content: String,
+    #[serde(default)]
+    created_at: String,
+    sender: ChatMessageSender,
+    /// Only for replies
+    #[serde(default)]
+    metadata: Option<ChatMessageMetadata>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ChatMessageSender {
+    id: u64,
+    username: String,
+    slug: String,
+    #[serde(default)]
+    identity: ChatMessageIdentity,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct ChatMessageIdentity {
+    color: String,
+    badges: Vec<ChatMessageBadge>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ChatMessageBadge {
+    #[serde(rename = "type")]
+    badge_type: String,
+    #[serde(default)]
+    count: Option<u32>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ChatMessageMetadata {
+    #[serde(default)]
+    original_sender: Option<ChatMessageOriginalSender>,
+    #[serde(default)]
+    original_message: Option<ChatMessageOriginalMessage>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ChatMessageOriginalSender {
+    username: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ChatMessageOriginalMessage {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubscriptionData {
+    username: String,
+    #[serde(default)]
+    months: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct GiftedSubscriptionsData {
+    gifter_username: String,
+    #[serde(default)]
+    gifted_usernames: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamHostData {
+    host_username: String,
+    #[serde(default)]
+    number_viewers: u32,
+    #[serde(default)]
+    optional_message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessageDeletedData {
+    message: MessageDeletedMessage,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessageDeletedMessage {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct UserBannedData {
+    user: UserBannedUser,
+    banned_by: UserBannedUser,
+    /// Only for temporary bans
+    #[serde(default)]
+    expires_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UserBannedUser {
+    username: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct FollowersUpdatedData {
+    #[serde(default)]
+    username: Option<String>,
+    #[serde(default)]
+    followed: bool,
+}
+
+fn parse_data<T: for<'de> Deserialize<'de>>(data: Value) -> Result<T> {
+    match data {
+        Value::String(data) => Ok(from_str(&data)?),
+        data => Ok(from_value(data)?),
+    }
+}
+
+impl TryFrom<&str> for KickMessage {
+    type Error = anyhow::Error;
+
+    fn try_from(raw: &str) -> Result<Self> {
+        let frame: PusherFrame = from_str(raw)?;
+        Ok(match frame.event.as_str() {
+            "pusher:connection_established" => KickMessage::ConnectionEstablished,
+            "pusher:ping" => KickMessage::Ping,
+            "App\\Events\\ChatMessageEvent" => KickMessage::Chat(parse_data(frame.data)?),
+            "App\\Events\\SubscriptionEvent" => {
+                let data: SubscriptionData = parse_data(frame.data)?;
+                KickMessage::Subscription {
+                    username: data.username,
+                    months: data.months,
+                }
+            }
+            "App\\Events\\GiftedSubscriptionsEvent" => {
+                let data: GiftedSubscriptionsData = parse_data(frame.data)?;
+                KickMessage::GiftedSubscriptions {
+                    gifter: data.gifter_username,
+                    gifted: data.gifted_usernames,
+                }
+            }
+            "App\\Events\\StreamHostEvent" => {
+                let data: StreamHostData = parse_data(frame.data)?;
+                KickMessage::Host {
+                    host: data.host_username,
+                    viewers: data.number_viewers,
+                    msg: data.optional_message,
+                }
+            }
+            "App\\Events\\MessageDeletedEvent" => {
+                let data: MessageDeletedData = parse_data(frame.data)?;
+                KickMessage::MessageDeleted {
+                    message_id: data.message.id,
+                }
+            }
+            "App\\Events\\UserBannedEvent" => {
+                let data: UserBannedData = parse_data(frame.data)?;
+                KickMessage::UserBanned {
+                    username: data.user.username,
+                    banned_by: data.banned_by.username,
+                    expires_at: data.expires_at.and_then(|expires_at| parse_timestamp(&expires_at)),
+                }
+            }
+            // Unfollows and anonymous updates only change the count
+            "App\\Events\\FollowersUpdated" => match parse_data::<FollowersUpdatedData>(frame.data)? {
+                FollowersUpdatedData {
+                    username: Some(username),
+                    followed: true,
+                } => KickMessage::Follow { username },
+                _ => KickMessage::Unimplemented { event: frame.event },
+            },
+            _ => KickMessage::Unimplemented { event: frame.event },
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct KickConfig {
+    pub pusher_url: String,
+    pub api_url: String,
+    /// Tells who `token` belongs to
+    pub user_url: String,
+    pub chatroom_id: String,
+    /// Needed for channel events like follows, chat works without it
+    pub channel_id: Option<String>,
+    /// Needed to send messages, reading the chat is anonymous
+    pub token: Option<String>,
+}
+
+impl KickConfig {
+    pub fn from_env() -> Option<Self> {
+        Some(Self {
+            pusher_url: env::var("KICK_PUSHER_URL").unwrap_or(DEFAULT_KICK_PUSHER_URL.to_string()),
+            api_url: env::var("KICK_API_URL").unwrap_or(DEFAULT_KICK_API_URL.to_string()),
+            user_url: env::var("KICK_USER_URL").unwrap_or(DEFAULT_KICK_USER_URL.to_string()),
+            chatroom_id: env::var("KICK_CHATROOM_ID").ok()?,
+            channel_id: env::var("KICK_CHANNEL_ID").ok(),
+            token: env::var("KICK_TOKEN").ok(),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct KickUser {
+    id: u64,
+}
+
+pub struct KickChat {
+    config: KickConfig,
+    stream: WebSocket<MaybeTlsStream<TcpStream>>,
+    client: Client,
+    /// User id of the bot account, to recognize its own messages
+    bot_id: Option<String>,
+}
+
+impl KickChat {
+    pub fn connect(config: KickConfig) -> Result<Self> {
+        let (stream, _) = connect(&config.pusher_url)?;
+        set_read_timeout(&stream)?;
+        let client = Client::new();
+        let bot_id = match bot_id(&client, &config) {
+            Ok(bot_id) => bot_id,
+            Err(err) => {
+                eprintln!(
+                    "WARN - Cannot get the Kick user of KICK_TOKEN, its messages may be answered: {:?}",
+                    err
+                );
+                None
+            }
+        };
+        Ok(Self {
+            config,
+            stream,
+            client,
+            bot_id,
+        })
+    }
+
+    /// Whether the message was sent with `KICK_TOKEN`.
+    pub fn is_bot(&self, message: &PlatformMessage) -> bool {
+        self.bot_id.is_some() && message.sender_id == self.bot_id
+    }
+
+    /// Reads the next frame, answering the Pusher protocol ones (subscription and pings) on the way.
+    pub fn read(&mut self) -> Result<KickMessage> {
+        let raw = self.stream.read()?;
+        let raw = raw.to_text()?;
+        let message = KickMessage::try_from(raw).unwrap_or_else(|err| {
+            eprintln!("WARN - Cannot parse Kick message {raw}: {:?}", err);
+            KickMessage::Unimplemented { event: raw.to_string() }
+        });
+        match message {
+            KickMessage::ConnectionEstablished => {
+                let mut channels = vec![format!("chatrooms.{}.v2", self.config.chatroom_id)];
+                if let Some(channel_id) = &self.config.channel_id {
+                    channels.push(format!("channel.{channel_id}"));
+                }
+                for channel in channels {
+                    self.send_frame(json!({ "event": "pusher:subscribe", "data": { "auth": "", "channel": channel } }))?;
+                }
+            }
+            KickMessage::Ping => {
+                self.send_frame(json!({ "event": "pusher:pong", "data": {} }))?;
+            }
+            _ => {}
+        }
+        Ok(message)
+    }
+
+    fn send_frame(&mut self, frame: Value) -> Result<()> {
+        self.stream.send(Message::Text(frame.to_string()))?;
+        Ok(())
+    }
+
+    /// Sends a chat message. The Pusher socket is read-only, so this goes through the HTTP API.
+    pub fn send_message(&self, msg: &str) -> Result<()> {
+        let token = self
+            .config
+            .token
+            .as_ref()
+            .ok_or(anyhow::Error::msg("KICK_TOKEN to be defined to send messages"))?;
+        self.client
+            .post(format!("{}/messages/send/{}", self.config.api_url, self.config.chatroom_id))
+            .bearer_auth(token)
+            .json(&json!({ "content": msg, "type": "message" }))
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Id of the user `KICK_TOKEN` belongs to, `None` without a token.
+fn bot_id(client: &Client, config: &KickConfig) -> Result<Option<String>> {
+    let Some(token) = &config.token else {
+        return Ok(None);
+    };
+    let user = client
+        .get(&config.user_url)
+        .bearer_auth(token)
+        .timeout(API_TIMEOUT)
+        .send()?
+        .error_for_status()?
+        .json::<KickUser>()?;
+    Ok(Some(user.id.to_string()))
+}
+
+pub fn connector() -> Option<Box<dyn PlatformConnector>> {
+    Some(Box::new(KickConnector {
+        config: KickConfig::from_env()?,
+        chat: None,
+    }))
+}
+
+pub struct KickConnector {
+    config: KickConfig,
+    chat: Option<KickChat>,
+}
+
+impl KickConnector {
+    fn chat(&mut self) -> Result<&mut KickChat> {
+        self.chat.as_mut().ok_or(anyhow::Error::msg("Kick chat is not connected"))
+    }
+}
+
+impl PlatformConnector for KickConnector {
+    fn info(&self) -> PlatformInfo {
+        PlatformInfo {
+            platform: Platform::KICK,
+            label: "Kick",
+            color: [83, 252, 24],
+        }
+    }
+
+    fn connect(&mut self) -> Result<()> {
+        self.chat = Some(KickChat::connect(self.config.clone())?);
+        Ok(())
+    }
+
+    fn poll(&mut self) -> Result<Vec<IncomingEvent>> {
+        let chat = self.chat()?;
+        let message = match chat.read() {
+            Ok(message) => message,
+            Err(err) if is_timeout(&err) => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        let event = match message.into_event() {
+            Some(PlatformEvent::Chat(mut message)) => {
+                message.is_bot = chat.is_bot(&message);
+                PlatformEvent::Chat(message)
+            }
+            Some(event) => event,
+            None => return Ok(Vec::new()),
+        };
+        Ok(vec![IncomingEvent::new(event, None)])
+    }
+
+    fn send_message(&mut self, _channel: Option<&str>, msg: &str) -> Result<()> {
+        self.chat()?.send_message(msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpListener, thread};
+
+    use tungstenite::accept;
+
+    use super::*;
+    use crate::fake_http::{ok, serve};
+
+    fn expect(socket: &mut WebSocket<TcpStream>, expected: Value) {
+        let frame = socket.read().unwrap();
+        assert_eq!(from_str::<Value>(frame.to_text().unwrap()).unwrap(), expected);
+    }
+
+    fn send(socket: &mut WebSocket<TcpStream>, frame: Value) {
+        socket.send(Message::Text(frame.to_string())).unwrap();
+    }
+
+    fn connector(port: u16, channel_id: Option<&str>, user_url: Option<String>) -> KickConnector {
+        KickConnector {
+            config: KickConfig {
+                pusher_url: format!("ws://127.0.0.1:{port}"),
+                api_url: DEFAULT_KICK_API_URL.to_string(),
+                token: user_url.as_ref().map(|_| "token".to_string()),
+                user_url: user_url.unwrap_or_default(),
+                chatroom_id: "7".to_string(),
+                channel_id: channel_id.map(str::to_string),
+            },
+            chat: None,
+        }
+    }
+
+    fn chat(sender_id: u64, username: &str, content: &str) -> Value {
+        let chat = json!({
+            "id": format!("m{sender_id}"),
+            "chatroom_id": 7,
+            "content": content,
+            "created_at": "2024-01-01T00:00:00Z",
+            "sender": {"id": sender_id, "username": username, "slug": username.to_lowercase(), "identity": {"color": "#FF0000", "badges": []}}
+        });
+        json!({"event": "App\\Events\\ChatMessageEvent", "data": chat.to_string()})
+    }
+
+    /// Polls until the server hangs up.
+    fn events(connector: &mut KickConnector) -> Vec<PlatformEvent> {
+        let mut events = Vec::new();
+        loop {
+            match connector.poll() {
+                Ok(incoming) => events.extend(incoming.into_iter().map(|incoming| incoming.event)),
+                Err(_) => return events,
+            }
+        }
+    }
+
+    #[test]
+    fn protocol_frames_are_not_events() {
+        for frame in [
+            json!({"event": "pusher:connection_established", "data": "{\"socket_id\":\"1.2\"}"}),
+            json!({"event": "pusher:ping", "data": {}}),
+            json!({"event": "pusher_internal:subscription_succeeded", "channel": "chatrooms.1.v2"}),
+            json!({"event": "App\\Events\\FollowersUpdated", "data": "{\"followersCount\":10}"}),
+        ] {
+            let message = KickMessage::try_from(frame.to_string().as_str()).unwrap();
+            assert_eq!(message.clone().into_event(), None, "{message}");
+        }
+    }
+
+    #[test]
+    fn reads_chat_from_pusher() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let mut socket = accept(listener.accept().unwrap().0).unwrap();
+            send(
+                &mut socket,
+                json!({"event": "pusher:connection_established", "data": "{\"socket_id\":\"1.2\"}"}),
+            );
+            expect(
+                &mut socket,
+                json!({"event": "pusher:subscribe", "data": {"auth": "", "channel": "chatrooms.7.v2"}}),
+            );
+            expect(
+                &mut socket,
+                json!({"event": "pusher:subscribe", "data": {"auth": "", "channel": "channel.9"}}),
+            );
+            send(&mut socket, json!({"event": "pusher:ping", "data": {}}));
+            expect(&mut socket, json!({"event": "pusher:pong", "data": {}}));
+            let chat = json!({
+                "id": "m1",
+                "chatroom_id": 7,
+                "content": "!today [emote:37226:KEKW]",
+                "created_at": "2024-01-01T00:00:00Z",
+                "sender": {"id": 42, "username": "Viewer", "slug": "viewer", "identity": {
+                    "color": "#FF0000", "badges": [{"type": "moderator", "text": "Moderator"}]
+                }}
+            });
+            send(
+                &mut socket,
+                json!({"event": "App\\Events\\ChatMessageEvent", "data": chat.to_string()}),
+            );
+            send(
+                &mut socket,
+                json!({"event": "App\\Events\\PinnedMessageCreatedEvent", "data": "{}"}),
+            );
+            send(
+                &mut socket,
+                json!({"event": "App\\Events\\FollowersUpdated", "data": {"username": "fan", "followed": true}}),
+            );
+            socket.close(None).unwrap();
+            while socket.read().is_ok() {}
+        });
+
+        let mut connector = connector(port, Some("9"), None);
+        connector.connect().unwrap();
+        let events = events(&mut connector);
+        server.join().unwrap();
+
+        let [PlatformEvent::Chat(message), follow] = events.as_slice() else {
+            panic!("Expected a chat message and a follow, got {events:?}");
+        };
+        assert_eq!(message.sender, "viewer");
+        assert_eq!(message.sender_id.as_deref(), Some("42"));
+        assert_eq!(message.channel.as_deref(), Some("7"));
+        assert_eq!(message.badges, [Badge::new("moderator", None)]);
+        assert_eq!(message.emotes.len(), 1);
+        assert_eq!(
+            follow,
+            &PlatformEvent::Follow {
+                platform: Platform::KICK,
+                user: "fan".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn own_messages_are_marked() {
+        let (user_url, requests, api) = serve(vec![ok(json!({"id": 42, "username": "Bot"}))]);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let mut socket = accept(listener.accept().unwrap().0).unwrap();
+            send(
+                &mut socket,
+                json!({"event": "pusher:connection_established", "data": "{\"socket_id\":\"1.2\"}"}),
+            );
+            expect(
+                &mut socket,
+                json!({"event": "pusher:subscribe", "data": {"auth": "", "channel": "chatrooms.7.v2"}}),
+            );
+            send(&mut socket, chat(42, "Bot", "!discord"));
+            send(&mut socket, chat(43, "Viewer", "!discord"));
+            socket.close(None).unwrap();
+            while socket.read().is_ok() {}
+        });
+
+        let mut connector = connector(port, None, Some(format!("{user_url}/api/v1/user")));
+        connector.connect().unwrap();
+        let events = events(&mut connector);
+        server.join().unwrap();
+        api.join().unwrap();
+
+        assert_eq!(requests.recv().unwrap(), "GET /api/v1/user HTTP/1.1");
+        let is_bot: Vec<(&str, bool)> = events
+            .iter()
+            .filter_map(|event| match event {
+                PlatformEvent::Chat(message) => Some((message.sender.as_str(), message.is_bot)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(is_bot, [("bot", true), ("viewer", false)]);
+    }
+}
@@ -1,38 +1,80 @@
 use std::{
+    env,
     sync::{Arc, Mutex},
-    thread,
 };
 
 use anyhow::Result;
-use tokio::sync::mpsc::unbounded_channel;
 
-use crate::{command::CommandHandler, gui::run, messages::PlatformMessage};
+use crate::{
+    bus::{all_events, chat_only, EventBus},
+    command::CommandHandler,
+    connector::Registry,
+    gui::run,
+    timer::Timers,
+};
 
+mod bus;
+mod chat_log;
 mod command;
+mod commands_file;
+mod connector;
+mod cooldown;
+mod discord;
+mod export;
+#[cfg(test)]
+mod fake_http;
 mod gui;
+mod help;
+mod import;
+mod irc;
+mod kick;
 mod messages;
+mod moderation;
+mod permission;
+mod sqlite;
+mod store;
+mod template;
+mod timer;
+mod trigger;
 mod twitch;
+mod twitter;
 mod youtube;
 mod youtube_model;
 
 fn main() -> Result<()> {
     println!("ttv-bot");
 
-    let command_handler = CommandHandler::new();
+    let mut command_handler = CommandHandler::new(store::from_env()?)?;
 
-    let (sender, receiver) = unbounded_channel::<PlatformMessage>();
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("import") => return import::cli(&mut command_handler, &args[1..]),
+        Some("export") => return export::cli(&command_handler, &args[1..]),
+        Some(other) => return Err(anyhow::Error::msg(format!("Unknown subcommand {other}, expected import or export"))),
+        None => {}
+    }
 
-    let command_handler = Arc::new(Mutex::new(command_handler));
-    let twitch_command_handler = command_handler.clone();
-    let twitch_sender = sender.clone();
-    let youtube_sender = sender.clone();
+    // Like the commands file, a timers file that can't be read is never overwritten
+    let timers = Timers::load()?;
 
-    let _twitch_thread = thread::spawn(|| twitch::listen(twitch_command_handler, twitch_sender));
-    let _youtube_thread = thread::spawn(|| youtube::listen(youtube_sender));
+    // Subscribe before the connectors start so no event is missed
+    let bus = EventBus::from_env();
+    let gui_subscription = bus.subscribe("gui", all_events());
+    let timers_subscription = bus.subscribe("timers", chat_only());
+    if let Err(err) = chat_log::start(&bus) {
+        eprintln!("ERROR - Cannot open chat log: {:?}", err);
+    }
+    if let Err(err) = sqlite::start_history(&bus) {
+        eprintln!("ERROR - Cannot keep the chat history: {:?}", err);
+    }
 
-    let gui_command_handler = command_handler.clone();
+    let command_handler = Arc::new(Mutex::new(command_handler));
+    command::watch(command_handler.clone());
+    let registry = Arc::new(Registry::start(command_handler.clone(), bus));
+    let timers = Arc::new(Mutex::new(timers));
+    timer::start(timers.clone(), registry.clone(), timers_subscription);
 
-    let _ = run(gui_command_handler, receiver);
+    let _ = run(command_handler, registry, timers, gui_subscription);
 
     Ok(())
 
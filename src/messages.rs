@@ -1,10 +1,447 @@
-pub enum Platform {
-    Twitch,
-    Youtube,
+use std::{
+    borrow::Cow,
+    fmt,
+    ops::Range,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{connector::ConnectorStatus, moderation::ModerationAction};
+
+/// Identifies a platform. It is open on purpose: each connector declares its own constant
+/// (`Platform::TWITCH`, `Platform::KICK`...) next to its implementation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Platform(Cow<'static, str>);
+
+/// Longest chat message each platform takes, in characters
+static MESSAGE_LIMITS: [(Platform, usize); 6] = [
+    (Platform::DISCORD, 2000),
+    (Platform::IRC, 400),
+    (Platform::KICK, 500),
+    (Platform::TWITCH, 500),
+    (Platform::TWITTER, 280),
+    (Platform::YOUTUBE, 200),
+];
+static DEFAULT_MESSAGE_LIMIT: usize = 200;
+
+impl Platform {
+    pub const fn new(id: &'static str) -> Self {
+        Self(Cow::Borrowed(id))
+    }
+
+    pub fn id(&self) -> &str {
+        &self.0
+    }
+
+    /// Longest message that can be sent to the platform, in characters.
+    pub fn message_limit(&self) -> usize {
+        MESSAGE_LIMITS
+            .iter()
+            .find(|(platform, _)| platform == self)
+            .map_or(DEFAULT_MESSAGE_LIMIT, |(_, limit)| *limit)
+    }
+}
+
+impl From<&str> for Platform {
+    fn from(id: &str) -> Self {
+        Self(Cow::Owned(id.to_string()))
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Serialize for Platform {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.id())
+    }
+}
+
+impl<'de> Deserialize<'de> for Platform {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self(Cow::Owned(String::deserialize(deserializer)?)))
+    }
+}
+
+/// A platform, and one of its channels if given. Where a timer posts (to the default channel if
+/// none) or where a command works (on every channel if none).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatTarget {
+    pub platform: Platform,
+    pub channel: Option<String>,
+}
+
+impl ChatTarget {
+    /// `platform` or `platform:channel`, like `twitch` or `discord:1234`
+    pub fn parse(target: &str) -> Self {
+        match target.trim().split_once(':') {
+            Some((platform, channel)) => Self {
+                platform: platform.trim().into(),
+                channel: Some(channel.trim().to_string()),
+            },
+            None => Self {
+                platform: target.trim().into(),
+                channel: None,
+            },
+        }
+    }
+
+    pub fn matches(&self, message: &PlatformMessage) -> bool {
+        self.platform == message.platform && (self.channel.is_none() || self.channel == message.channel)
+    }
+}
+
+impl fmt::Display for ChatTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.channel {
+            Some(channel) => write!(f, "{}:{channel}", self.platform),
+            None => write!(f, "{}", self.platform),
+        }
+    }
+}
+
+/// How a platform is presented in the GUI.
+#[derive(Debug, Clone)]
+pub struct PlatformInfo {
+    pub platform: Platform,
+    pub label: &'static str,
+    pub color: [u8; 3],
+}
+
+/// A badge shown next to the sender (`moderator`, `subscriber`...). `version` is whatever the
+/// platform attaches to it, like the number of months for Twitch subscriber badges.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Badge {
+    pub name: String,
+    pub version: Option<String>,
+}
+
+impl Badge {
+    pub fn new(name: &str, version: Option<&str>) -> Self {
+        Self {
+            name: name.to_string(),
+            version: version.map(str::to_string),
+        }
+    }
+}
+
+/// An emote inside the message text. `range` is in chars, not bytes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Emote {
+    pub id: String,
+    pub name: String,
+    pub range: Range<usize>,
+}
+
+/// The message being replied to, as much of it as the platform tells us.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplyContext {
+    pub platform_id: String,
+    pub sender: Option<String>,
+    pub msg: Option<String>,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PlatformMessage {
+    /// Unique id given by us, platform ids are only unique within their platform
+    pub id: String,
+    /// Id of the message on its platform, needed to delete it or reply to it
+    pub platform_id: Option<String>,
+    pub platform: Platform,
+    pub received_at: DateTime<Utc>,
+    /// When the platform says the message was sent
+    pub sent_at: Option<DateTime<Utc>>,
+    /// Login or username, what commands get as sender
     pub sender: String,
+    pub sender_id: Option<String>,
+    pub display_name: Option<String>,
+    pub color: Option<[u8; 3]>,
+    pub badges: Vec<Badge>,
+    pub emotes: Vec<Emote>,
+    /// Channel (or chat room) the message was sent to, for platforms that have several
+    pub channel: Option<String>,
+    pub reply: Option<ReplyContext>,
+    /// Whether the message was sent by the bot account itself
+    pub is_bot: bool,
     pub msg: String,
-    pub platform: Platform,
+}
+
+static MESSAGE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+impl PlatformMessage {
+    /// A message with nothing but the required fields, connectors fill in the rest.
+    pub fn new(platform: Platform, sender: String, msg: String) -> Self {
+        let received_at = Utc::now();
+        let count = MESSAGE_COUNTER.fetch_add(1, Ordering::Relaxed);
+        Self {
+            id: format!("{}-{count}", received_at.timestamp_micros()),
+            platform_id: None,
+            platform,
+            received_at,
+            sent_at: None,
+            sender,
+            sender_id: None,
+            display_name: None,
+            color: None,
+            badges: Vec::new(),
+            emotes: Vec::new(),
+            channel: None,
+            reply: None,
+            is_bot: false,
+            msg,
+        }
+    }
+
+    /// Name to show, the display name if the platform has one.
+    pub fn display_name(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.sender)
+    }
+
+    /// Id to moderate the sender with, falling back to the login for platforms that accept it.
+    pub fn moderation_id(&self) -> &str {
+        self.sender_id.as_deref().unwrap_or(&self.sender)
+    }
+}
+
+/// Everything a connector reports, chat or not. Each variant carries the platform it came from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PlatformEvent {
+    Chat(Box<PlatformMessage>),
+    Subscription {
+        platform: Platform,
+        user: String,
+        months: Option<u32>,
+        tier: Option<String>,
+        msg: Option<String>,
+    },
+    GiftedSubscriptions {
+        platform: Platform,
+        gifter: String,
+        count: u32,
+        /// Empty when the platform does not say who got them
+        recipients: Vec<String>,
+    },
+    /// Super Chats, Super Stickers, tips... `amount` is already formatted with its currency
+    Donation {
+        platform: Platform,
+        user: String,
+        amount: String,
+        msg: Option<String>,
+    },
+    /// Raids and hosts
+    Raid {
+        platform: Platform,
+        user: String,
+        viewers: u32,
+    },
+    Follow {
+        platform: Platform,
+        user: String,
+    },
+    /// A moderation action taken on the platform, by us or anyone else
+    Moderation {
+        platform: Platform,
+        moderator: Option<String>,
+        action: ModerationAction,
+    },
+    MessageDeleted {
+        platform: Platform,
+        platform_id: String,
+    },
+    ConnectionState {
+        platform: Platform,
+        status: ConnectorStatus,
+    },
+}
+
+impl PlatformEvent {
+    pub fn platform(&self) -> &Platform {
+        match self {
+            PlatformEvent::Chat(message) => &message.platform,
+            PlatformEvent::Subscription { platform, .. }
+            | PlatformEvent::GiftedSubscriptions { platform, .. }
+            | PlatformEvent::Donation { platform, .. }
+            | PlatformEvent::Raid { platform, .. }
+            | PlatformEvent::Follow { platform, .. }
+            | PlatformEvent::Moderation { platform, .. }
+            | PlatformEvent::MessageDeleted { platform, .. }
+            | PlatformEvent::ConnectionState { platform, .. } => platform,
+        }
+    }
+}
+
+impl From<PlatformMessage> for PlatformEvent {
+    fn from(message: PlatformMessage) -> Self {
+        PlatformEvent::Chat(Box::new(message))
+    }
+}
+
+impl fmt::Display for PlatformEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlatformEvent::Chat(message) => write!(f, "[{}] {}", message.display_name(), message.msg),
+            PlatformEvent::Subscription {
+                user, months, tier, msg, ..
+            } => {
+                write!(f, "{user} subscribed")?;
+                if let Some(tier) = tier {
+                    write!(f, " ({tier})")?;
+                }
+                if let Some(months) = months {
+                    write!(f, " for {months} months")?;
+                }
+                if let Some(msg) = msg {
+                    write!(f, ": {msg}")?;
+                }
+                Ok(())
+            }
+            PlatformEvent::GiftedSubscriptions {
+                gifter, count, recipients, ..
+            } => match recipients.is_empty() {
+                true => write!(f, "{gifter} gifted {count} subs"),
+                false => write!(f, "{gifter} gifted {count} subs to {}", recipients.join(", ")),
+            },
+            PlatformEvent::Donation { user, amount, msg, .. } => match msg {
+                Some(msg) => write!(f, "{user} donated {amount}: {msg}"),
+                None => write!(f, "{user} donated {amount}"),
+            },
+            PlatformEvent::Raid { user, viewers, .. } => write!(f, "{user} is raiding with {viewers} viewers"),
+            PlatformEvent::Follow { user, .. } => write!(f, "{user} followed"),
+            PlatformEvent::Moderation { moderator, action, .. } => match moderator {
+                Some(moderator) => write!(f, "{moderator}: {action}"),
+                None => write!(f, "{action}"),
+            },
+            PlatformEvent::MessageDeleted { platform_id, .. } => write!(f, "message {platform_id} deleted"),
+            PlatformEvent::ConnectionState { platform, status } => write!(f, "{platform} {status}"),
+        }
+    }
+}
+
+/// Parses `#RRGGBB`.
+pub fn parse_hex_color(color: &str) -> Option<[u8; 3]> {
+    let color = color.strip_prefix('#')?;
+    if color.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(color.get(i..i + 2)?, 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// Parses RFC 3339 timestamps, the format most platform APIs use.
+pub fn parse_timestamp(timestamp: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn message(msg: &str) -> PlatformMessage {
+        PlatformMessage::new(Platform::TWITCH, "viewer".to_string(), msg.to_string())
+    }
+
+    #[test]
+    fn platforms_are_their_ids() {
+        assert_eq!(Platform::from("twitch"), Platform::TWITCH);
+        assert_eq!(serde_json::to_string(&Platform::KICK).unwrap(), "\"kick\"");
+        assert_eq!(serde_json::from_str::<Platform>("\"youtube\"").unwrap(), Platform::YOUTUBE);
+        assert_eq!(Platform::TWITTER.message_limit(), 280);
+        assert_eq!(Platform::from("mastodon").message_limit(), DEFAULT_MESSAGE_LIMIT);
+    }
+
+    #[test]
+    fn targets_match_their_platform_and_channel() {
+        let general = PlatformMessage {
+            channel: Some("general".to_string()),
+            ..PlatformMessage::new(Platform::DISCORD, "viewer".to_string(), "hi".to_string())
+        };
+        let target = ChatTarget::parse(" discord : general ");
+        assert_eq!(target.to_string(), "discord:general");
+        assert!(target.matches(&general));
+        assert!(ChatTarget::parse("discord").matches(&general));
+        assert!(!ChatTarget::parse("discord:links").matches(&general));
+        assert!(!ChatTarget::parse("twitch").matches(&general));
+    }
+
+    #[test]
+    fn messages_get_unique_ids_and_fall_back_to_the_login() {
+        let (first, second) = (message("a"), message("b"));
+        assert_ne!(first.id, second.id);
+        assert_eq!((first.display_name(), first.moderation_id()), ("viewer", "viewer"));
+        let named = PlatformMessage {
+            display_name: Some("Viewer".to_string()),
+            sender_id: Some("1234".to_string()),
+            ..first
+        };
+        assert_eq!((named.display_name(), named.moderation_id()), ("Viewer", "1234"));
+    }
+
+    #[test]
+    fn events_survive_json_and_read_well() {
+        let events = [
+            PlatformEvent::from(message("hello")),
+            PlatformEvent::Subscription {
+                platform: Platform::TWITCH,
+                user: "fan".to_string(),
+                months: Some(3),
+                tier: Some("Tier 1".to_string()),
+                msg: Some("hype".to_string()),
+            },
+            PlatformEvent::GiftedSubscriptions {
+                platform: Platform::KICK,
+                gifter: "generous".to_string(),
+                count: 2,
+                recipients: Vec::new(),
+            },
+            PlatformEvent::Moderation {
+                platform: Platform::YOUTUBE,
+                moderator: Some("mod".to_string()),
+                action: ModerationAction::Timeout {
+                    user_id: "UCviewer".to_string(),
+                    duration: Duration::from_secs(600),
+                },
+            },
+            PlatformEvent::ConnectionState {
+                platform: Platform::TWITCH,
+                status: ConnectorStatus::Failed("timed out".to_string()),
+            },
+        ];
+        let lines: Vec<String> = events.iter().map(PlatformEvent::to_string).collect();
+        assert_eq!(
+            lines,
+            [
+                "[viewer] hello",
+                "fan subscribed (Tier 1) for 3 months: hype",
+                "generous gifted 2 subs",
+                "mod: timeout UCviewer for 600s",
+                "twitch failed: timed out",
+            ]
+        );
+        for event in events {
+            let json = serde_json::to_string(&event).unwrap();
+            assert_eq!(serde_json::from_str::<PlatformEvent>(&json).unwrap(), event);
+        }
+    }
+
+    #[test]
+    fn parses_colors_and_timestamps() {
+        assert_eq!(parse_hex_color("#FF8000"), Some([255, 128, 0]));
+        assert_eq!(parse_hex_color("FF8000"), None);
+        assert_eq!(parse_hex_color("#FF80"), None);
+        assert_eq!(parse_hex_color("#GG8000"), None);
+        assert_eq!(
+            parse_timestamp("2024-01-01T19:30:00+01:00"),
+            parse_timestamp("2024-01-01T18:30:00Z")
+        );
+        assert_eq!(parse_timestamp("yesterday"), None);
+    }
 }
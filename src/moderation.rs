@@ -0,0 +1,99 @@
+use std::{fmt, time::Duration};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::messages::Platform;
+
+/// A moderation action expressed in platform-agnostic terms. `user_id` and `message_id` are the
+/// ids the platform itself uses (Twitch user id or login, YouTube channel id, ...).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ModerationAction {
+    DeleteMessage { message_id: String },
+    Timeout { user_id: String, duration: Duration },
+    Ban { user_id: String },
+    Unban { user_id: String },
+    AddModerator { user_id: String },
+    RemoveModerator { user_id: String },
+}
+
+impl fmt::Display for ModerationAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ModerationAction::DeleteMessage { message_id } => write!(f, "delete message {message_id}"),
+            ModerationAction::Timeout { user_id, duration } => write!(f, "timeout {user_id} for {}s", duration.as_secs()),
+            ModerationAction::Ban { user_id } => write!(f, "ban {user_id}"),
+            ModerationAction::Unban { user_id } => write!(f, "unban {user_id}"),
+            ModerationAction::AddModerator { user_id } => write!(f, "add moderator {user_id}"),
+            ModerationAction::RemoveModerator { user_id } => write!(f, "remove moderator {user_id}"),
+        }
+    }
+}
+
+pub trait Moderator: Send + Sync {
+    fn platform(&self) -> Platform;
+    fn apply(&self, action: &ModerationAction) -> Result<()>;
+    /// Whether `Unban` can lift a ban on `user_id`. Platforms that need the id of the ban itself
+    /// only know the ones they issued.
+    fn can_unban(&self, _user_id: &str) -> bool {
+        true
+    }
+}
+
+/// Entry point for moderating any platform. The GUI (and anything else that wants to moderate)
+/// talks to this instead of to the platform clients directly.
+pub struct ModerationHandler {
+    moderators: Vec<Box<dyn Moderator>>,
+}
+
+impl ModerationHandler {
+    pub fn new(moderators: Vec<Box<dyn Moderator>>) -> Self {
+        Self { moderators }
+    }
+
+    pub fn supports(&self, platform: &Platform) -> bool {
+        self.moderators.iter().any(|moderator| moderator.platform() == *platform)
+    }
+
+    pub fn can_unban(&self, platform: &Platform, user_id: &str) -> bool {
+        self.moderators
+            .iter()
+            .any(|moderator| moderator.platform() == *platform && moderator.can_unban(user_id))
+    }
+
+    pub fn apply(&self, platform: &Platform, action: &ModerationAction) -> Result<()> {
+        match self.moderators.iter().find(|moderator| moderator.platform() == *platform) {
+            Some(moderator) => moderator.apply(action),
+            None => Err(anyhow::Error::msg(format!("Moderation is not available for {:?}", platform))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct SessionBans(Vec<&'static str>);
+
+    impl Moderator for SessionBans {
+        fn platform(&self) -> Platform {
+            Platform::YOUTUBE
+        }
+
+        fn apply(&self, _action: &ModerationAction) -> Result<()> {
+            Ok(())
+        }
+
+        fn can_unban(&self, user_id: &str) -> bool {
+            self.0.contains(&user_id)
+        }
+    }
+
+    #[test]
+    fn unban_is_offered_only_where_the_moderator_can_lift_it() {
+        let handler = ModerationHandler::new(vec![Box::new(SessionBans(vec!["UCbanned"]))]);
+        assert!(handler.can_unban(&Platform::YOUTUBE, "UCbanned"));
+        assert!(!handler.can_unban(&Platform::YOUTUBE, "UCother"));
+        assert!(!handler.can_unban(&Platform::TWITCH, "UCbanned"));
+    }
+}
@@ -0,0 +1,202 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env, fmt,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::messages::{Platform, PlatformMessage};
+
+/// Who can use a command. Each level includes the ones before it. No platform tells in chat who
+/// follows the channel, so nobody gets `Follower` from a badge and follower commands are in
+/// practice for subscribers and up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PermissionLevel {
+    #[default]
+    Everyone,
+    Follower,
+    Subscriber,
+    Vip,
+    Moderator,
+    Broadcaster,
+    BotAdmin,
+}
+
+impl PermissionLevel {
+    pub const ALL: [PermissionLevel; 7] = [
+        PermissionLevel::Everyone,
+        PermissionLevel::Follower,
+        PermissionLevel::Subscriber,
+        PermissionLevel::Vip,
+        PermissionLevel::Moderator,
+        PermissionLevel::Broadcaster,
+        PermissionLevel::BotAdmin,
+    ];
+
+    /// Level by its name in snake case, like `bot_admin`.
+    pub fn parse(level: &str) -> Option<Self> {
+        serde_json::from_value(serde_json::Value::String(level.to_string())).ok()
+    }
+
+    /// Level given by a platform badge, `None` for badges that give nothing.
+    fn from_badge(name: &str) -> Option<Self> {
+        match name {
+            "broadcaster" => Some(PermissionLevel::Broadcaster),
+            "moderator" => Some(PermissionLevel::Moderator),
+            "vip" => Some(PermissionLevel::Vip),
+            // YouTube members and Twitch/Kick founders are subscribers too
+            "subscriber" | "founder" | "member" => Some(PermissionLevel::Subscriber),
+            "follower" => Some(PermissionLevel::Follower),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PermissionLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PermissionLevel::Everyone => write!(f, "everyone"),
+            PermissionLevel::Follower => write!(f, "follower"),
+            PermissionLevel::Subscriber => write!(f, "subscriber"),
+            PermissionLevel::Vip => write!(f, "VIP"),
+            PermissionLevel::Moderator => write!(f, "moderator"),
+            PermissionLevel::Broadcaster => write!(f, "broadcaster"),
+            PermissionLevel::BotAdmin => write!(f, "bot admin"),
+        }
+    }
+}
+
+/// Bot admins of each platform and the level needed for the admin verbs (set, create, delete,
+/// edit and alias).
+pub struct Permissions {
+    /// Platform id to the user ids of its admins
+    admins: HashMap<String, HashSet<String>>,
+    pub admin_verbs: PermissionLevel,
+}
+
+impl Permissions {
+    /// `BOT_ADMINS` is a comma separated list of `platform:user_id`, `ADMIN_VERBS_LEVEL` one of the
+    /// levels in snake case (moderator by default). Names are not unique on every platform and can be
+    /// changed, so admins are only recognised by id, the services account on IRC.
+    pub fn from_env() -> Self {
+        let mut admins: HashMap<String, HashSet<String>> = HashMap::new();
+        for admin in env::var("BOT_ADMINS").unwrap_or_default().split(',') {
+            match admin.trim().split_once(':') {
+                Some((platform, user)) => {
+                    admins.entry(platform.to_string()).or_default().insert(user.trim().to_string());
+                }
+                None if admin.trim().is_empty() => {}
+                None => eprintln!("WARN - Ignoring bot admin {admin}, expected platform:user_id"),
+            }
+        }
+        let admin_verbs = env::var("ADMIN_VERBS_LEVEL")
+            .ok()
+            .filter(|level| !level.is_empty())
+            .and_then(|level| match PermissionLevel::parse(&level) {
+                Some(level) => Some(level),
+                None => {
+                    eprintln!("WARN - Unknown ADMIN_VERBS_LEVEL {level}, using moderator");
+                    None
+                }
+            })
+            .unwrap_or(PermissionLevel::Moderator);
+        Self { admins, admin_verbs }
+    }
+
+    /// Highest level the sender of the message has.
+    pub fn level(&self, message: &PlatformMessage) -> PermissionLevel {
+        let is_admin = match (self.admins.get(message.platform.id()), &message.sender_id) {
+            // IRC accounts are case insensitive
+            (Some(admins), Some(id)) if message.platform == Platform::IRC => admins.iter().any(|admin| admin.eq_ignore_ascii_case(id)),
+            (Some(admins), Some(id)) => admins.contains(id),
+            _ => false,
+        };
+        if is_admin {
+            return PermissionLevel::BotAdmin;
+        }
+        message
+            .badges
+            .iter()
+            .filter_map(|badge| PermissionLevel::from_badge(&badge.name))
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::Badge;
+
+    fn permissions(admins: &[(&str, &str)]) -> Permissions {
+        let mut map: HashMap<String, HashSet<String>> = HashMap::new();
+        for (platform, user) in admins {
+            map.entry(platform.to_string()).or_default().insert(user.to_string());
+        }
+        Permissions {
+            admins: map,
+            admin_verbs: PermissionLevel::Moderator,
+        }
+    }
+
+    fn message(platform: Platform, sender: &str, sender_id: Option<&str>) -> PlatformMessage {
+        PlatformMessage {
+            sender_id: sender_id.map(str::to_string),
+            ..PlatformMessage::new(platform, sender.to_string(), "!set foo bar".to_string())
+        }
+    }
+
+    #[test]
+    fn admins_are_matched_on_ids_only() {
+        let permissions = permissions(&[("youtube", "UCadmin"), ("twitch", "1234")]);
+        let level = |message| permissions.level(&message);
+
+        assert_eq!(
+            level(message(Platform::YOUTUBE, "Someone", Some("UCadmin"))),
+            PermissionLevel::BotAdmin
+        );
+        // Same display name as an admin id, different channel
+        assert_eq!(
+            level(message(Platform::YOUTUBE, "UCadmin", Some("UCother"))),
+            PermissionLevel::Everyone
+        );
+        assert_eq!(level(message(Platform::YOUTUBE, "UCadmin", None)), PermissionLevel::Everyone);
+        assert_eq!(
+            level(message(Platform::YOUTUBE, "Someone", Some("ucadmin"))),
+            PermissionLevel::Everyone
+        );
+        assert_eq!(level(message(Platform::TWITCH, "1234", None)), PermissionLevel::Everyone);
+        assert_eq!(
+            level(message(Platform::TWITCH, "zartisimo", Some("1234"))),
+            PermissionLevel::BotAdmin
+        );
+    }
+
+    #[test]
+    fn irc_admins_need_an_account() {
+        let permissions = permissions(&[("irc", "Zartisimo")]);
+
+        assert_eq!(
+            permissions.level(&message(Platform::IRC, "zartisimo", None)),
+            PermissionLevel::Everyone
+        );
+        assert_eq!(
+            permissions.level(&message(Platform::IRC, "guest", Some("zartisimo"))),
+            PermissionLevel::BotAdmin
+        );
+    }
+
+    #[test]
+    fn badges_give_their_highest_level() {
+        let permissions = permissions(&[]);
+        let mut message = message(Platform::TWITCH, "viewer", Some("1"));
+        message.badges = vec![
+            Badge::new("subscriber", Some("12")),
+            Badge::new("moderator", None),
+            Badge::new("glitchcon", None),
+        ];
+
+        assert_eq!(permissions.level(&message), PermissionLevel::Moderator);
+    }
+}
@@ -0,0 +1,350 @@
+use std::{env, path::Path, thread, time::Duration};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::{
+    bus::{chat_only, EventBus, Received, Subscription},
+    command::BotCommand,
+    commands_file,
+    messages::{PlatformEvent, PlatformMessage},
+    store::{self, CommandStore, Quote},
+};
+
+static BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+static SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS meta (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS commands (
+    name TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS quotes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    text TEXT NOT NULL,
+    added_by TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS users (
+    platform TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    login TEXT NOT NULL,
+    display_name TEXT,
+    first_seen TEXT NOT NULL,
+    last_seen TEXT NOT NULL,
+    messages INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (platform, user_id)
+);
+CREATE TABLE IF NOT EXISTS chat_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    platform TEXT NOT NULL,
+    channel TEXT,
+    platform_id TEXT,
+    user_id TEXT NOT NULL,
+    msg TEXT NOT NULL,
+    received_at TEXT NOT NULL,
+    data TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS chat_history_received_at ON chat_history (received_at);
+";
+
+/// Commands, quotes, users and chat history in a SQLite database. Each thread opens its own
+/// connection; the database takes care of them writing at the same time.
+pub struct SqliteStore {
+    connection: Connection,
+}
+
+impl SqliteStore {
+    pub fn open(path: &str) -> Result<Self> {
+        let connection = Connection::open(path).map_err(|err| anyhow::Error::msg(format!("Cannot open database {path}: {err}")))?;
+        connection.busy_timeout(BUSY_TIMEOUT)?;
+        // Readers don't block the writer, so the GUI and the connectors don't wait for each other
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(Self { connection })
+    }
+
+    /// Brings over the commands of the JSON file at `path`, only the first time the database is
+    /// used so commands deleted later don't come back. A database that already has commands is
+    /// not touched.
+    pub fn import_json_once(&mut self, path: &str) -> Result<()> {
+        let transaction = self.connection.transaction()?;
+        let imported: Option<String> = transaction
+            .query_row("SELECT value FROM meta WHERE key = 'json_imported'", [], |row| row.get(0))
+            .optional()?;
+        if imported.is_some() {
+            return Ok(());
+        }
+        let empty = transaction.query_row("SELECT COUNT(*) FROM commands", [], |row| row.get::<_, i64>(0))? == 0;
+        if empty && Path::new(path).exists() {
+            let commands = commands_file::read(path)?;
+            eprintln!("INFO - Importing {} commands from {path}", commands.len());
+            for command in &commands {
+                transaction.execute(
+                    "INSERT INTO commands (name, data) VALUES (?1, ?2)",
+                    params![command.name, serde_json::to_string(command)?],
+                )?;
+            }
+        }
+        transaction.execute(
+            "INSERT INTO meta (key, value) VALUES ('json_imported', ?1)",
+            params![Utc::now().to_rfc3339()],
+        )?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Saves the message to the chat history and updates what is known about its sender.
+    pub fn record_chat(&mut self, message: &PlatformMessage) -> Result<()> {
+        let received_at = message.received_at.to_rfc3339();
+        let transaction = self.connection.transaction()?;
+        transaction.execute(
+            "INSERT INTO chat_history (platform, channel, platform_id, user_id, msg, received_at, data)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                message.platform.id(),
+                message.channel,
+                message.platform_id,
+                message.moderation_id(),
+                message.msg,
+                received_at,
+                serde_json::to_string(message)?,
+            ],
+        )?;
+        transaction.execute(
+            "INSERT INTO users (platform, user_id, login, display_name, first_seen, last_seen, messages)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?5, 1)
+             ON CONFLICT (platform, user_id) DO UPDATE SET
+                login = excluded.login,
+                display_name = excluded.display_name,
+                last_seen = excluded.last_seen,
+                messages = messages + 1",
+            params![
+                message.platform.id(),
+                message.moderation_id(),
+                message.sender,
+                message.display_name,
+                received_at,
+            ],
+        )?;
+        transaction.commit()?;
+        Ok(())
+    }
+}
+
+impl CommandStore for SqliteStore {
+    fn load(&mut self) -> Result<Vec<BotCommand>> {
+        let mut statement = self.connection.prepare("SELECT name, data FROM commands ORDER BY name")?;
+        let rows = statement.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+        rows.map(|row| {
+            let (name, data) = row?;
+            serde_json::from_str(&data).map_err(|err| anyhow::Error::msg(format!("Command {name} in the database is invalid: {err}")))
+        })
+        .collect()
+    }
+
+    fn save_command(&mut self, command: &BotCommand) -> Result<()> {
+        self.connection.execute(
+            "INSERT INTO commands (name, data) VALUES (?1, ?2)
+             ON CONFLICT (name) DO UPDATE SET data = excluded.data",
+            params![command.name, serde_json::to_string(command)?],
+        )?;
+        Ok(())
+    }
+
+    fn delete_command(&mut self, name: &str) -> Result<()> {
+        self.connection.execute("DELETE FROM commands WHERE name = ?1", params![name])?;
+        Ok(())
+    }
+
+    fn add_quote(&mut self, text: &str, added_by: &str) -> Result<i64> {
+        self.connection.execute(
+            "INSERT INTO quotes (text, added_by, created_at) VALUES (?1, ?2, ?3)",
+            params![text, added_by, Utc::now().to_rfc3339()],
+        )?;
+        Ok(self.connection.last_insert_rowid())
+    }
+
+    fn quote(&mut self, id: Option<i64>) -> Result<Option<Quote>> {
+        let query = match id {
+            Some(_) => "SELECT id, text, added_by, created_at FROM quotes WHERE id = ?1",
+            None => "SELECT id, text, added_by, created_at FROM quotes WHERE ?1 IS NULL ORDER BY RANDOM() LIMIT 1",
+        };
+        let quote = self
+            .connection
+            .query_row(query, params![id], |row| {
+                Ok(Quote {
+                    id: row.get(0)?,
+                    text: row.get(1)?,
+                    added_by: row.get(2)?,
+                    created_at: row
+                        .get::<_, String>(3)
+                        .map(|created_at| DateTime::parse_from_rfc3339(&created_at).map(Into::into).unwrap_or_default())?,
+                })
+            })
+            .optional()?;
+        Ok(quote)
+    }
+}
+
+/// Keeps the chat history and users in the database when `COMMANDS_STORE` is `sqlite`.
+pub fn start_history(bus: &EventBus) -> Result<()> {
+    if env::var("COMMANDS_STORE").as_deref() != Ok("sqlite") {
+        return Ok(());
+    }
+    let store = SqliteStore::open(&store::sqlite_path())?;
+    let subscription = bus.subscribe("chat history", chat_only());
+    thread::spawn(move || record_history(store, subscription));
+    Ok(())
+}
+
+fn record_history(mut store: SqliteStore, mut subscription: Subscription) {
+    while let Some(received) = subscription.recv() {
+        let Received::Event(PlatformEvent::Chat(message)) = received else {
+            continue;
+        };
+        if let Err(err) = store.record_chat(&message) {
+            eprintln!("ERROR - Cannot save chat message to the database: {:?}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process};
+
+    use super::*;
+    use crate::{connector::ConnectorStatus, messages::Platform};
+
+    fn command(name: &str, contents: &str) -> BotCommand {
+        BotCommand {
+            name: name.to_string(),
+            contents: contents.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn test_path(name: &str) -> String {
+        let path = env::temp_dir().join(format!("ttv-bot-{}-{name}", process::id()));
+        let _ = fs::remove_file(&path);
+        path.to_string_lossy().to_string()
+    }
+
+    fn chat(sender: &str, msg: &str) -> PlatformMessage {
+        PlatformMessage {
+            sender_id: Some(format!("{sender}-id")),
+            channel: Some("zartisimo".to_string()),
+            ..PlatformMessage::new(Platform::TWITCH, sender.to_string(), msg.to_string())
+        }
+    }
+
+    #[test]
+    fn commands_round_trip() {
+        let mut store = SqliteStore::open(":memory:").unwrap();
+        let mut discord = command("discord", "discord.gg/abc");
+        discord.aliases = vec!["dc".to_string()];
+        discord.count = 3;
+        store.save_command(&discord).unwrap();
+        store.save_command(&command("today", "Just chatting")).unwrap();
+        assert_eq!(store.load().unwrap(), [discord.clone(), command("today", "Just chatting")]);
+
+        store.save_command(&command("today", "Coding")).unwrap();
+        store.delete_command("discord").unwrap();
+        store.delete_command("missing").unwrap();
+        assert_eq!(store.load().unwrap(), [command("today", "Coding")]);
+    }
+
+    #[test]
+    fn quotes_are_numbered_and_picked() {
+        let mut store = SqliteStore::open(":memory:").unwrap();
+        assert_eq!(store.quote(None).unwrap(), None);
+        assert_eq!(store.add_quote("first", "viewer").unwrap(), 1);
+        assert_eq!(store.add_quote("second", "mod").unwrap(), 2);
+
+        let quote = store.quote(Some(2)).unwrap().unwrap();
+        assert_eq!((quote.text.as_str(), quote.added_by.as_str()), ("second", "mod"));
+        assert_eq!(quote.to_string(), "#2: second");
+        assert_eq!(store.quote(Some(3)).unwrap(), None);
+        assert!(store.quote(None).unwrap().is_some());
+    }
+
+    #[test]
+    fn json_commands_are_imported_only_once() {
+        let json = test_path("import.json");
+        commands_file::write(&json, &[command("discord", "discord.gg/abc")]).unwrap();
+        let db = test_path("import.db");
+
+        let mut store = SqliteStore::open(&db).unwrap();
+        store.import_json_once(&json).unwrap();
+        assert_eq!(store.load().unwrap(), [command("discord", "discord.gg/abc")]);
+        store.delete_command("discord").unwrap();
+        drop(store);
+
+        let mut store = SqliteStore::open(&db).unwrap();
+        store.import_json_once(&json).unwrap();
+        assert_eq!(store.load().unwrap(), []);
+    }
+
+    #[test]
+    fn databases_with_commands_are_not_imported_into() {
+        let json = test_path("no-import.json");
+        commands_file::write(&json, &[command("discord", "discord.gg/abc")]).unwrap();
+        let mut store = SqliteStore::open(":memory:").unwrap();
+        store.save_command(&command("today", "Just chatting")).unwrap();
+        store.import_json_once(&json).unwrap();
+        assert_eq!(store.load().unwrap(), [command("today", "Just chatting")]);
+    }
+
+    #[test]
+    fn history_keeps_chat_and_counts_users() {
+        let db = test_path("history.db");
+        let bus = EventBus::new(16);
+        let subscription = bus.subscribe("chat history", chat_only());
+        let recorder = thread::spawn({
+            let store = SqliteStore::open(&db).unwrap();
+            move || record_history(store, subscription)
+        });
+
+        bus.publish(chat("viewer", "hi").into());
+        bus.publish(PlatformEvent::ConnectionState {
+            platform: Platform::TWITCH,
+            status: ConnectorStatus::Connected,
+        });
+        bus.publish(chat("viewer", "!today").into());
+        bus.publish(chat("other", "hello").into());
+        // Closes the bus, the recorder stops once it saved everything
+        drop(bus);
+        recorder.join().unwrap();
+
+        let store = SqliteStore::open(&db).unwrap();
+        let history: Vec<(String, String)> = store
+            .connection
+            .prepare("SELECT user_id, msg FROM chat_history ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            history,
+            [
+                ("viewer-id".to_string(), "hi".to_string()),
+                ("viewer-id".to_string(), "!today".to_string()),
+                ("other-id".to_string(), "hello".to_string()),
+            ]
+        );
+        let messages: i64 = store
+            .connection
+            .query_row(
+                "SELECT messages FROM users WHERE platform = 'twitch' AND user_id = 'viewer-id'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(messages, 2);
+    }
+}
@@ -0,0 +1,313 @@
+use std::{collections::BTreeMap, env, fmt, fs, time::SystemTime};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rand::seq::SliceRandom;
+
+use crate::{
+    command::BotCommand,
+    commands_file::{self, Backup},
+    sqlite::SqliteStore,
+};
+
+pub static COMMANDS_FILE: &str = "commands.json";
+static DEFAULT_SQLITE_PATH: &str = "omnichatter.db";
+
+/// A quote saved from chat with `!addquote`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quote {
+    pub id: i64,
+    pub text: String,
+    pub added_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl fmt::Display for Quote {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{}: {}", self.id, self.text)
+    }
+}
+
+/// Where commands (and, if the backend supports them, quotes) are kept. `CommandHandler` keeps
+/// its own copy of the commands and tells the store about each change.
+pub trait CommandStore: Send {
+    fn load(&mut self) -> Result<Vec<BotCommand>>;
+
+    /// Inserts the command or replaces the one with the same name.
+    fn save_command(&mut self, command: &BotCommand) -> Result<()>;
+
+    fn delete_command(&mut self, name: &str) -> Result<()>;
+
+    /// Same as `save_command` for each of them, for stores that can do it at once.
+    fn save_commands(&mut self, commands: &[BotCommand]) -> Result<()> {
+        commands.iter().try_for_each(|command| self.save_command(command))
+    }
+
+    /// All the commands, if they were changed from outside the bot since they were last loaded or
+    /// saved. `Ok(None)` if they were not.
+    fn reload(&mut self) -> Result<Option<Vec<BotCommand>>> {
+        Ok(None)
+    }
+
+    /// Previous versions of the commands, the most recent first.
+    fn backups(&self) -> Vec<Backup> {
+        Vec::new()
+    }
+
+    /// Puts back the commands of a backup and returns them.
+    fn restore(&mut self, _backup: &Backup) -> Result<Vec<BotCommand>> {
+        Err(anyhow::Error::msg("This store has no backups"))
+    }
+
+    /// Returns the id of the new quote.
+    fn add_quote(&mut self, _text: &str, _added_by: &str) -> Result<i64> {
+        Err(anyhow::Error::msg("Quotes need COMMANDS_STORE=sqlite"))
+    }
+
+    /// The quote with that id or, if `None`, a random one. `Ok(None)` if there is no such quote.
+    fn quote(&mut self, _id: Option<i64>) -> Result<Option<Quote>> {
+        Err(anyhow::Error::msg("Quotes need COMMANDS_STORE=sqlite"))
+    }
+}
+
+/// `COMMANDS_STORE` picks the backend: `json` (the default, `commands.json`), `sqlite` (the
+/// database at `SQLITE_PATH`) or `memory` (nothing is kept after closing).
+pub fn from_env() -> Result<Box<dyn CommandStore>> {
+    match env::var("COMMANDS_STORE").as_deref() {
+        Ok("sqlite") => {
+            let mut store = SqliteStore::open(&sqlite_path())?;
+            store.import_json_once(COMMANDS_FILE)?;
+            Ok(Box::new(store))
+        }
+        Ok("memory") => Ok(Box::new(MemoryStore::default())),
+        Ok("json") | Ok("") | Err(_) => Ok(Box::new(JsonStore::open(COMMANDS_FILE)?)),
+        Ok(other) => Err(anyhow::Error::msg(format!(
+            "Unknown COMMANDS_STORE {other}, expected json, sqlite or memory"
+        ))),
+    }
+}
+
+pub fn sqlite_path() -> String {
+    env::var("SQLITE_PATH")
+        .ok()
+        .filter(|path| !path.is_empty())
+        .unwrap_or(DEFAULT_SQLITE_PATH.to_string())
+}
+
+/// Commands in a JSON file, rewritten whole on every change.
+pub struct JsonStore {
+    path: String,
+    commands: BTreeMap<String, BotCommand>,
+    /// Modification time and size of the file when it was last read or written, to notice it
+    /// being changed by something else
+    seen: Option<(SystemTime, u64)>,
+    /// Why the file, changed by something else, can't be loaded. It is not overwritten until fixed
+    invalid: Option<String>,
+}
+
+impl JsonStore {
+    pub fn open(path: &str) -> Result<Self> {
+        let commands = commands_file::read(path)?
+            .into_iter()
+            .map(|command| (command.name.clone(), command))
+            .collect();
+        Ok(Self {
+            path: path.to_string(),
+            commands,
+            seen: file_version(path),
+            invalid: None,
+        })
+    }
+
+    /// Writes `commands` and, only if that works, keeps them. Changes made to the file by something
+    /// else are never overwritten, they have to be reloaded first.
+    fn write(&mut self, commands: BTreeMap<String, BotCommand>) -> Result<()> {
+        if let Some(err) = &self.invalid {
+            return Err(anyhow::Error::msg(format!(
+                "{} was changed and can't be loaded, fix it first: {err}",
+                self.path
+            )));
+        }
+        if file_version(&self.path) != self.seen {
+            return Err(anyhow::Error::msg(format!(
+                "{} was changed outside the bot, try again once it is reloaded",
+                self.path
+            )));
+        }
+        let list: Vec<BotCommand> = commands.values().cloned().collect();
+        commands_file::write(&self.path, &list)?;
+        self.commands = commands;
+        self.seen = file_version(&self.path);
+        Ok(())
+    }
+}
+
+impl CommandStore for JsonStore {
+    fn load(&mut self) -> Result<Vec<BotCommand>> {
+        Ok(self.commands.values().cloned().collect())
+    }
+
+    fn save_command(&mut self, command: &BotCommand) -> Result<()> {
+        let mut commands = self.commands.clone();
+        commands.insert(command.name.clone(), command.clone());
+        self.write(commands)
+    }
+
+    fn delete_command(&mut self, name: &str) -> Result<()> {
+        let mut commands = self.commands.clone();
+        commands.remove(name);
+        self.write(commands)
+    }
+
+    fn save_commands(&mut self, new: &[BotCommand]) -> Result<()> {
+        let mut commands = self.commands.clone();
+        commands.extend(new.iter().map(|command| (command.name.clone(), command.clone())));
+        self.write(commands)
+    }
+
+    fn reload(&mut self) -> Result<Option<Vec<BotCommand>>> {
+        let version = file_version(&self.path);
+        if version == self.seen {
+            return Ok(None);
+        }
+        self.seen = version;
+        // Read without `commands_file::read`, which would create or migrate the file. Same rules as
+        // on startup: templates that don't parse are still loaded and rendered as they are
+        let commands = fs::read_to_string(&self.path)
+            .map_err(|err| anyhow::Error::msg(format!("Cannot read commands file {}: {err}", self.path)))
+            .and_then(|contents| commands_file::parse(&self.path, &contents))
+            .map(|(commands, _)| commands);
+        match commands {
+            Ok(commands) => {
+                self.invalid = None;
+                self.commands = commands.iter().map(|command| (command.name.clone(), command.clone())).collect();
+                Ok(Some(commands))
+            }
+            Err(err) => {
+                self.invalid = Some(err.to_string());
+                Err(err)
+            }
+        }
+    }
+
+    fn backups(&self) -> Vec<Backup> {
+        commands_file::backups(&self.path)
+    }
+
+    fn restore(&mut self, backup: &Backup) -> Result<Vec<BotCommand>> {
+        let commands = commands_file::restore(&self.path, backup)?;
+        self.commands = commands.iter().map(|command| (command.name.clone(), command.clone())).collect();
+        self.seen = file_version(&self.path);
+        self.invalid = None;
+        Ok(commands)
+    }
+}
+
+fn file_version(path: &str) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Keeps everything in memory only, for trying things out.
+#[derive(Default)]
+pub struct MemoryStore {
+    commands: BTreeMap<String, BotCommand>,
+    quotes: Vec<Quote>,
+}
+
+impl CommandStore for MemoryStore {
+    fn load(&mut self) -> Result<Vec<BotCommand>> {
+        Ok(self.commands.values().cloned().collect())
+    }
+
+    fn save_command(&mut self, command: &BotCommand) -> Result<()> {
+        self.commands.insert(command.name.clone(), command.clone());
+        Ok(())
+    }
+
+    fn delete_command(&mut self, name: &str) -> Result<()> {
+        self.commands.remove(name);
+        Ok(())
+    }
+
+    fn add_quote(&mut self, text: &str, added_by: &str) -> Result<i64> {
+        let id = self.quotes.len() as i64 + 1;
+        self.quotes.push(Quote {
+            id,
+            text: text.to_string(),
+            added_by: added_by.to_string(),
+            created_at: Utc::now(),
+        });
+        Ok(id)
+    }
+
+    fn quote(&mut self, id: Option<i64>) -> Result<Option<Quote>> {
+        Ok(match id {
+            Some(id) => self.quotes.iter().find(|quote| quote.id == id).cloned(),
+            None => self.quotes.choose(&mut rand::thread_rng()).cloned(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, process};
+
+    use super::*;
+
+    /// An empty directory of its own for each test.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("ttv-bot-{}-{name}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn command(name: &str, contents: &str) -> BotCommand {
+        BotCommand {
+            name: name.to_string(),
+            contents: contents.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn names(commands: &[BotCommand]) -> Vec<&str> {
+        commands.iter().map(|command| command.name.as_str()).collect()
+    }
+
+    #[test]
+    fn reload_picks_up_external_edits_and_blocks_writes_while_invalid() {
+        let path = test_dir("reload").join("commands.json").to_string_lossy().to_string();
+        // Written before templates existed, the brace is just text
+        let legacy = command("json", "use {\"key\": value");
+        commands_file::write(&path, std::slice::from_ref(&legacy)).unwrap();
+        let mut store = JsonStore::open(&path).unwrap();
+        assert_eq!(store.reload().unwrap(), None);
+
+        commands_file::write(&path, &[legacy.clone(), command("discord", "discord.gg/abc")]).unwrap();
+        let reloaded = store.reload().unwrap().expect("the edit to be noticed");
+        assert_eq!(names(&reloaded), ["json", "discord"]);
+
+        fs::write(&path, "{ not json").unwrap();
+        assert!(store.reload().is_err());
+        assert!(store.save_command(&command("today", "Just chatting")).is_err());
+        assert!(store.delete_command("discord").is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{ not json");
+
+        commands_file::write(&path, &[legacy]).unwrap();
+        assert_eq!(names(&store.reload().unwrap().unwrap()), ["json"]);
+        store.save_command(&command("today", "Just chatting")).unwrap();
+        assert_eq!(names(&commands_file::read(&path).unwrap()), ["json", "today"]);
+    }
+
+    #[test]
+    fn writes_never_overwrite_unseen_edits() {
+        let path = test_dir("unseen").join("commands.json").to_string_lossy().to_string();
+        let mut store = JsonStore::open(&path).unwrap();
+        commands_file::write(&path, &[command("discord", "discord.gg/abc")]).unwrap();
+        assert!(store.save_command(&command("today", "Just chatting")).is_err());
+        store.reload().unwrap();
+        store.save_command(&command("today", "Just chatting")).unwrap();
+        assert_eq!(names(&store.load().unwrap()), ["discord", "today"]);
+    }
+}
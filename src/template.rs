@@ -0,0 +1,319 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use rand::{seq::SliceRandom, Rng};
+
+/// Everything in `{}` a command template can use:
+///
+/// - `{user}` who sent the command
+/// - `{args}` everything after the command, `{arg1}`, `{arg2}`... each word of it
+/// - `{target}` first argument without its `@`, or the sender if there is none
+/// - `{channel}` and `{platform}` where the command was sent
+/// - `{uptime}` how long the stream has been live
+/// - `{count}` the command's counter
+/// - `{random 1 100}` a number in the range (both included)
+/// - `{time America/New_York}` current time in the time zone, UTC if none is given
+/// - `{pick a|b|c}` one of the options
+///
+/// `{{` and `}}` are literal braces.
+#[derive(Debug, Clone, PartialEq)]
+enum Placeholder {
+    User,
+    Args,
+    Arg(usize),
+    Target,
+    Channel,
+    Platform,
+    Uptime,
+    Count,
+    Random(i64, i64),
+    Time(Option<Tz>),
+    Pick(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Text(String),
+    Placeholder(Placeholder),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplateError {
+    UnknownPlaceholder(String),
+    InvalidArguments { placeholder: String, msg: String },
+    Unclosed,
+    UnexpectedClose,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemplateError::UnknownPlaceholder(name) => write!(f, "unknown placeholder {{{name}}}"),
+            TemplateError::InvalidArguments { placeholder, msg } => write!(f, "invalid {{{placeholder}}}: {msg}"),
+            TemplateError::Unclosed => write!(f, "a {{ is never closed, use {{{{ for a literal one"),
+            TemplateError::UnexpectedClose => write!(f, "a }} is never opened, use }}}} for a literal one"),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+/// What a template is rendered with.
+pub struct TemplateContext<'a> {
+    pub user: &'a str,
+    pub args: &'a [&'a str],
+    pub channel: Option<&'a str>,
+    pub platform: Option<&'a str>,
+    pub count: i64,
+    /// Only called if the template uses `{uptime}`
+    pub live_since: &'a dyn Fn() -> Option<DateTime<Utc>>,
+    pub now: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Self, TemplateError> {
+        let mut segments = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '}' => return Err(TemplateError::UnexpectedClose),
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => placeholder.push(c),
+                            None => return Err(TemplateError::Unclosed),
+                        }
+                    }
+                    if !text.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut text)));
+                    }
+                    segments.push(Segment::Placeholder(parse_placeholder(&placeholder)?));
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            segments.push(Segment::Text(text));
+        }
+        Ok(Self { segments })
+    }
+
+    /// Whether the template shows the command's counter.
+    pub fn uses_count(&self) -> bool {
+        self.segments.contains(&Segment::Placeholder(Placeholder::Count))
+    }
+
+    pub fn render(&self, context: &TemplateContext) -> String {
+        let mut rendered = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Text(text) => rendered.push_str(text),
+                Segment::Placeholder(placeholder) => rendered.push_str(&render_placeholder(placeholder, context)),
+            }
+        }
+        rendered
+    }
+}
+
+fn parse_placeholder(placeholder: &str) -> Result<Placeholder, TemplateError> {
+    let (name, args) = placeholder.trim().split_once(' ').unwrap_or((placeholder.trim(), ""));
+    let args = args.trim();
+    let invalid = |msg: &str| TemplateError::InvalidArguments {
+        placeholder: name.to_string(),
+        msg: msg.to_string(),
+    };
+    let no_args = |placeholder: Placeholder| match args.is_empty() {
+        true => Ok(placeholder),
+        false => Err(invalid("takes no arguments")),
+    };
+
+    match name {
+        "user" => no_args(Placeholder::User),
+        "args" => no_args(Placeholder::Args),
+        "target" => no_args(Placeholder::Target),
+        "channel" => no_args(Placeholder::Channel),
+        "platform" => no_args(Placeholder::Platform),
+        "uptime" => no_args(Placeholder::Uptime),
+        "count" => no_args(Placeholder::Count),
+        "random" => {
+            let bounds: Vec<&str> = args.split_whitespace().collect();
+            let [min, max] = bounds[..] else {
+                return Err(invalid("expected a minimum and a maximum, like {random 1 100}"));
+            };
+            let (Ok(min), Ok(max)) = (min.parse::<i64>(), max.parse::<i64>()) else {
+                return Err(invalid("the minimum and maximum must be integers"));
+            };
+            if min > max {
+                return Err(invalid("the minimum is greater than the maximum"));
+            }
+            Ok(Placeholder::Random(min, max))
+        }
+        "time" if args.is_empty() => Ok(Placeholder::Time(None)),
+        "time" => args
+            .parse::<Tz>()
+            .map(|tz| Placeholder::Time(Some(tz)))
+            .map_err(|_| invalid(&format!("unknown time zone {args}"))),
+        "pick" => {
+            let options: Vec<String> = args.split('|').map(|option| option.trim().to_string()).collect();
+            if options.iter().all(|option| option.is_empty()) {
+                return Err(invalid("expected options separated by |, like {pick a|b|c}"));
+            }
+            Ok(Placeholder::Pick(options))
+        }
+        _ => match name.strip_prefix("arg").and_then(|n| n.parse::<usize>().ok()) {
+            Some(0) => Err(invalid("arguments start at {arg1}")),
+            Some(n) => no_args(Placeholder::Arg(n)),
+            None => Err(TemplateError::UnknownPlaceholder(placeholder.trim().to_string())),
+        },
+    }
+}
+
+fn render_placeholder(placeholder: &Placeholder, context: &TemplateContext) -> String {
+    match placeholder {
+        Placeholder::User => context.user.to_string(),
+        Placeholder::Args => context.args.join(" "),
+        Placeholder::Arg(n) => context.args.get(n - 1).unwrap_or(&"").to_string(),
+        Placeholder::Target => match context.args.first() {
+            Some(target) => target.trim_start_matches('@').to_string(),
+            None => context.user.to_string(),
+        },
+        Placeholder::Channel => context.channel.unwrap_or("").to_string(),
+        Placeholder::Platform => context.platform.unwrap_or("").to_string(),
+        Placeholder::Uptime => match (context.live_since)() {
+            Some(live_since) => format_duration(context.now - live_since),
+            None => "offline".to_string(),
+        },
+        Placeholder::Count => context.count.to_string(),
+        Placeholder::Random(min, max) => rand::thread_rng().gen_range(*min..=*max).to_string(),
+        Placeholder::Time(Some(tz)) => context.now.with_timezone(tz).format("%H:%M").to_string(),
+        Placeholder::Time(None) => context.now.format("%H:%M UTC").to_string(),
+        Placeholder::Pick(options) => options.choose(&mut rand::thread_rng()).cloned().unwrap_or_default(),
+    }
+}
+
+fn format_duration(duration: chrono::Duration) -> String {
+    let minutes = duration.num_minutes().max(0);
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{minutes}m"),
+        (hours, minutes) => format!("{hours}h {minutes}m"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn render(template: &str, args: &[&str]) -> String {
+        let now = Utc.with_ymd_and_hms(2024, 1, 15, 18, 30, 0).unwrap();
+        let context = TemplateContext {
+            user: "zartisimo",
+            args,
+            channel: Some("zartisimo"),
+            platform: Some("twitch"),
+            count: 7,
+            live_since: &|| Some(Utc.with_ymd_and_hms(2024, 1, 15, 16, 5, 0).unwrap()),
+            now,
+        };
+        Template::parse(template).unwrap().render(&context)
+    }
+
+    fn invalid(template: &str) -> String {
+        match Template::parse(template).unwrap_err() {
+            TemplateError::InvalidArguments { placeholder, .. } => placeholder,
+            err => panic!("expected invalid arguments, got {err}"),
+        }
+    }
+
+    #[test]
+    fn renders_placeholders() {
+        assert_eq!(
+            render("{user} hugs {target} on {platform}/{channel} ({count})", &["@friend"]),
+            "zartisimo hugs friend on twitch/zartisimo (7)"
+        );
+        assert_eq!(render("hugs {target}", &[]), "hugs zartisimo");
+        assert_eq!(render("{args}|{arg1}|{arg3}", &["a", "b"]), "a b|a|");
+        assert_eq!(render("live for {uptime}", &[]), "live for 2h 25m");
+        assert_eq!(render("{time}, {time Europe/Madrid}", &[]), "18:30 UTC, 19:30");
+        assert_eq!(render("{ user }", &[]), "zartisimo");
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        assert_eq!(render("{{user}} is {user}", &[]), "{user} is zartisimo");
+        assert_eq!(render("}}{{", &[]), "}{");
+        assert_eq!(Template::parse("{user"), Err(TemplateError::Unclosed));
+        assert_eq!(Template::parse("user}"), Err(TemplateError::UnexpectedClose));
+    }
+
+    #[test]
+    fn rejects_unknown_placeholders_and_bad_arguments() {
+        assert_eq!(
+            Template::parse("{followers}"),
+            Err(TemplateError::UnknownPlaceholder("followers".to_string()))
+        );
+        assert_eq!(invalid("{arg0}"), "arg0");
+        assert_eq!(invalid("{user me}"), "user");
+        assert_eq!(invalid("{random 1}"), "random");
+        assert_eq!(invalid("{random a b}"), "random");
+        assert_eq!(invalid("{random 10 1}"), "random");
+        assert_eq!(invalid("{time Mars/Olympus}"), "time");
+        assert_eq!(invalid("{pick | }"), "pick");
+    }
+
+    #[test]
+    fn random_and_pick_stay_in_bounds() {
+        for _ in 0..100 {
+            let n: i64 = render("{random -2 2}", &[]).parse().unwrap();
+            assert!((-2..=2).contains(&n));
+            assert_eq!(render("{random 5 5}", &[]), "5");
+            assert!(["a", "b c"].contains(&render("{pick a| b c }", &[]).as_str()));
+        }
+    }
+
+    #[test]
+    fn uptime_is_offline_without_a_stream_and_only_asked_when_used() {
+        let context = TemplateContext {
+            user: "zartisimo",
+            args: &[],
+            channel: None,
+            platform: None,
+            count: 0,
+            live_since: &|| None,
+            now: Utc::now(),
+        };
+        assert_eq!(Template::parse("{uptime}").unwrap().render(&context), "offline");
+        let context = TemplateContext {
+            live_since: &|| panic!("uptime is not used"),
+            ..context
+        };
+        assert_eq!(Template::parse("{user}").unwrap().render(&context), "zartisimo");
+    }
+
+    #[test]
+    fn durations_show_hours_and_minutes() {
+        assert_eq!(format_duration(chrono::Duration::seconds(59)), "0m");
+        assert_eq!(format_duration(chrono::Duration::minutes(59)), "59m");
+        assert_eq!(format_duration(chrono::Duration::minutes(60)), "1h 0m");
+        assert_eq!(format_duration(chrono::Duration::minutes(26 * 60 + 3)), "26h 3m");
+        assert_eq!(format_duration(chrono::Duration::minutes(-5)), "0m");
+    }
+}
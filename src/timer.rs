@@ -0,0 +1,412 @@
+use std::{
+    collections::HashMap,
+    fs,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bus::{Received, Subscription},
+    commands_file,
+    connector::Registry,
+    cooldown::{Clock, SystemClock},
+    messages::{ChatTarget, Platform, PlatformEvent, PlatformMessage},
+};
+
+static TIMERS_FILE: &str = "timers.json";
+static TICK: Duration = Duration::from_secs(1);
+
+/// A message posted every `interval` seconds, as long as `min_messages` were sent in the chats it
+/// targets since the last time. No targets means every platform with channels, Twitter only posts
+/// timers that target it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Timer {
+    pub name: String,
+    pub message: String,
+    pub interval: u64,
+    #[serde(default)]
+    pub min_messages: u64,
+    #[serde(default)]
+    pub targets: Vec<ChatTarget>,
+    #[serde(default)]
+    pub paused: bool,
+}
+
+impl Timer {
+    fn counts(&self, message: &PlatformMessage) -> bool {
+        match self.targets.is_empty() {
+            true => has_channels(&message.platform),
+            false => self.targets.iter().any(|target| target.matches(message)),
+        }
+    }
+}
+
+struct TimerState {
+    last_run: DateTime<Utc>,
+    messages: u64,
+}
+
+/// The timers and when they last ran. Shared by the GUI and the thread that posts them.
+pub struct Timers {
+    timers: Vec<Timer>,
+    states: HashMap<String, TimerState>,
+    clock: Box<dyn Clock>,
+    /// Where the timers are saved
+    path: String,
+}
+
+impl Timers {
+    pub fn new(timers: Vec<Timer>, clock: Box<dyn Clock>) -> Self {
+        let mut new = Self {
+            timers: Vec::new(),
+            states: HashMap::new(),
+            clock,
+            path: TIMERS_FILE.to_string(),
+        };
+        for timer in timers {
+            new.reset(&timer.name);
+            new.timers.push(timer);
+        }
+        new
+    }
+
+    /// Reads the timers file, if there is none there are no timers.
+    pub fn load() -> Result<Self> {
+        Self::load_from(TIMERS_FILE)
+    }
+
+    fn load_from(path: &str) -> Result<Self> {
+        let timers = match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|err| anyhow::Error::msg(format!("Timers file {path} is invalid, fix it or move it away: {err}")))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(anyhow::Error::msg(format!("Cannot read timers file {path}: {err}"))),
+        };
+        let mut timers = Self::new(timers, Box::new(SystemClock));
+        timers.path = path.to_string();
+        Ok(timers)
+    }
+
+    pub fn timers(&self) -> &[Timer] {
+        &self.timers
+    }
+
+    /// Adds the timer or replaces the one named `original`, which keeps its schedule even if
+    /// renamed. Without `original` a timer with the same name is replaced.
+    pub fn save_timer(&mut self, original: Option<&str>, timer: Timer) -> Result<()> {
+        if timer.name.trim().is_empty() {
+            return Err(anyhow::Error::msg("Timer name cannot be empty"));
+        }
+        if timer.message.trim().is_empty() {
+            return Err(anyhow::Error::msg("Timer message cannot be empty"));
+        }
+        if timer.interval == 0 {
+            return Err(anyhow::Error::msg("Timer interval cannot be 0"));
+        }
+        if let Some(target) = timer
+            .targets
+            .iter()
+            .find(|target| !has_channels(&target.platform) && target.channel.is_some())
+        {
+            return Err(anyhow::Error::msg(format!(
+                "{} has no channels, target it as just {}",
+                target.platform, target.platform
+            )));
+        }
+        let original = original.unwrap_or(&timer.name).to_string();
+        if original != timer.name && self.timers.iter().any(|existing| existing.name == timer.name) {
+            return Err(anyhow::Error::msg(format!("Timer {} already exists", timer.name)));
+        }
+        match self.timers.iter_mut().find(|existing| existing.name == original) {
+            Some(existing) => {
+                if let Some(state) = self.states.remove(&original) {
+                    self.states.insert(timer.name.clone(), state);
+                }
+                *existing = timer;
+            }
+            None => {
+                self.reset(&timer.name);
+                self.timers.push(timer);
+            }
+        }
+        self.write_file()
+    }
+
+    pub fn remove_timer(&mut self, name: &str) -> Result<()> {
+        self.timers.retain(|timer| timer.name != name);
+        self.states.remove(name);
+        self.write_file()
+    }
+
+    /// A resumed timer waits a whole interval before posting.
+    pub fn set_paused(&mut self, name: &str, paused: bool) -> Result<()> {
+        let Some(timer) = self.timers.iter_mut().find(|timer| timer.name == name) else {
+            return Err(anyhow::Error::msg(format!("Timer {name} does not exist")));
+        };
+        timer.paused = paused;
+        if !paused {
+            self.reset(name);
+        }
+        self.write_file()
+    }
+
+    /// Counts a chat message towards the timers targeting its chat.
+    pub fn record_chat(&mut self, message: &PlatformMessage) {
+        if message.is_bot {
+            return;
+        }
+        for timer in self.timers.iter().filter(|timer| timer.counts(message)) {
+            if let Some(state) = self.states.get_mut(&timer.name) {
+                state.messages += 1;
+            }
+        }
+    }
+
+    /// Timers that have to post now. They are considered run from now on.
+    pub fn due(&mut self) -> Vec<Timer> {
+        let now = self.clock.now();
+        let mut due = Vec::new();
+        for timer in self.timers.iter().filter(|timer| !timer.paused) {
+            let Some(state) = self.states.get_mut(&timer.name) else {
+                continue;
+            };
+            let elapsed = (now - state.last_run).num_seconds();
+            if elapsed >= timer.interval as i64 && state.messages >= timer.min_messages {
+                *state = TimerState {
+                    last_run: now,
+                    messages: 0,
+                };
+                due.push(timer.clone());
+            }
+        }
+        due
+    }
+
+    fn reset(&mut self, name: &str) {
+        let state = TimerState {
+            last_run: self.clock.now(),
+            messages: 0,
+        };
+        self.states.insert(name.to_string(), state);
+    }
+
+    fn write_file(&self) -> Result<()> {
+        commands_file::replace(&self.path, &serde_json::to_string_pretty(&self.timers)?)
+    }
+}
+
+/// Posts the timers when they are due. `subscription` is the chat they count messages from.
+pub fn start(timers: Arc<Mutex<Timers>>, registry: Arc<Registry>, subscription: Subscription) {
+    thread::spawn(move || run(timers, registry, subscription));
+}
+
+fn run(timers: Arc<Mutex<Timers>>, registry: Arc<Registry>, mut subscription: Subscription) {
+    loop {
+        let due = {
+            let mut timers = timers.lock().expect("To lock the timers");
+            while let Some(received) = subscription.try_recv() {
+                if let Received::Event(PlatformEvent::Chat(message)) = received {
+                    timers.record_chat(&message);
+                }
+            }
+            timers.due()
+        };
+        for timer in due {
+            post(&registry, &timer);
+        }
+        thread::sleep(TICK);
+    }
+}
+
+/// Twitter has no channels, what its connector gets as channel is the tweet to reply to.
+fn has_channels(platform: &Platform) -> bool {
+    *platform != Platform::TWITTER
+}
+
+/// Where the timer posts when `connected` are the platforms running.
+fn targets<'a>(timer: &Timer, connected: impl Iterator<Item = &'a Platform>) -> Vec<ChatTarget> {
+    match timer.targets.is_empty() {
+        true => connected
+            .filter(|platform| has_channels(platform))
+            .map(|platform| ChatTarget {
+                platform: platform.clone(),
+                channel: None,
+            })
+            .collect(),
+        false => timer.targets.clone(),
+    }
+}
+
+fn post(registry: &Registry, timer: &Timer) {
+    let connected = registry.handles().iter().map(|handle| &handle.info.platform);
+    for mut target in targets(timer, connected) {
+        if !has_channels(&target.platform) && target.channel.take().is_some() {
+            eprintln!(
+                "WARN - Timer {} targets a channel on {}, posting it as a new message",
+                timer.name, target.platform
+            );
+        }
+        let Some(handle) = registry.handles().iter().find(|handle| handle.info.platform == target.platform) else {
+            eprintln!("WARN - Timer {} targets {}, which is not connected", timer.name, target.platform);
+            continue;
+        };
+        if let Err(err) = handle.send_message(target.channel, timer.message.clone()) {
+            eprintln!("ERROR - Cannot post timer {}: {:?}", timer.name, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, path::Path};
+
+    use super::*;
+    use crate::cooldown::ManualClock;
+
+    fn timer(name: &str, interval: u64, min_messages: u64, targets: &[&str]) -> Timer {
+        Timer {
+            name: name.to_string(),
+            message: format!("{name} message"),
+            interval,
+            min_messages,
+            targets: targets.iter().map(|target| ChatTarget::parse(target)).collect(),
+            paused: false,
+        }
+    }
+
+    fn timers(timers: Vec<Timer>, file: &str) -> (Timers, ManualClock) {
+        let clock = ManualClock::new();
+        let mut timers = Timers::new(timers, Box::new(clock.clone()));
+        timers.path = env::temp_dir()
+            .join(format!("ttv-bot-{}-{file}", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        (timers, clock)
+    }
+
+    fn names(due: Vec<Timer>) -> Vec<String> {
+        due.into_iter().map(|timer| timer.name).collect()
+    }
+
+    fn chat(platform: Platform, channel: Option<&str>) -> PlatformMessage {
+        PlatformMessage {
+            channel: channel.map(str::to_string),
+            ..PlatformMessage::new(platform, "viewer".to_string(), "hello".to_string())
+        }
+    }
+
+    #[test]
+    fn timers_are_due_every_interval() {
+        let (mut timers, clock) = timers(vec![timer("socials", 60, 0, &[]), timer("discord", 90, 0, &[])], "interval");
+
+        clock.advance(59);
+        assert!(timers.due().is_empty());
+        clock.advance(1);
+        assert_eq!(names(timers.due()), ["socials"]);
+        assert!(timers.due().is_empty());
+        clock.advance(30);
+        assert_eq!(names(timers.due()), ["discord"]);
+        clock.advance(30);
+        assert_eq!(names(timers.due()), ["socials"]);
+    }
+
+    #[test]
+    fn timers_wait_for_messages_in_their_chats() {
+        let (mut timers, clock) = timers(vec![timer("socials", 60, 2, &["twitch"])], "messages");
+
+        timers.record_chat(&chat(Platform::TWITCH, Some("#zartisimo")));
+        timers.record_chat(&chat(Platform::DISCORD, None));
+        timers.record_chat(&PlatformMessage {
+            is_bot: true,
+            ..chat(Platform::TWITCH, None)
+        });
+        clock.advance(120);
+        assert!(timers.due().is_empty());
+        timers.record_chat(&chat(Platform::TWITCH, None));
+        assert_eq!(names(timers.due()), ["socials"]);
+        // The count starts over
+        clock.advance(60);
+        assert!(timers.due().is_empty());
+    }
+
+    #[test]
+    fn paused_timers_wait_a_whole_interval_once_resumed() {
+        let (mut timers, clock) = timers(vec![timer("socials", 60, 0, &[])], "paused");
+
+        timers.set_paused("socials", true).unwrap();
+        clock.advance(120);
+        assert!(timers.due().is_empty());
+        timers.set_paused("socials", false).unwrap();
+        clock.advance(59);
+        assert!(timers.due().is_empty());
+        clock.advance(1);
+        assert_eq!(names(timers.due()), ["socials"]);
+        fs::remove_file(&timers.path).unwrap();
+    }
+
+    #[test]
+    fn renaming_a_timer_replaces_it() {
+        let (mut timers, clock) = timers(vec![timer("socials", 60, 0, &[]), timer("discord", 60, 0, &[])], "rename");
+
+        clock.advance(30);
+        timers.save_timer(Some("socials"), timer("links", 60, 0, &[])).unwrap();
+        let saved: Vec<&str> = timers.timers().iter().map(|timer| timer.name.as_str()).collect();
+        assert_eq!(saved, ["links", "discord"]);
+        // Still on the schedule of the timer it was
+        clock.advance(30);
+        assert_eq!(names(timers.due()), ["links", "discord"]);
+
+        assert!(timers.save_timer(Some("links"), timer("discord", 60, 0, &[])).is_err());
+
+        let written: Vec<Timer> = serde_json::from_str(&fs::read_to_string(&timers.path).unwrap()).unwrap();
+        assert_eq!(written, timers.timers());
+        assert!(!Path::new(&format!("{}.tmp", timers.path)).exists());
+        fs::remove_file(&timers.path).unwrap();
+    }
+
+    #[test]
+    fn twitter_targets_have_no_channel() {
+        let (mut timers, _) = timers(Vec::new(), "twitter");
+
+        assert!(timers.save_timer(None, timer("socials", 60, 0, &["twitter:1234"])).is_err());
+        assert!(timers.timers().is_empty());
+        timers
+            .save_timer(None, timer("socials", 60, 0, &["twitter", "discord:1234"]))
+            .unwrap();
+        fs::remove_file(&timers.path).unwrap();
+    }
+
+    #[test]
+    fn untargeted_timers_skip_twitter() {
+        let connected = [Platform::TWITCH, Platform::TWITTER, Platform::DISCORD];
+        let targets = |targets: &[&str]| -> Vec<String> {
+            super::targets(&timer("socials", 60, 0, targets), connected.iter())
+                .iter()
+                .map(ChatTarget::to_string)
+                .collect()
+        };
+        assert_eq!(targets(&[]), ["twitch", "discord"]);
+        assert_eq!(targets(&["twitter", "twitch:zartisimo"]), ["twitter", "twitch:zartisimo"]);
+
+        let (mut timers, clock) = timers(vec![timer("socials", 60, 1, &[])], "untargeted");
+        timers.record_chat(&chat(Platform::TWITTER, None));
+        clock.advance(60);
+        assert!(timers.due().is_empty());
+    }
+
+    #[test]
+    fn invalid_timers_files_are_not_loaded() {
+        let (timers, _) = timers(Vec::new(), "invalid.json");
+        fs::write(&timers.path, "[{\"name\": \"socials\"").unwrap();
+        assert!(Timers::load_from(&timers.path).is_err());
+        assert_eq!(fs::read_to_string(&timers.path).unwrap(), "[{\"name\": \"socials\"");
+
+        fs::remove_file(&timers.path).unwrap();
+        assert!(Timers::load_from(&timers.path).unwrap().timers().is_empty());
+    }
+}
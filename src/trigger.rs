@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+/// Makes a command answer chat messages that contain a keyword or match a regex, without the
+/// `COMMAND_SYMBOL`. Both ignore case. Keywords match whole words unless `substring` is set, so
+/// `hi` does not fire on `this`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Trigger {
+    pub pattern: String,
+    /// `pattern` is a regex instead of a keyword
+    #[serde(default)]
+    pub regex: bool,
+    /// The keyword also matches inside words
+    #[serde(default)]
+    pub substring: bool,
+    /// Messages sent by the bot itself don't fire it
+    #[serde(default = "ignore_bot_default")]
+    pub ignore_bot: bool,
+}
+
+fn ignore_bot_default() -> bool {
+    true
+}
+
+impl Default for Trigger {
+    fn default() -> Self {
+        Self {
+            pattern: String::new(),
+            regex: false,
+            substring: false,
+            ignore_bot: true,
+        }
+    }
+}
+
+impl Trigger {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.pattern.trim().is_empty() {
+            return Err(anyhow::Error::msg("Trigger pattern cannot be empty"));
+        }
+        if self.regex {
+            compile(&self.pattern)?;
+        }
+        Ok(())
+    }
+
+    /// `regexes` keeps the regexes already compiled, by pattern. Invalid regexes never match.
+    pub fn is_match(&self, text: &str, regexes: &mut HashMap<String, Regex>) -> bool {
+        if !self.regex {
+            return contains_keyword(&text.to_lowercase(), &self.pattern.to_lowercase(), self.substring);
+        }
+        if !regexes.contains_key(&self.pattern) {
+            match compile(&self.pattern) {
+                Ok(regex) => regexes.insert(self.pattern.clone(), regex),
+                Err(_) => return false,
+            };
+        }
+        regexes[&self.pattern].is_match(text)
+    }
+}
+
+/// Whether `keyword` is in `text`. Unless `substring`, the keyword can't be part of a longer word:
+/// it needs a boundary on each side that starts or ends with a word character.
+fn contains_keyword(text: &str, keyword: &str, substring: bool) -> bool {
+    if substring {
+        return text.contains(keyword);
+    }
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let starts_word = keyword.chars().next().is_some_and(is_word);
+    let ends_word = keyword.chars().next_back().is_some_and(is_word);
+    text.char_indices().any(|(start, _)| {
+        if !text[start..].starts_with(keyword) {
+            return false;
+        }
+        let before = text[..start].chars().next_back();
+        let after = text[start + keyword.len()..].chars().next();
+        let joined_before = starts_word && before.is_some_and(is_word);
+        let joined_after = ends_word && after.is_some_and(is_word);
+        !joined_before && !joined_after
+    })
+}
+
+fn compile(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern).case_insensitive(true).build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyword(pattern: &str, substring: bool) -> Trigger {
+        Trigger {
+            pattern: pattern.to_string(),
+            substring,
+            ..Default::default()
+        }
+    }
+
+    fn fired(trigger: &Trigger, chat: &[&'static str]) -> Vec<&'static str> {
+        let mut regexes = HashMap::new();
+        chat.iter().copied().filter(|msg| trigger.is_match(msg, &mut regexes)).collect()
+    }
+
+    static CHAT: [&str; 8] = [
+        "hi everyone",
+        "Hi!",
+        "this is fine",
+        "which one is the discord?",
+        "join the Discord: discord.gg/abc",
+        "chill vibes",
+        "hihi",
+        "ok :) hi",
+    ];
+
+    #[test]
+    fn keywords_match_whole_words() {
+        assert_eq!(fired(&keyword("hi", false), &CHAT), ["hi everyone", "Hi!", "ok :) hi"]);
+        assert_eq!(
+            fired(&keyword("discord", false), &CHAT),
+            ["which one is the discord?", "join the Discord: discord.gg/abc"]
+        );
+        assert_eq!(
+            fired(&keyword("the discord", false), &CHAT),
+            ["which one is the discord?", "join the Discord: discord.gg/abc"]
+        );
+    }
+
+    #[test]
+    fn keywords_can_match_inside_words() {
+        assert_eq!(
+            fired(&keyword("hi", true), &CHAT),
+            [
+                "hi everyone",
+                "Hi!",
+                "this is fine",
+                "which one is the discord?",
+                "chill vibes",
+                "hihi",
+                "ok :) hi"
+            ]
+        );
+    }
+
+    #[test]
+    fn keywords_with_symbols_need_boundaries_on_their_word_side_only() {
+        assert_eq!(fired(&keyword(":)", false), &CHAT), ["ok :) hi"]);
+        assert_eq!(fired(&keyword("discord.gg", false), &CHAT), ["join the Discord: discord.gg/abc"]);
+        assert_eq!(fired(&keyword("ñandú", false), &["el ñandú corre", "elñandú"]), ["el ñandú corre"]);
+    }
+
+    #[test]
+    fn regexes_ignore_case() {
+        let trigger = Trigger {
+            pattern: r"^hi\b".to_string(),
+            regex: true,
+            ..Default::default()
+        };
+        assert_eq!(fired(&trigger, &CHAT), ["hi everyone", "Hi!"]);
+        let invalid = Trigger {
+            pattern: "(".to_string(),
+            regex: true,
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+        assert!(fired(&invalid, &CHAT).is_empty());
+    }
+}
@@ -1,179 +1,650 @@
-use std::{
-    env,
-    fmt::Display,
-    net::TcpStream,
-    sync::{Arc, Mutex},
-};
+use std::{collections::HashMap, env, fmt::Display, time::Duration};
 
 use anyhow::Result;
-use tokio::sync::mpsc::UnboundedSender;
-use tungstenite::{connect, stream::MaybeTlsStream, Message, WebSocket};
+use chrono::{DateTime, Utc};
+use reqwest::blocking::{Client, RequestBuilder};
+use serde::Deserialize;
+use serde_json::json;
 
 use crate::{
-    command::{CommandHandler, COMMAND_SYMBOL},
-    messages::{Platform, PlatformMessage},
+    connector::{is_timeout, IncomingEvent, PlatformConnector, API_TIMEOUT},
+    irc::{IrcAuth, IrcClient, IrcConfig, IrcMessage, IrcServer},
+    messages::{parse_hex_color, parse_timestamp, Badge, Emote, Platform, PlatformEvent, PlatformInfo, PlatformMessage, ReplyContext},
+    moderation::{ModerationAction, Moderator},
 };
 
+static DEFAULT_TWITCH_CHAT_URL: &str = "ws://irc-ws.chat.twitch.tv:80";
+static DEFAULT_TWITCH_API_URL: &str = "https://api.twitch.tv/helix";
+
+impl Platform {
+    pub const TWITCH: Platform = Platform::new("twitch");
+}
+
 #[derive(Debug, Clone)]
 pub enum TwitchMessage {
-    JOIN {
+    Join {
         channel: String,
     },
-    PRIVMSG {
+    Privmsg {
         sender: String,
         channel: String,
         msg: String,
+        /// IRCv3 tags, only there when the `twitch.tv/tags` capability was requested
+        tags: HashMap<String, String>,
+    },
+    /// Subs, gifts, raids... what it is comes in the `msg-id` tag
+    UserNotice {
+        channel: String,
+        msg: Option<String>,
+        tags: HashMap<String, String>,
+    },
+    /// A single message was deleted
+    ClearMsg {
+        channel: String,
+        tags: HashMap<String, String>,
     },
-    UNIMPLEMENTED {
+    /// A user was banned or timed out
+    ClearChat {
+        channel: String,
+        user: String,
+        tags: HashMap<String, String>,
+    },
+    Unimplemented {
         msg: String,
     },
 }
 
-impl Into<PlatformMessage> for TwitchMessage {
-    fn into(self) -> PlatformMessage {
+impl TwitchMessage {
+    /// The event to report for this message, `None` for the ones nobody outside cares about.
+    pub fn into_event(self) -> Option<PlatformEvent> {
+        let tag = |tags: &HashMap<String, String>, name: &str| tags.get(name).filter(|value| !value.is_empty()).cloned();
         match self {
-            TwitchMessage::PRIVMSG {
-                sender,
-                channel: _,
-                msg,
-            } => PlatformMessage {
+            TwitchMessage::Privmsg { .. } => PlatformMessage::try_from(self).ok().map(PlatformEvent::from),
+            TwitchMessage::UserNotice { msg, tags, .. } => {
+                let platform = Platform::TWITCH;
+                let user = tag(&tags, "display-name").or(tag(&tags, "login")).unwrap_or_default();
+                let number = |name: &str| tag(&tags, name).and_then(|number| number.parse().ok());
+                match tag(&tags, "msg-id")?.as_str() {
+                    "sub" | "resub" => Some(PlatformEvent::Subscription {
+                        platform,
+                        user,
+                        months: number("msg-param-cumulative-months"),
+                        tier: tag(&tags, "msg-param-sub-plan"),
+                        msg,
+                    }),
+                    // Gifts that are part of a `submysterygift` were already reported with it
+                    "subgift" if tag(&tags, "msg-param-community-gift-id").is_none() => Some(PlatformEvent::GiftedSubscriptions {
+                        platform,
+                        gifter: user,
+                        count: 1,
+                        recipients: tag(&tags, "msg-param-recipient-user-name").into_iter().collect(),
+                    }),
+                    "submysterygift" => Some(PlatformEvent::GiftedSubscriptions {
+                        platform,
+                        gifter: user,
+                        count: number("msg-param-mass-gift-count").unwrap_or(1),
+                        recipients: Vec::new(),
+                    }),
+                    "raid" => Some(PlatformEvent::Raid {
+                        platform,
+                        user: tag(&tags, "msg-param-displayName").unwrap_or(user),
+                        viewers: number("msg-param-viewerCount").unwrap_or_default(),
+                    }),
+                    _ => None,
+                }
+            }
+            TwitchMessage::ClearMsg { tags, .. } => Some(PlatformEvent::MessageDeleted {
+                platform: Platform::TWITCH,
+                platform_id: tag(&tags, "target-msg-id")?,
+            }),
+            TwitchMessage::ClearChat { user, tags, .. } => {
+                let user_id = tag(&tags, "target-user-id").unwrap_or(user);
+                let action = match tag(&tags, "ban-duration").and_then(|duration| duration.parse().ok()) {
+                    Some(seconds) => ModerationAction::Timeout {
+                        user_id,
+                        duration: Duration::from_secs(seconds),
+                    },
+                    None => ModerationAction::Ban { user_id },
+                };
+                Some(PlatformEvent::Moderation {
+                    platform: Platform::TWITCH,
+                    moderator: None,
+                    action,
+                })
+            }
+            TwitchMessage::Join { .. } | TwitchMessage::Unimplemented { .. } => None,
+        }
+    }
+}
+
+impl TryFrom<TwitchMessage> for PlatformMessage {
+    type Error = anyhow::Error;
+
+    /// Only a PRIVMSG is a chat message.
+    fn try_from(message: TwitchMessage) -> Result<Self> {
+        match message {
+            TwitchMessage::Privmsg {
                 sender,
+                channel,
                 msg,
-                platform: Platform::Twitch,
-            },
-            _ => {
-                panic!("Should not convert other than a PRIVMSG and current is: {}", self)
+                tags,
+            } => {
+                let tag = |name: &str| tags.get(name).filter(|value| !value.is_empty()).cloned();
+                Ok(PlatformMessage {
+                    platform_id: tag("id"),
+                    sent_at: tag("tmi-sent-ts")
+                        .and_then(|millis| millis.parse().ok())
+                        .and_then(DateTime::from_timestamp_millis),
+                    sender_id: tag("user-id"),
+                    display_name: tag("display-name"),
+                    color: tag("color").and_then(|color| parse_hex_color(&color)),
+                    badges: tag("badges").map(|badges| parse_badges(&badges)).unwrap_or_default(),
+                    emotes: tag("emotes").map(|emotes| parse_emotes(&emotes, &msg)).unwrap_or_default(),
+                    channel: Some(channel),
+                    reply: tag("reply-parent-msg-id").map(|platform_id| ReplyContext {
+                        platform_id,
+                        sender: tag("reply-parent-user-login"),
+                        msg: tag("reply-parent-msg-body"),
+                    }),
+                    ..PlatformMessage::new(Platform::TWITCH, sender, msg)
+                })
             }
+            _ => Err(anyhow::Error::msg(format!("{message} is not a chat message"))),
+        }
+    }
+}
+
+/// `badges` tag: `moderator/1,subscriber/12`
+fn parse_badges(badges: &str) -> Vec<Badge> {
+    badges
+        .split(',')
+        .filter_map(|badge| {
+            let (name, version) = badge.split_once('/')?;
+            Some(Badge::new(name, Some(version)))
+        })
+        .collect()
+}
+
+/// `emotes` tag: `25:0-4,12-16/1902:6-10`, positions are inclusive and in chars.
+fn parse_emotes(emotes: &str, msg: &str) -> Vec<Emote> {
+    let chars: Vec<char> = msg.chars().collect();
+    let mut parsed = Vec::new();
+    for emote in emotes.split('/') {
+        let Some((id, positions)) = emote.split_once(':') else {
+            continue;
+        };
+        for position in positions.split(',') {
+            let Some((start, end)) = position.split_once('-') else {
+                continue;
+            };
+            let (Ok(start), Ok(end)) = (start.parse::<usize>(), end.parse::<usize>()) else {
+                continue;
+            };
+            let Some(name) = chars.get(start..=end) else {
+                continue;
+            };
+            parsed.push(Emote {
+                id: id.to_string(),
+                name: name.iter().collect(),
+                range: start..end + 1,
+            });
         }
     }
+    parsed
 }
 
 impl Display for TwitchMessage {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            TwitchMessage::JOIN { channel } => {
-                write!(f, "JOIN ( channel: {channel} )")
+            TwitchMessage::Join { channel } => {
+                write!(f, "Join ( channel: {channel} )")
             }
-            TwitchMessage::PRIVMSG { sender, channel, msg } => {
+            TwitchMessage::Privmsg { sender, channel, msg, .. } => {
                 write!(f, "PRIVMSG ( sender: {sender}, channel: {channel}, msg: {msg} )")
             }
-            TwitchMessage::UNIMPLEMENTED { msg } => {
-                write!(f, "UNIMPLEMENTED ( msg: {msg} )")
+            TwitchMessage::UserNotice { channel, tags, .. } => {
+                write!(
+                    f,
+                    "USERNOTICE ( channel: {channel}, msg-id: {} )",
+                    tags.get("msg-id").map_or("", |id| id)
+                )
+            }
+            TwitchMessage::ClearMsg { channel, tags } => {
+                write!(
+                    f,
+                    "CLEARMSG ( channel: {channel}, target-msg-id: {} )",
+                    tags.get("target-msg-id").map_or("", |id| id)
+                )
+            }
+            TwitchMessage::ClearChat { channel, user, .. } => {
+                write!(f, "CLEARCHAT ( channel: {channel}, user: {user} )")
+            }
+            TwitchMessage::Unimplemented { msg } => {
+                write!(f, "Unimplemented ( msg: {msg} )")
             }
         }
     }
 }
 
-impl Into<TwitchMessage> for &str {
-    fn into(self) -> TwitchMessage {
+impl From<&IrcMessage> for TwitchMessage {
+    fn from(message: &IrcMessage) -> Self {
         //:zartisimo!zartisimo@zartisimo.tmi.twitch.tv PRIVMSG #zartisimo :test
         //|__________________________________________| |_____| |________| |____...
-        let mut iter = self.split(" ");
-        let sender: String = iter.next().expect("sender part to be present in twitch message")[1..]
-            .split("!")
-            .next()
-            .expect("sender to be present in twitch message sender part")
-            .into();
-        let command = iter.next().expect("command to be present in twitch message");
-        match command {
-            "PRIVMSG" => {
-                let channel: String = iter.next().expect("channel to be present")[1..].into();
-                let mut msg = iter.collect::<Vec<&str>>().join(" ")[1..].to_string();
-                msg.remove(msg.len() - 1);
-                msg.remove(msg.len() - 1);
-                TwitchMessage::PRIVMSG { sender, channel, msg }
-            }
-            _ => TwitchMessage::UNIMPLEMENTED { msg: self.to_string() },
+        let channel = message.param(0).unwrap_or("").trim_start_matches('#').to_string();
+        match message.command.as_str() {
+            "PRIVMSG" => TwitchMessage::Privmsg {
+                sender: message.nick().unwrap_or("").to_string(),
+                channel,
+                msg: message.param(1).unwrap_or("").to_string(),
+                tags: message.tags.clone(),
+            },
+            "JOIN" => TwitchMessage::Join { channel },
+            "USERNOTICE" => TwitchMessage::UserNotice {
+                channel,
+                msg: message.param(1).map(str::to_string),
+                tags: message.tags.clone(),
+            },
+            "CLEARMSG" => TwitchMessage::ClearMsg {
+                channel,
+                tags: message.tags.clone(),
+            },
+            // Without a user the whole chat was cleared
+            "CLEARCHAT" if message.param(1).is_some() => TwitchMessage::ClearChat {
+                channel,
+                user: message.param(1).unwrap_or("").to_string(),
+                tags: message.tags.clone(),
+            },
+            _ => TwitchMessage::Unimplemented { msg: message.to_string() },
         }
     }
 }
 
-impl Into<String> for TwitchMessage {
-    fn into(self) -> String {
-        match self {
-            TwitchMessage::JOIN { channel } => {
-                format!("JOIN #{channel}")
+pub struct TwitchConfig {
+    pub chat_url: String,
+    pub api_url: String,
+    pub token: String,
+    /// Only needed for Helix, chat works without it
+    pub client_id: Option<String>,
+    pub nick: String,
+    pub channel: String,
+}
+
+impl TwitchConfig {
+    pub fn from_env() -> Option<Self> {
+        let nick = env::var("TWITCH_NICK").unwrap_or("zartisimo".to_string());
+        Some(Self {
+            chat_url: env::var("TWITCH_CHAT_URL").unwrap_or(DEFAULT_TWITCH_CHAT_URL.to_string()),
+            api_url: env::var("TWITCH_API_URL").unwrap_or(DEFAULT_TWITCH_API_URL.to_string()),
+            token: env::var("TWITCH_TOKEN").ok()?,
+            client_id: env::var("TWITCH_CLIENT_ID").ok(),
+            channel: env::var("TWITCH_CHANNEL").unwrap_or(nick.clone()),
+            nick,
+        })
+    }
+}
+
+pub fn connector() -> Option<Box<dyn PlatformConnector>> {
+    Some(Box::new(TwitchConnector {
+        config: TwitchConfig::from_env()?,
+        client: None,
+        api_client: Client::new(),
+    }))
+}
+
+/// Twitch chat is IRC over a WebSocket, with Twitch specific commands and tags.
+pub struct TwitchConnector {
+    config: TwitchConfig,
+    client: Option<IrcClient>,
+    api_client: Client,
+}
+
+impl TwitchConnector {
+    fn client(&mut self) -> Result<&mut IrcClient> {
+        self.client.as_mut().ok_or(anyhow::Error::msg("Twitch chat is not connected"))
+    }
+}
+
+impl PlatformConnector for TwitchConnector {
+    fn info(&self) -> PlatformInfo {
+        PlatformInfo {
+            platform: Platform::TWITCH,
+            label: "Twitch",
+            color: [191, 148, 255],
+        }
+    }
+
+    fn connect(&mut self) -> Result<()> {
+        let config = IrcConfig {
+            server: IrcServer::WebSocket {
+                url: self.config.chat_url.clone(),
+            },
+            nick: self.config.nick.clone(),
+            realname: self.config.nick.clone(),
+            password: Some(format!("oauth:{}", self.config.token)),
+            auth: IrcAuth::None,
+            channels: vec![format!("#{}", self.config.channel)],
+            capabilities: vec!["twitch.tv/tags".to_string(), "twitch.tv/commands".to_string()],
+        };
+        self.client = Some(IrcClient::connect(config)?);
+        Ok(())
+    }
+
+    fn poll(&mut self) -> Result<Vec<IncomingEvent>> {
+        let irc_message = match self.client()?.read() {
+            Ok(message) => message,
+            Err(err) if is_timeout(&err) => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        let nick = self.client()?.nick().to_string();
+        let reply_to = irc_message.param(0).map(|channel| channel.trim_start_matches('#').to_string());
+        let event = match TwitchMessage::from(&irc_message).into_event() {
+            Some(PlatformEvent::Chat(mut message)) => {
+                message.is_bot = message.sender == nick;
+                PlatformEvent::Chat(message)
             }
-            TwitchMessage::PRIVMSG {
-                sender: _,
-                channel,
-                msg,
-            } => {
-                format!("PRIVMSG #{channel} :{msg}")
+            Some(event) => event,
+            None => return Ok(Vec::new()),
+        };
+        Ok(vec![IncomingEvent::new(event, reply_to)])
+    }
+
+    fn send_message(&mut self, channel: Option<&str>, msg: &str) -> Result<()> {
+        let channel = format!("#{}", channel.unwrap_or(&self.config.channel));
+        self.client()?.privmsg(&channel, msg)
+    }
+
+    fn live_since(&self) -> Option<DateTime<Utc>> {
+        let client_id = self.config.client_id.as_ref()?;
+        let response = self
+            .api_client
+            .get(format!("{}/streams", self.config.api_url))
+            .timeout(API_TIMEOUT)
+            .query(&[("user_login", &self.config.channel)])
+            .bearer_auth(&self.config.token)
+            .header("Client-Id", client_id)
+            .send()
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.json::<HelixStreamsResponse>());
+        match response {
+            // No stream means offline
+            Ok(response) => parse_timestamp(&response.data.first()?.started_at),
+            Err(err) => {
+                eprintln!("WARN - Cannot get Twitch stream of {}: {:?}", self.config.channel, err);
+                None
             }
-            TwitchMessage::UNIMPLEMENTED { msg: _ } => todo!("Probably won't exist"),
         }
     }
+
+    fn moderator(&self) -> Option<Box<dyn Moderator>> {
+        Some(Box::new(TwitchModerator::from_env()?))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct HelixStreamsResponse {
+    data: Vec<HelixStream>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HelixStream {
+    started_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct HelixUsersResponse {
+    data: Vec<HelixUser>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HelixUser {
+    id: String,
 }
 
-pub fn get_twitch_stream() -> Result<WebSocket<MaybeTlsStream<TcpStream>>> {
-    let token = env::var("TWITCH_TOKEN").expect("TWITCH_TOKEN to be defined");
+/// Moderation through the Helix API, IRC moderation commands (`/ban`, `/timeout`...) are no
+/// longer supported by Twitch.
+pub struct TwitchModerator {
+    client: Client,
+    api_url: String,
+    token: String,
+    client_id: String,
+    broadcaster_id: String,
+    moderator_id: String,
+}
+
+impl TwitchModerator {
+    pub fn from_env() -> Option<Self> {
+        let broadcaster_id = env::var("TWITCH_BROADCASTER_ID").ok()?;
+        Some(Self {
+            client: Client::new(),
+            api_url: env::var("TWITCH_API_URL").unwrap_or(DEFAULT_TWITCH_API_URL.to_string()),
+            token: env::var("TWITCH_TOKEN").ok()?,
+            client_id: env::var("TWITCH_CLIENT_ID").ok()?,
+            moderator_id: env::var("TWITCH_MODERATOR_ID").unwrap_or(broadcaster_id.clone()),
+            broadcaster_id,
+        })
+    }
 
-    let (mut stream, _) = connect("ws://irc-ws.chat.twitch.tv:80").expect("to connect");
+    fn authorized(&self, request: RequestBuilder) -> RequestBuilder {
+        request.bearer_auth(&self.token).header("Client-Id", &self.client_id)
+    }
+
+    /// Helix only accepts user ids, but chat only gives us logins. Anything that is not numeric is
+    /// treated as a login and resolved.
+    fn resolve_user_id(&self, user: &str) -> Result<String> {
+        if user.chars().all(|c| c.is_ascii_digit()) {
+            return Ok(user.to_string());
+        }
+        let response = self
+            .authorized(self.client.get(format!("{}/users", self.api_url)))
+            .query(&[("login", user)])
+            .send()?
+            .error_for_status()?
+            .json::<HelixUsersResponse>()?;
+        response
+            .data
+            .into_iter()
+            .next()
+            .map(|user| user.id)
+            .ok_or(anyhow::Error::msg(format!("Twitch user {user} not found")))
+    }
 
-    stream.send(Message::Text(format!("PASS oauth:{token}")))?;
-    stream.send(Message::Text(format!("NICK zartisimo")))?;
-    stream.send(Message::Text(
-        TwitchMessage::JOIN {
-            channel: "zartisimo".to_string(),
+    fn ban(&self, user_id: &str, duration: Option<u64>) -> Result<()> {
+        let mut data = json!({ "user_id": self.resolve_user_id(user_id)? });
+        if let Some(duration) = duration {
+            data["duration"] = json!(duration);
         }
-        .into(),
-    ))?;
-    return Ok(stream);
+        self.authorized(self.client.post(format!("{}/moderation/bans", self.api_url)))
+            .query(&[("broadcaster_id", &self.broadcaster_id), ("moderator_id", &self.moderator_id)])
+            .json(&json!({ "data": data }))
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
 }
 
-#[allow(dead_code)]
-pub fn get_twitch_stream_arcmutex() -> Result<Arc<Mutex<WebSocket<MaybeTlsStream<TcpStream>>>>> {
-    return Ok(Arc::new(Mutex::new(get_twitch_stream()?)));
+impl Moderator for TwitchModerator {
+    fn platform(&self) -> Platform {
+        Platform::TWITCH
+    }
+
+    fn apply(&self, action: &ModerationAction) -> Result<()> {
+        let request = match action {
+            ModerationAction::DeleteMessage { message_id } => self.client.delete(format!("{}/moderation/chat", self.api_url)).query(&[
+                ("broadcaster_id", &self.broadcaster_id),
+                ("moderator_id", &self.moderator_id),
+                ("message_id", message_id),
+            ]),
+            ModerationAction::Timeout { user_id, duration } => return self.ban(user_id, Some(duration.as_secs())),
+            ModerationAction::Ban { user_id } => return self.ban(user_id, None),
+            ModerationAction::Unban { user_id } => self.client.delete(format!("{}/moderation/bans", self.api_url)).query(&[
+                ("broadcaster_id", &self.broadcaster_id),
+                ("moderator_id", &self.moderator_id),
+                ("user_id", &self.resolve_user_id(user_id)?),
+            ]),
+            ModerationAction::AddModerator { user_id } => self.client.post(format!("{}/moderation/moderators", self.api_url)).query(&[
+                ("broadcaster_id", &self.broadcaster_id),
+                ("user_id", &self.resolve_user_id(user_id)?),
+            ]),
+            ModerationAction::RemoveModerator { user_id } => {
+                self.client.delete(format!("{}/moderation/moderators", self.api_url)).query(&[
+                    ("broadcaster_id", &self.broadcaster_id),
+                    ("user_id", &self.resolve_user_id(user_id)?),
+                ])
+            }
+        };
+        self.authorized(request).send()?.error_for_status()?;
+        Ok(())
+    }
 }
 
-// TODO: Maybe create a command queue in case the CommandHandler is busy and cannot be locked
-// (this is kinda too optimistic for a stream with 1 viewer XD)
-pub fn listen(
-    command_handler: Arc<Mutex<CommandHandler>>,
-    twitch_sender: UnboundedSender<PlatformMessage>,
-) -> Result<()> {
-    let mut stream = get_twitch_stream()?;
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{TcpListener, TcpStream},
+        thread,
+    };
+
+    use tungstenite::{accept, Message, WebSocket};
+
+    use super::*;
+
+    fn twitch_message(line: &str) -> TwitchMessage {
+        TwitchMessage::from(&IrcMessage::parse(line).unwrap())
+    }
+
+    #[test]
+    fn privmsg_is_a_chat_message() {
+        let message = twitch_message(
+            "@badges=moderator/1,subscriber/12;display-name=Zartisimo;id=abc;user-id=1234 :zartisimo!zartisimo@zartisimo.tmi.twitch.tv PRIVMSG #zartisimo :!today",
+        );
+        let chat = PlatformMessage::try_from(message).unwrap();
+        assert_eq!(chat.sender, "zartisimo");
+        assert_eq!(chat.sender_id.as_deref(), Some("1234"));
+        assert_eq!(chat.platform_id.as_deref(), Some("abc"));
+        assert_eq!(chat.channel.as_deref(), Some("zartisimo"));
+        assert_eq!(
+            chat.badges,
+            [Badge::new("moderator", Some("1")), Badge::new("subscriber", Some("12"))]
+        );
+        assert_eq!(chat.msg, "!today");
+    }
+
+    #[test]
+    fn other_messages_are_not_chat_messages() {
+        for line in [
+            ":zartisimo!zartisimo@zartisimo.tmi.twitch.tv JOIN #zartisimo",
+            "@msg-id=sub;login=viewer :tmi.twitch.tv USERNOTICE #zartisimo :hi",
+            "@target-msg-id=abc :tmi.twitch.tv CLEARMSG #zartisimo :bad words",
+            "@ban-duration=600;target-user-id=42 :tmi.twitch.tv CLEARCHAT #zartisimo :troll",
+            ":tmi.twitch.tv ROOMSTATE #zartisimo",
+        ] {
+            assert!(PlatformMessage::try_from(twitch_message(line)).is_err(), "{line}");
+        }
+    }
 
-    loop {
-        let msg = stream
-            .read()
-            .map_err(|err| {
-                eprintln!("Error reading msg from stream: {:?}", err);
+    #[test]
+    fn moderation_notices_become_events() {
+        let timeout = twitch_message("@ban-duration=600;target-user-id=42 :tmi.twitch.tv CLEARCHAT #zartisimo :troll");
+        assert_eq!(
+            timeout.into_event(),
+            Some(PlatformEvent::Moderation {
+                platform: Platform::TWITCH,
+                moderator: None,
+                action: ModerationAction::Timeout {
+                    user_id: "42".to_string(),
+                    duration: Duration::from_secs(600),
+                },
+            })
+        );
+        let deleted = twitch_message("@target-msg-id=abc :tmi.twitch.tv CLEARMSG #zartisimo :bad words");
+        assert_eq!(
+            deleted.into_event(),
+            Some(PlatformEvent::MessageDeleted {
+                platform: Platform::TWITCH,
+                platform_id: "abc".to_string(),
             })
-            .expect("msg");
+        );
+        assert_eq!(twitch_message(":tmi.twitch.tv ROOMSTATE #zartisimo").into_event(), None);
+    }
 
-        let message: TwitchMessage = msg.to_text().unwrap().into();
+    fn expect(socket: &mut WebSocket<TcpStream>, expected: &str) {
+        match socket.read().unwrap() {
+            Message::Text(text) => assert_eq!(text, expected),
+            other => panic!("Unexpected frame {other:?}"),
+        }
+    }
 
-        match &message {
-            TwitchMessage::PRIVMSG {
-                sender,
-                channel: _,
-                msg,
-            } => {
-                if msg.starts_with(COMMAND_SYMBOL) {
-                    let response = command_handler
-                        .lock()
-                        .expect("To lock command_handler for Twitch thread")
-                        // TODO: find the best way to do this
-                        .handle_command(sender.to_string(), msg[1..].to_string())
-                        .unwrap_or(None);
-                    if let Some(response) = response {
-                        stream.send(Message::Text(
-                            TwitchMessage::PRIVMSG {
-                                sender: "zartisimo".to_string(),
-                                channel: "zartisimo".to_string(),
-                                msg: response,
-                            }
-                            .into(),
-                        ))?;
-                    }
-                } else {
-                    twitch_sender.send(message.into()).expect("To be able to send");
-                }
+    fn send(socket: &mut WebSocket<TcpStream>, lines: &[&str]) {
+        let frame: String = lines.iter().map(|line| format!("{line}\r\n")).collect();
+        socket.send(Message::Text(frame)).unwrap();
+    }
+
+    #[test]
+    fn chats_over_the_irc_client() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let mut socket = accept(listener.accept().unwrap().0).unwrap();
+            expect(&mut socket, "CAP REQ twitch.tv/tags");
+            expect(&mut socket, "CAP REQ twitch.tv/commands");
+            expect(&mut socket, "PASS oauth:token");
+            expect(&mut socket, "NICK bot");
+            expect(&mut socket, "USER bot 0 * bot");
+            send(
+                &mut socket,
+                &[
+                    ":tmi.twitch.tv CAP * ACK :twitch.tv/tags",
+                    ":tmi.twitch.tv CAP * ACK :twitch.tv/commands",
+                ],
+            );
+            expect(&mut socket, "CAP END");
+            send(&mut socket, &[":tmi.twitch.tv 001 bot :Welcome, GLHF!"]);
+            expect(&mut socket, "JOIN #zartisimo");
+            send(
+                &mut socket,
+                &[
+                    "PING :tmi.twitch.tv",
+                    "@display-name=Viewer;user-id=42 :viewer!viewer@viewer.tmi.twitch.tv PRIVMSG #zartisimo :!today",
+                ],
+            );
+            expect(&mut socket, "PONG tmi.twitch.tv");
+            expect(&mut socket, "PRIVMSG #zartisimo :Just chatting");
+            expect(&mut socket, "PRIVMSG #zartisimo :and coding");
+        });
+
+        let mut connector = TwitchConnector {
+            config: TwitchConfig {
+                chat_url: format!("ws://127.0.0.1:{port}"),
+                api_url: DEFAULT_TWITCH_API_URL.to_string(),
+                token: "token".to_string(),
+                client_id: None,
+                nick: "bot".to_string(),
+                channel: "zartisimo".to_string(),
+            },
+            client: None,
+            api_client: Client::new(),
+        };
+        connector.connect().unwrap();
+        let mut events = Vec::new();
+        for _ in 0..20 {
+            events.extend(connector.poll().unwrap());
+            if !events.is_empty() {
+                break;
             }
-            TwitchMessage::JOIN { channel: _ } => {}
-            TwitchMessage::UNIMPLEMENTED { msg: _ } => {}
         }
+        let [IncomingEvent {
+            event: PlatformEvent::Chat(message),
+            reply_to,
+        }] = events.as_slice()
+        else {
+            panic!("Expected a chat message, got {} events", events.len());
+        };
+        assert_eq!(message.sender, "viewer");
+        assert_eq!(message.sender_id.as_deref(), Some("42"));
+        assert_eq!(message.msg, "!today");
+        assert_eq!(reply_to.as_deref(), Some("zartisimo"));
+
+        connector.send_message(reply_to.as_deref(), "Just chatting\nand coding").unwrap();
+        server.join().unwrap();
     }
 }
@@ -0,0 +1,436 @@
+use std::{
+    env,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use reqwest::{blocking::Client, header::HeaderMap, StatusCode};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    command::COMMAND_SYMBOL,
+    connector::{idle, IncomingEvent, PlatformConnector},
+    messages::{parse_timestamp, Platform, PlatformInfo, PlatformMessage, ReplyContext},
+};
+
+static DEFAULT_TWITTER_API_URL: &str = "https://api.twitter.com/2";
+static MIN_POLLING_INTERVAL: Duration = Duration::from_secs(15);
+
+impl Platform {
+    pub const TWITTER: Platform = Platform::new("twitter");
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct TweetsResponse {
+    data: Vec<Tweet>,
+    includes: Includes,
+    meta: Meta,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Tweet {
+    pub id: String,
+    pub text: String,
+    pub author_id: String,
+    pub created_at: String,
+    pub referenced_tweets: Vec<ReferencedTweet>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ReferencedTweet {
+    #[serde(rename = "type")]
+    pub reference_type: String,
+    pub id: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Includes {
+    users: Vec<User>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct User {
+    id: String,
+    username: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Meta {
+    newest_id: Option<String>,
+}
+
+/// What to poll: the mentions of a user or the results of a search query.
+pub enum TwitterSource {
+    Mentions { user_id: String },
+    Search { query: String },
+}
+
+pub struct TwitterConfig {
+    pub api_url: String,
+    pub source: TwitterSource,
+    /// App-only token, enough to read
+    pub bearer_token: String,
+    /// User-context token, needed to reply
+    pub user_token: Option<String>,
+}
+
+impl TwitterConfig {
+    pub fn from_env() -> Option<Self> {
+        let source = match (env::var("TWITTER_USER_ID"), env::var("TWITTER_QUERY")) {
+            (Ok(user_id), _) => TwitterSource::Mentions { user_id },
+            (_, Ok(query)) => TwitterSource::Search { query },
+            _ => return None,
+        };
+        Some(Self {
+            api_url: env::var("TWITTER_API_URL").unwrap_or(DEFAULT_TWITTER_API_URL.to_string()),
+            source,
+            bearer_token: env::var("TWITTER_BEARER_TOKEN").ok()?,
+            user_token: env::var("TWITTER_USER_TOKEN").ok(),
+        })
+    }
+}
+
+/// A tweet together with the username of its author.
+#[derive(Debug, Clone)]
+pub struct Mention {
+    pub tweet: Tweet,
+    pub username: String,
+}
+
+impl From<Mention> for PlatformMessage {
+    fn from(mention: Mention) -> Self {
+        let reply = mention
+            .tweet
+            .referenced_tweets
+            .iter()
+            .find(|referenced| referenced.reference_type == "replied_to")
+            .map(|referenced| ReplyContext {
+                platform_id: referenced.id.clone(),
+                sender: None,
+                msg: None,
+            });
+        PlatformMessage {
+            platform_id: Some(mention.tweet.id),
+            sent_at: parse_timestamp(&mention.tweet.created_at),
+            sender_id: Some(mention.tweet.author_id),
+            reply,
+            ..PlatformMessage::new(Platform::TWITTER, mention.username, mention.tweet.text)
+        }
+    }
+}
+
+impl Mention {
+    /// Tweets start with the handles they reply to, the command (if any) comes after them.
+    pub fn command(&self) -> Option<&str> {
+        let mut text = self.tweet.text.trim_start();
+        while text.starts_with('@') {
+            text = text
+                .split_once(char::is_whitespace)
+                .map(|(_, rest)| rest.trim_start())
+                .unwrap_or("");
+        }
+        text.strip_prefix(COMMAND_SYMBOL)
+    }
+}
+
+pub struct TwitterClient {
+    config: TwitterConfig,
+    client: Client,
+    since_id: Option<String>,
+    polled: bool,
+}
+
+impl TwitterClient {
+    pub fn new(config: TwitterConfig) -> Self {
+        Self {
+            config,
+            client: Client::new(),
+            since_id: None,
+            polled: false,
+        }
+    }
+
+    /// Fetches the tweets posted since the last poll, oldest first, and how long to wait before
+    /// polling again according to the rate limit headers.
+    pub fn poll(&mut self) -> Result<(Vec<Mention>, Duration)> {
+        let request = match &self.config.source {
+            TwitterSource::Mentions { user_id } => self.client.get(format!("{}/users/{}/mentions", self.config.api_url, user_id)),
+            TwitterSource::Search { query } => self
+                .client
+                .get(format!("{}/tweets/search/recent", self.config.api_url))
+                .query(&[("query", query)]),
+        };
+        let mut request = request.bearer_auth(&self.config.bearer_token).query(&[
+            ("expansions", "author_id"),
+            ("user.fields", "username"),
+            ("tweet.fields", "created_at,referenced_tweets"),
+        ]);
+        if let Some(since_id) = &self.since_id {
+            request = request.query(&[("since_id", since_id)]);
+        }
+
+        let response = request.send()?;
+        let polling_interval = polling_interval(response.headers());
+        if response.status() == StatusCode::TOO_MANY_REQUESTS {
+            return Ok((Vec::new(), polling_interval));
+        }
+        let response = response.error_for_status()?.json::<TweetsResponse>()?;
+
+        // Skip what was posted before we started, we don't want to answer old commands
+        if response.meta.newest_id.is_some() {
+            self.since_id = response.meta.newest_id;
+        }
+        if !self.polled {
+            self.polled = true;
+            return Ok((Vec::new(), polling_interval));
+        }
+
+        let users = response.includes.users;
+        let mentions = response
+            .data
+            .into_iter()
+            .rev()
+            .map(|tweet| Mention {
+                username: users
+                    .iter()
+                    .find(|user| user.id == tweet.author_id)
+                    .map(|user| user.username.clone())
+                    .unwrap_or(tweet.author_id.clone()),
+                tweet,
+            })
+            .collect();
+        Ok((mentions, polling_interval))
+    }
+
+    /// Whether the tweet was posted by the account whose mentions are polled.
+    pub fn is_own(&self, tweet: &Tweet) -> bool {
+        matches!(&self.config.source, TwitterSource::Mentions { user_id } if *user_id == tweet.author_id)
+    }
+
+    /// Posts a tweet, as a reply to `in_reply_to` if given.
+    pub fn post(&self, text: &str, in_reply_to: Option<&str>) -> Result<()> {
+        let token = self
+            .config
+            .user_token
+            .as_ref()
+            .ok_or(anyhow::Error::msg("TWITTER_USER_TOKEN to be defined to tweet"))?;
+        let mut tweet = json!({ "text": text });
+        if let Some(tweet_id) = in_reply_to {
+            tweet["reply"] = json!({ "in_reply_to_tweet_id": tweet_id });
+        }
+        self.client
+            .post(format!("{}/tweets", self.config.api_url))
+            .bearer_auth(token)
+            .json(&tweet)
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Spreads the remaining requests of the current rate limit window over the time left until it
+/// resets, never polling faster than `MIN_POLLING_INTERVAL`.
+fn polling_interval(headers: &HeaderMap) -> Duration {
+    let header = |name: &str| -> Option<u64> { headers.get(name)?.to_str().ok()?.parse().ok() };
+    let (Some(remaining), Some(reset)) = (header("x-rate-limit-remaining"), header("x-rate-limit-reset")) else {
+        return MIN_POLLING_INTERVAL;
+    };
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time to go forward").as_secs();
+    let until_reset = Duration::from_secs(reset.saturating_sub(now));
+    if remaining == 0 {
+        return until_reset.max(MIN_POLLING_INTERVAL);
+    }
+    (until_reset / remaining as u32).max(MIN_POLLING_INTERVAL)
+}
+
+pub fn connector() -> Option<Box<dyn PlatformConnector>> {
+    Some(Box::new(TwitterConnector {
+        client: TwitterClient::new(TwitterConfig::from_env()?),
+        next_poll: Instant::now(),
+    }))
+}
+
+/// Mentions are answered with replies to the tweet, anything sent without a tweet to reply to
+/// is posted as a new tweet.
+pub struct TwitterConnector {
+    client: TwitterClient,
+    next_poll: Instant,
+}
+
+impl PlatformConnector for TwitterConnector {
+    fn info(&self) -> PlatformInfo {
+        PlatformInfo {
+            platform: Platform::TWITTER,
+            label: "Twitter",
+            color: [29, 155, 240],
+        }
+    }
+
+    fn connect(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn poll(&mut self) -> Result<Vec<IncomingEvent>> {
+        if Instant::now() < self.next_poll {
+            idle();
+            return Ok(Vec::new());
+        }
+
+        self.next_poll = Instant::now() + MIN_POLLING_INTERVAL;
+        let (mentions, polling_interval) = self.client.poll()?;
+        self.next_poll = Instant::now() + polling_interval;
+
+        Ok(mentions
+            .into_iter()
+            .map(|mention| {
+                let reply_to = Some(mention.tweet.id.clone());
+                let mut message: PlatformMessage = mention.clone().into();
+                message.is_bot = self.client.is_own(&mention.tweet);
+                // Leave the command where the command handler expects it, without the handles
+                if let Some(command) = mention.command() {
+                    message.msg = format!("{COMMAND_SYMBOL}{command}");
+                }
+                IncomingEvent::new(message, reply_to)
+            })
+            .collect())
+    }
+
+    fn send_message(&mut self, tweet_id: Option<&str>, msg: &str) -> Result<()> {
+        self.client.post(msg, tweet_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::fake_http::{ok, serve, FakeResponse};
+
+    fn now() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+    }
+
+    fn client(api_url: String) -> TwitterClient {
+        TwitterClient::new(TwitterConfig {
+            api_url: format!("{api_url}/2"),
+            source: TwitterSource::Mentions { user_id: "1".to_string() },
+            bearer_token: "bearer".to_string(),
+            user_token: None,
+        })
+    }
+
+    fn tweets(ids: &[&str]) -> Value {
+        let data: Vec<Value> = ids
+            .iter()
+            .map(|id| json!({ "id": id, "text": format!("@bot !today {id}"), "author_id": "42" }))
+            .collect();
+        json!({
+            "data": data,
+            "includes": { "users": [{ "id": "42", "username": "viewer" }] },
+            "meta": { "newest_id": ids.first(), "result_count": ids.len() }
+        })
+    }
+
+    #[test]
+    fn only_tweets_since_the_last_poll_are_returned() {
+        let (url, requests, server) = serve(vec![
+            ok(tweets(&["10"])),
+            ok(tweets(&["12", "11"])),
+            ok(json!({ "meta": { "result_count": 0 } })),
+            ok(tweets(&["13"])),
+        ]);
+        let mut client = client(url);
+
+        // What was there before we started is skipped
+        assert!(client.poll().unwrap().0.is_empty());
+        assert!(!requests.recv().unwrap().contains("since_id"));
+
+        let ids: Vec<String> = client.poll().unwrap().0.into_iter().map(|mention| mention.tweet.id).collect();
+        assert_eq!(ids, ["11", "12"]);
+        assert!(requests.recv().unwrap().contains("since_id=10"));
+
+        assert!(client.poll().unwrap().0.is_empty());
+        assert!(requests.recv().unwrap().contains("since_id=12"));
+
+        let mentions = client.poll().unwrap().0;
+        assert!(requests.recv().unwrap().contains("since_id=12"));
+        assert_eq!(mentions[0].username, "viewer");
+        assert_eq!(mentions[0].command(), Some("today 13"));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn polling_follows_the_rate_limit() {
+        let headers = |remaining: Option<u64>, reset: Option<u64>| {
+            let mut headers = HeaderMap::new();
+            if let Some(remaining) = remaining {
+                headers.insert("x-rate-limit-remaining", HeaderValue::from(remaining));
+            }
+            if let Some(reset) = reset {
+                headers.insert("x-rate-limit-reset", HeaderValue::from(reset));
+            }
+            headers
+        };
+        let secs = |interval: Duration| interval.as_secs_f64().round() as u64;
+
+        assert_eq!(polling_interval(&headers(None, None)), MIN_POLLING_INTERVAL);
+        assert_eq!(secs(polling_interval(&headers(Some(10), Some(now() + 600)))), 60);
+        // Never faster than the minimum, even with requests to spare
+        assert_eq!(polling_interval(&headers(Some(450), Some(now() + 600))), MIN_POLLING_INTERVAL);
+        assert_eq!(secs(polling_interval(&headers(Some(0), Some(now() + 300)))), 300);
+        assert_eq!(polling_interval(&headers(Some(0), Some(now() - 10))), MIN_POLLING_INTERVAL);
+
+        let (url, _requests, server) = serve(vec![FakeResponse {
+            headers: vec![
+                ("x-rate-limit-remaining", "5".to_string()),
+                ("x-rate-limit-reset", (now() + 300).to_string()),
+            ],
+            ..ok(tweets(&["10"]))
+        }]);
+        let (_, interval) = client(url).poll().unwrap();
+        assert_eq!(secs(interval), 60);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn backs_off_until_the_reset_when_rate_limited() {
+        let (url, requests, server) = serve(vec![
+            ok(tweets(&["10"])),
+            FakeResponse {
+                status: 429,
+                headers: vec![
+                    ("x-rate-limit-remaining", "0".to_string()),
+                    ("x-rate-limit-reset", (now() + 120).to_string()),
+                ],
+                body: json!({ "title": "Too Many Requests" }),
+            },
+        ]);
+        let mut connector = TwitterConnector {
+            client: client(url),
+            next_poll: Instant::now(),
+        };
+
+        assert!(connector.poll().unwrap().is_empty());
+        requests.recv().unwrap();
+        connector.next_poll = Instant::now();
+        assert!(connector.poll().unwrap().is_empty());
+        assert!(requests.recv().unwrap().contains("since_id=10"));
+        assert_eq!(connector.client.since_id.as_deref(), Some("10"));
+        let wait = connector.next_poll - Instant::now();
+        assert!(wait > Duration::from_secs(110) && wait <= Duration::from_secs(120), "{wait:?}");
+
+        // No request until then, the fake would not answer it
+        assert!(connector.poll().unwrap().is_empty());
+        server.join().unwrap();
+        assert!(requests.try_recv().is_err());
+    }
+}
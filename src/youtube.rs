@@ -1,87 +1,680 @@
 use crate::{
-    messages::{Platform, PlatformMessage},
-    youtube_model::{LiveStreamsResponse, YoutubeResponse},
+    connector::{idle, IncomingEvent, PlatformConnector, API_TIMEOUT},
+    messages::{parse_timestamp, Badge, Platform, PlatformEvent, PlatformInfo, PlatformMessage},
+    moderation::{ModerationAction, Moderator},
+    youtube_model::{
+        ChannelListResponse, LiveChatBan, LiveChatBanSnippet, LiveChatMessage, LiveChatModerator, LiveChatModeratorListResponse,
+        LiveChatModeratorSnippet, LiveStreamsResponse, SnippetDetails, UserDetails, YoutubeResponse,
+    },
 };
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use reqwest::{blocking::Client, header::ACCEPT};
-use std::{env, thread, time::Duration};
-use tokio::sync::mpsc::UnboundedSender;
-
-pub fn listen(sender: UnboundedSender<PlatformMessage>) -> Result<()> {
-    loop {
-        sender
-            .send(PlatformMessage {
-                sender: "yt-username".to_string(),
-                msg: "Hi this is a yt message".to_string(),
-                platform: Platform::Youtube,
-            })
-            .expect("To send the PlatformMessage");
-
-        thread::sleep(Duration::from_secs(10));
-    }
-    // WIP
-    /*
-    let youtube_token = env::var("YOUTUBE_TOKEN").expect("YOUTUBE_TOKEN to be defined");
-    let client = Client::new();
-    // TODO: Find a way to get live_chat_id dynamically. Otherwise, add link to request it on
-    // Youtube API docs: https://developers.google.com/youtube/v3/live/docs/liveBroadcasts/list
-    let url = format!("https://youtube.googleapis.com/youtube/v3/liveBroadcasts?part=snippet%2CcontentDetails%2Cstatus&broadcastType=all&mine=true&key={}", youtube_token);
-    let res = client
-        .get(url)
-        .header(ACCEPT, "application/json")
-        .send()
-        .map_err(|err| eprintln!("Error getting LiveStreams: {:?}", err))
-        .unwrap();
-    println!("res: {:?}", res);
-    let res = res
-        .json::<LiveStreamsResponse>()
-        .map_err(|err| eprintln!("Error parsing LiveStreams: {:?}", err))
-        .expect("LiveStreamsResponse");
-    let live_chat_id = &res.items.first().expect("livestream").snippet.live_chat_id;
-    let url = format!("https://youtube.googleapis.com/youtube/v3/liveChat/messages?liveChatId={}&part=id&part=snippet&part=authorDetails&key={}",
-                      live_chat_id, youtube_token);
-    let mut page_token = String::new();
-    loop {
-        let mut full_url = url.clone();
-        if page_token != "" {
-            full_url.push_str(&format!("&pageToken={}", page_token));
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    env,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+impl Platform {
+    pub const YOUTUBE: Platform = Platform::new("youtube");
+}
+
+static DEFAULT_YOUTUBE_API_URL: &str = "https://youtube.googleapis.com/youtube/v3";
+
+/// Authenticated client for the parts of the YouTube Live Streaming API that require OAuth (the
+/// moderation endpoints). Reading chat works with a plain API key, acting on it does not.
+pub struct YoutubeClient {
+    client: Client,
+    api_url: String,
+    oauth_token: String,
+    live_chat_id: Mutex<Option<String>>,
+}
+
+impl YoutubeClient {
+    pub fn new(api_url: String, oauth_token: String, live_chat_id: Option<String>) -> Self {
+        Self {
+            client: Client::new(),
+            api_url,
+            oauth_token,
+            live_chat_id: Mutex::new(live_chat_id),
         }
-        println!("Making request to Youtube");
-        match client.get(full_url).header(ACCEPT, "application/json").send() {
-            Ok(r) => {
-                println!("Response: {:?}", r);
-                match r.json::<YoutubeResponse>() {
-                    Ok(response) => {
-                        println!("Received {} yt messages", response.items.len());
-                        // TODO: Persist this somewhere to not reload old messages on restart
-                        page_token = response.next_page_token;
-                        for msg in response.items {
-                            match msg.snippet.type_field.as_str() {
-                                "textMessageEvent" => {
-                                    sender
-                                        .send(PlatformMessage {
-                                            sender: msg.author_details.display_name,
-                                            msg: msg.snippet.text_message_details.message_text,
-                                            platform: Platform::Youtube,
-                                        })
-                                        .expect("To send the PlatformMessage");
-                                }
-                                "superChatEvent" => {}
-                                _ => {}
-                            }
-                        }
-                    }
-                    Err(err) => {
-                        eprintln!("Error parsing response: {:?}", err);
-                    }
-                }
+    }
+
+    pub fn from_env() -> Option<Self> {
+        let oauth_token = env::var("YOUTUBE_OAUTH_TOKEN").ok()?;
+        let api_url = env::var("YOUTUBE_API_URL").unwrap_or(DEFAULT_YOUTUBE_API_URL.to_string());
+        Some(Self::new(api_url, oauth_token, env::var("YOUTUBE_LIVE_CHAT_ID").ok()))
+    }
+
+    /// Returns the configured live chat id or, if none was configured, the one of the currently
+    /// active broadcast of the authenticated channel.
+    pub fn live_chat_id(&self) -> Result<String> {
+        let mut live_chat_id = self.live_chat_id.lock().expect("To lock live_chat_id");
+        if let Some(id) = live_chat_id.as_ref() {
+            return Ok(id.clone());
+        }
+
+        let response = self
+            .client
+            .get(format!("{}/liveBroadcasts", self.api_url))
+            .query(&[("part", "snippet"), ("broadcastStatus", "active")])
+            .bearer_auth(&self.oauth_token)
+            .header(ACCEPT, "application/json")
+            .send()?
+            .error_for_status()?
+            .json::<LiveStreamsResponse>()?;
+        let id = response
+            .items
+            .first()
+            .map(|stream| stream.snippet.live_chat_id.clone())
+            .ok_or(anyhow::Error::msg("There is no active broadcast"))?;
+        *live_chat_id = Some(id.clone());
+        Ok(id)
+    }
+
+    /// Id of the authenticated channel, the one messages are sent as.
+    pub fn channel_id(&self) -> Result<String> {
+        let response = self
+            .client
+            .get(format!("{}/channels", self.api_url))
+            .query(&[("part", "id"), ("mine", "true")])
+            .bearer_auth(&self.oauth_token)
+            .header(ACCEPT, "application/json")
+            .timeout(API_TIMEOUT)
+            .send()?
+            .error_for_status()?
+            .json::<ChannelListResponse>()?;
+        response
+            .items
+            .into_iter()
+            .next()
+            .map(|channel| channel.id)
+            .ok_or(anyhow::Error::msg("The OAuth token has no channel"))
+    }
+
+    /// When the active broadcast of the authenticated channel started, `None` if there is none.
+    pub fn live_since(&self) -> Result<Option<DateTime<Utc>>> {
+        let response = self
+            .client
+            .get(format!("{}/liveBroadcasts", self.api_url))
+            .timeout(API_TIMEOUT)
+            .query(&[("part", "snippet"), ("broadcastStatus", "active")])
+            .bearer_auth(&self.oauth_token)
+            .header(ACCEPT, "application/json")
+            .send()?
+            .error_for_status()?
+            .json::<LiveStreamsResponse>()?;
+        Ok(response
+            .items
+            .first()
+            .and_then(|stream| stream.snippet.actual_start_time.as_ref())
+            .and_then(|start_time| parse_timestamp(start_time)))
+    }
+
+    /// `liveChatMessages.delete`
+    pub fn delete_message(&self, message_id: &str) -> Result<()> {
+        self.client
+            .delete(format!("{}/liveChat/messages", self.api_url))
+            .query(&[("id", message_id)])
+            .bearer_auth(&self.oauth_token)
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// `liveChatMessages.insert`
+    pub fn send_message(&self, text: &str) -> Result<()> {
+        let message = json!({
+            "snippet": {
+                "liveChatId": self.live_chat_id()?,
+                "type": "textMessageEvent",
+                "textMessageDetails": { "messageText": text }
+            }
+        });
+        self.client
+            .post(format!("{}/liveChat/messages", self.api_url))
+            .query(&[("part", "snippet")])
+            .bearer_auth(&self.oauth_token)
+            .json(&message)
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// `liveChatBans.insert`. A `duration` makes it a temporary ban (timeout), otherwise the ban is
+    /// permanent.
+    pub fn ban(&self, channel_id: &str, duration: Option<Duration>) -> Result<LiveChatBan> {
+        let ban = LiveChatBan {
+            snippet: LiveChatBanSnippet {
+                live_chat_id: self.live_chat_id()?,
+                type_field: match duration {
+                    Some(_) => "temporary".to_string(),
+                    None => "permanent".to_string(),
+                },
+                ban_duration_seconds: duration.map(|duration| duration.as_secs().to_string()),
+                banned_user_details: UserDetails {
+                    channel_id: channel_id.to_string(),
+                    ..Default::default()
+                },
+            },
+            ..Default::default()
+        };
+        Ok(self
+            .client
+            .post(format!("{}/liveChat/bans", self.api_url))
+            .query(&[("part", "snippet")])
+            .bearer_auth(&self.oauth_token)
+            .header(ACCEPT, "application/json")
+            .json(&ban)
+            .send()?
+            .error_for_status()?
+            .json::<LiveChatBan>()?)
+    }
+
+    /// `liveChatBans.delete`. Takes the id of the ban resource, not the one of the user.
+    pub fn unban(&self, ban_id: &str) -> Result<()> {
+        self.client
+            .delete(format!("{}/liveChat/bans", self.api_url))
+            .query(&[("id", ban_id)])
+            .bearer_auth(&self.oauth_token)
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// `liveChatModerators.list`
+    pub fn list_moderators(&self) -> Result<Vec<LiveChatModerator>> {
+        let live_chat_id = self.live_chat_id()?;
+        let mut moderators = Vec::new();
+        let mut page_token: Option<String> = None;
+        loop {
+            let mut request = self
+                .client
+                .get(format!("{}/liveChat/moderators", self.api_url))
+                .query(&[("liveChatId", live_chat_id.as_str()), ("part", "id,snippet"), ("maxResults", "50")])
+                .bearer_auth(&self.oauth_token)
+                .header(ACCEPT, "application/json");
+            if let Some(page_token) = &page_token {
+                request = request.query(&[("pageToken", page_token)]);
+            }
+            let response = request.send()?.error_for_status()?.json::<LiveChatModeratorListResponse>()?;
+            moderators.extend(response.items);
+            match response.next_page_token {
+                Some(token) if !token.is_empty() => page_token = Some(token),
+                _ => return Ok(moderators),
+            }
+        }
+    }
+
+    /// `liveChatModerators.insert`
+    pub fn add_moderator(&self, channel_id: &str) -> Result<LiveChatModerator> {
+        let moderator = LiveChatModerator {
+            snippet: LiveChatModeratorSnippet {
+                live_chat_id: self.live_chat_id()?,
+                moderator_details: UserDetails {
+                    channel_id: channel_id.to_string(),
+                    ..Default::default()
+                },
+            },
+            ..Default::default()
+        };
+        Ok(self
+            .client
+            .post(format!("{}/liveChat/moderators", self.api_url))
+            .query(&[("part", "snippet")])
+            .bearer_auth(&self.oauth_token)
+            .header(ACCEPT, "application/json")
+            .json(&moderator)
+            .send()?
+            .error_for_status()?
+            .json::<LiveChatModerator>()?)
+    }
+
+    /// `liveChatModerators.delete`. Takes the id of the moderator resource, not the one of the user.
+    pub fn remove_moderator(&self, moderator_id: &str) -> Result<()> {
+        self.client
+            .delete(format!("{}/liveChat/moderators", self.api_url))
+            .query(&[("id", moderator_id)])
+            .bearer_auth(&self.oauth_token)
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Maps the platform-agnostic `ModerationAction`s to YouTube calls. Users are identified by
+/// their channel id.
+pub struct YoutubeModerator {
+    client: YoutubeClient,
+    // channel id -> ban id, needed to lift bans issued during this session
+    bans: Mutex<HashMap<String, String>>,
+}
+
+impl YoutubeModerator {
+    pub fn from_env() -> Option<Self> {
+        Some(Self {
+            client: YoutubeClient::from_env()?,
+            bans: Mutex::new(HashMap::new()),
+        })
+    }
+}
+
+impl Moderator for YoutubeModerator {
+    fn platform(&self) -> Platform {
+        Platform::YOUTUBE
+    }
+
+    fn apply(&self, action: &ModerationAction) -> Result<()> {
+        match action {
+            ModerationAction::DeleteMessage { message_id } => self.client.delete_message(message_id),
+            ModerationAction::Timeout { user_id, duration } => {
+                let ban = self.client.ban(user_id, Some(*duration))?;
+                self.bans.lock().expect("To lock bans").insert(user_id.clone(), ban.id);
+                Ok(())
+            }
+            ModerationAction::Ban { user_id } => {
+                let ban = self.client.ban(user_id, None)?;
+                self.bans.lock().expect("To lock bans").insert(user_id.clone(), ban.id);
+                Ok(())
+            }
+            ModerationAction::Unban { user_id } => match self.bans.lock().expect("To lock bans").remove(user_id) {
+                Some(ban_id) => self.client.unban(&ban_id),
+                None => Err(anyhow::Error::msg(format!("No ban for {user_id} was issued in this session"))),
+            },
+            ModerationAction::AddModerator { user_id } => self.client.add_moderator(user_id).map(|_| ()),
+            ModerationAction::RemoveModerator { user_id } => {
+                let moderator = self
+                    .client
+                    .list_moderators()?
+                    .into_iter()
+                    .find(|moderator| moderator.snippet.moderator_details.channel_id == *user_id)
+                    .ok_or(anyhow::Error::msg(format!("{user_id} is not a moderator")))?;
+                self.client.remove_moderator(&moderator.id)
+            }
+        }
+    }
+
+    // The API can neither list bans nor lift one without its id
+    fn can_unban(&self, user_id: &str) -> bool {
+        self.bans.lock().expect("To lock bans").contains_key(user_id)
+    }
+}
+
+/// Paid messages, memberships, deletions and bans become their own events, anything else with
+/// something to show is chat.
+fn platform_event(message: &LiveChatMessage, live_chat_id: &str) -> Option<PlatformEvent> {
+    let platform = Platform::YOUTUBE;
+    let user = message
+        .author_details
+        .as_ref()
+        .map(|author| author.display_name.clone())
+        .unwrap_or_default();
+    let comment = |comment: &str| Some(comment.to_string()).filter(|comment| !comment.is_empty());
+    Some(match &message.snippet.details {
+        SnippetDetails::SuperChatEvent { super_chat_details } => PlatformEvent::Donation {
+            platform,
+            user,
+            amount: super_chat_details.amount_display_string.clone(),
+            msg: comment(&super_chat_details.user_comment),
+        },
+        SnippetDetails::SuperStickerEvent { super_sticker_details } => PlatformEvent::Donation {
+            platform,
+            user,
+            amount: super_sticker_details.amount_display_string.clone(),
+            msg: comment(&super_sticker_details.super_sticker_metadata.alt_text),
+        },
+        SnippetDetails::NewSponsorEvent { new_sponsor_details } => PlatformEvent::Subscription {
+            platform,
+            user,
+            months: None,
+            tier: comment(&new_sponsor_details.member_level_name),
+            msg: None,
+        },
+        SnippetDetails::MemberMilestoneChatEvent {
+            member_milestone_chat_details,
+        } => PlatformEvent::Subscription {
+            platform,
+            user,
+            months: Some(member_milestone_chat_details.member_month),
+            tier: comment(&member_milestone_chat_details.member_level_name),
+            msg: comment(&member_milestone_chat_details.user_comment),
+        },
+        SnippetDetails::MembershipGiftingEvent {
+            membership_gifting_details,
+        } => PlatformEvent::GiftedSubscriptions {
+            platform,
+            gifter: user,
+            count: membership_gifting_details.gift_memberships_count,
+            recipients: Vec::new(),
+        },
+        SnippetDetails::MessageDeletedEvent { message_deleted_details } => PlatformEvent::MessageDeleted {
+            platform,
+            platform_id: message_deleted_details.deleted_message_id.clone(),
+        },
+        SnippetDetails::UserBannedEvent { user_banned_details } => {
+            let user_id = user_banned_details.banned_user_details.channel_id.clone();
+            let duration = user_banned_details
+                .ban_duration_seconds
+                .as_ref()
+                .and_then(|seconds| seconds.parse().ok())
+                .filter(|_| user_banned_details.ban_type == "temporary");
+            PlatformEvent::Moderation {
+                platform,
+                // The author of a ban event is the moderator who banned
+                moderator: Some(user),
+                action: match duration {
+                    Some(seconds) => ModerationAction::Timeout {
+                        user_id,
+                        duration: Duration::from_secs(seconds),
+                    },
+                    None => ModerationAction::Ban { user_id },
+                },
+            }
+        }
+        _ => platform_message(message, live_chat_id)?.into(),
+    })
+}
+
+/// `None` for events with nothing to show in the chat.
+fn platform_message(message: &LiveChatMessage, live_chat_id: &str) -> Option<PlatformMessage> {
+    let author = message.author_details.clone().unwrap_or_default();
+    let badges = [
+        (author.is_chat_owner, "broadcaster"),
+        (author.is_chat_moderator, "moderator"),
+        (author.is_chat_sponsor, "member"),
+        (author.is_verified, "verified"),
+    ]
+    .into_iter()
+    .filter(|(has_badge, _)| *has_badge)
+    .map(|(_, name)| Badge::new(name, None))
+    .collect();
+    Some(PlatformMessage {
+        platform_id: Some(message.id.clone()),
+        sent_at: parse_timestamp(&message.snippet.published_at),
+        sender_id: Some(author.channel_id),
+        display_name: Some(author.display_name.clone()),
+        badges,
+        channel: Some(live_chat_id.to_string()),
+        // YouTube only has display names
+        ..PlatformMessage::new(Platform::YOUTUBE, author.display_name, message_text(message)?)
+    })
+}
+
+/// Text shown in the chat for a YouTube event, `None` for the events that have nothing to show.
+fn message_text(msg: &LiveChatMessage) -> Option<String> {
+    match &msg.snippet.details {
+        SnippetDetails::TextMessageEvent { text_message_details } => Some(text_message_details.message_text.clone()),
+        SnippetDetails::SuperChatEvent { super_chat_details } => Some(format!(
+            "[Super Chat {}] {}",
+            super_chat_details.amount_display_string, super_chat_details.user_comment
+        )),
+        SnippetDetails::SuperStickerEvent { super_sticker_details } => Some(format!(
+            "[Super Sticker {}] {}",
+            super_sticker_details.amount_display_string, super_sticker_details.super_sticker_metadata.alt_text
+        )),
+        SnippetDetails::MessageDeletedEvent { .. } | SnippetDetails::Tombstone | SnippetDetails::Unknown => None,
+        _ => msg.snippet.display_message.clone().filter(|_| msg.snippet.has_display_content),
+    }
+}
+
+pub struct YoutubeConfig {
+    pub api_url: String,
+    pub api_key: String,
+    pub live_chat_id: Option<String>,
+}
+
+impl YoutubeConfig {
+    pub fn from_env() -> Option<Self> {
+        Some(Self {
+            api_url: env::var("YOUTUBE_API_URL").unwrap_or(DEFAULT_YOUTUBE_API_URL.to_string()),
+            api_key: env::var("YOUTUBE_TOKEN").ok()?,
+            live_chat_id: env::var("YOUTUBE_LIVE_CHAT_ID").ok(),
+        })
+    }
+}
+
+pub fn connector() -> Option<Box<dyn PlatformConnector>> {
+    Some(Box::new(YoutubeConnector {
+        config: YoutubeConfig::from_env()?,
+        oauth_client: YoutubeClient::from_env(),
+        client: Client::new(),
+        live_chat_id: None,
+        bot_channel_id: None,
+        page_token: None,
+        next_poll: Instant::now(),
+    }))
+}
+
+/// Reads the live chat with the API key. Sending messages needs OAuth, so it is only possible
+/// when `YOUTUBE_OAUTH_TOKEN` is defined too.
+pub struct YoutubeConnector {
+    config: YoutubeConfig,
+    oauth_client: Option<YoutubeClient>,
+    client: Client,
+    live_chat_id: Option<String>,
+    /// Channel of the OAuth token, to recognize the bot's own messages
+    bot_channel_id: Option<String>,
+    page_token: Option<String>,
+    next_poll: Instant,
+}
+
+impl PlatformConnector for YoutubeConnector {
+    fn info(&self) -> PlatformInfo {
+        PlatformInfo {
+            platform: Platform::YOUTUBE,
+            label: "Youtube",
+            color: [255, 0, 0],
+        }
+    }
+
+    fn connect(&mut self) -> Result<()> {
+        // Finding the active broadcast needs OAuth, with only an API key the chat id has to be given.
+        // See https://developers.google.com/youtube/v3/live/docs/liveBroadcasts/list
+        let live_chat_id = match (&self.config.live_chat_id, &self.oauth_client) {
+            (Some(live_chat_id), _) => live_chat_id.clone(),
+            (None, Some(oauth_client)) => oauth_client.live_chat_id()?,
+            (None, None) => return Err(anyhow::Error::msg("YOUTUBE_LIVE_CHAT_ID or YOUTUBE_OAUTH_TOKEN to be defined")),
+        };
+        self.live_chat_id = Some(live_chat_id);
+        if let (None, Some(oauth_client)) = (&self.bot_channel_id, &self.oauth_client) {
+            match oauth_client.channel_id() {
+                Ok(channel_id) => self.bot_channel_id = Some(channel_id),
+                Err(err) => eprintln!(
+                    "WARN - Cannot get the Youtube channel of the bot, its messages may be answered: {:?}",
+                    err
+                ),
             }
+        }
+        Ok(())
+    }
+
+    fn poll(&mut self) -> Result<Vec<IncomingEvent>> {
+        if Instant::now() < self.next_poll {
+            idle();
+            return Ok(Vec::new());
+        }
+        let live_chat_id = self
+            .live_chat_id
+            .as_ref()
+            .ok_or(anyhow::Error::msg("Youtube chat is not connected"))?;
+
+        let mut request = self
+            .client
+            .get(format!("{}/liveChat/messages", self.config.api_url))
+            .query(&[
+                ("liveChatId", live_chat_id.as_str()),
+                ("part", "id,snippet,authorDetails"),
+                ("key", self.config.api_key.as_str()),
+            ])
+            .header(ACCEPT, "application/json");
+        if let Some(page_token) = &self.page_token {
+            request = request.query(&[("pageToken", page_token)]);
+        }
+
+        self.next_poll = Instant::now() + Duration::from_secs(5);
+        let response = request.send()?.error_for_status()?.json::<YoutubeResponse>()?;
+        // TODO: Persist this somewhere to not reload old messages on restart
+        if response.next_page_token.is_some() {
+            self.page_token = response.next_page_token.clone();
+        }
+        if let Some(millis) = response.polling_interval_millis {
+            self.next_poll = Instant::now() + Duration::from_millis(millis);
+        }
+        if let Some(offline_at) = response.offline_at {
+            return Err(anyhow::Error::msg(format!("Youtube chat went offline at {offline_at}")));
+        }
+
+        Ok(response
+            .items
+            .iter()
+            .filter_map(|msg| platform_event(msg, live_chat_id))
+            .map(|event| match event {
+                PlatformEvent::Chat(mut message) => {
+                    message.is_bot = self.bot_channel_id.is_some() && message.sender_id == self.bot_channel_id;
+                    IncomingEvent::new(PlatformEvent::Chat(message), None)
+                }
+                event => IncomingEvent::new(event, None),
+            })
+            .collect())
+    }
+
+    fn send_message(&mut self, _channel: Option<&str>, msg: &str) -> Result<()> {
+        self.oauth_client
+            .as_ref()
+            .ok_or(anyhow::Error::msg("YOUTUBE_OAUTH_TOKEN to be defined to send messages"))?
+            .send_message(msg)
+    }
+
+    fn live_since(&self) -> Option<DateTime<Utc>> {
+        match self.oauth_client.as_ref()?.live_since() {
+            Ok(live_since) => live_since,
             Err(err) => {
-                eprintln!("Error sending request: {:?}", err);
+                eprintln!("WARN - Cannot get the Youtube broadcast: {:?}", err);
+                None
             }
         }
+    }
+
+    fn moderator(&self) -> Option<Box<dyn Moderator>> {
+        Some(Box::new(YoutubeModerator::from_env()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::fake_http::{ok, serve};
+
+    fn text(id: &str, channel_id: &str, name: &str, text: &str) -> Value {
+        json!({
+            "id": id,
+            "snippet": {
+                "type": "textMessageEvent",
+                "publishedAt": "2024-01-01T00:00:00Z",
+                "hasDisplayContent": true,
+                "displayMessage": text,
+                "textMessageDetails": { "messageText": text }
+            },
+            "authorDetails": { "channelId": channel_id, "displayName": name, "isChatModerator": channel_id == "UCbot" }
+        })
+    }
+
+    fn connector(api_url: &str) -> YoutubeConnector {
+        YoutubeConnector {
+            config: YoutubeConfig {
+                api_url: api_url.to_string(),
+                api_key: "key".to_string(),
+                live_chat_id: Some("chat1".to_string()),
+            },
+            oauth_client: Some(YoutubeClient::new(
+                api_url.to_string(),
+                "oauth".to_string(),
+                Some("chat1".to_string()),
+            )),
+            client: Client::new(),
+            live_chat_id: None,
+            bot_channel_id: None,
+            page_token: None,
+            next_poll: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn polls_the_chat_and_marks_own_messages() {
+        let (url, requests, server) = serve(vec![
+            ok(json!({ "items": [{ "id": "UCbot" }] })),
+            ok(json!({
+                "nextPageToken": "page2",
+                "pollingIntervalMillis": 0,
+                "items": [text("m1", "UCbot", "Bot", "Join the discord"), text("m2", "UCviewer", "Viewer", "!discord")]
+            })),
+            ok(json!({ "items": [] })),
+        ]);
+        let mut connector = connector(&url);
+        connector.connect().unwrap();
+        assert_eq!(requests.recv().unwrap(), "GET /channels?part=id&mine=true HTTP/1.1");
+
+        let messages: Vec<(String, bool)> = connector
+            .poll()
+            .unwrap()
+            .into_iter()
+            .map(|incoming| match incoming.event {
+                PlatformEvent::Chat(message) => (message.msg, message.is_bot),
+                event => panic!("Expected chat, got {event:?}"),
+            })
+            .collect();
+        assert_eq!(messages, [("Join the discord".to_string(), true), ("!discord".to_string(), false)]);
+        assert!(requests.recv().unwrap().contains("liveChatId=chat1"));
+
+        assert!(connector.poll().unwrap().is_empty());
+        assert!(requests.recv().unwrap().contains("pageToken=page2"));
+        server.join().unwrap();
+    }
 
-        thread::sleep(Duration::from_secs(5));
+    #[test]
+    fn live_since_is_when_the_broadcast_started() {
+        let (url, _requests, server) = serve(vec![
+            ok(json!({ "items": [{ "snippet": { "actualStartTime": "2024-01-01T18:00:00Z", "liveChatId": "chat1" } }] })),
+            ok(json!({ "items": [] })),
+        ]);
+        let connector = connector(&url);
+        assert_eq!(connector.live_since(), parse_timestamp("2024-01-01T18:00:00Z"));
+        assert_eq!(connector.live_since(), None);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn only_bans_of_this_session_can_be_lifted() {
+        let ban = json!({
+            "id": "ban1",
+            "snippet": { "liveChatId": "chat1", "type": "permanent", "bannedUserDetails": { "channelId": "UCviewer" } }
+        });
+        let (url, requests, server) = serve(vec![ok(ban), ok(json!({}))]);
+        let moderator = YoutubeModerator {
+            client: YoutubeClient::new(url, "oauth".to_string(), Some("chat1".to_string())),
+            bans: Mutex::new(HashMap::new()),
+        };
+        let unban = ModerationAction::Unban {
+            user_id: "UCviewer".to_string(),
+        };
+        assert!(!moderator.can_unban("UCviewer"));
+        assert!(moderator.apply(&unban).is_err());
+
+        moderator
+            .apply(&ModerationAction::Ban {
+                user_id: "UCviewer".to_string(),
+            })
+            .unwrap();
+        assert!(requests.recv().unwrap().starts_with("POST /liveChat/bans"));
+        assert!(moderator.can_unban("UCviewer"));
+        moderator.apply(&unban).unwrap();
+        assert_eq!(requests.recv().unwrap(), "DELETE /liveChat/bans?id=ban1 HTTP/1.1");
+        assert!(!moderator.can_unban("UCviewer"));
+        server.join().unwrap();
     }
-    */
 }
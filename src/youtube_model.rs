@@ -1,55 +1,220 @@
-use serde::Deserialize;
-use serde::Serialize;
+// These types mirror the YouTube Data API resources, so not every field is read by the bot.
+#![allow(dead_code)]
 
+use serde::{
+    de::{self, DeserializeOwned},
+    Deserialize, Deserializer, Serialize,
+};
+use serde_json::{from_value, Map, Value};
+
+/// Deserializes a list skipping (and reporting) the items that don't fit the model, so a single
+/// unexpected item doesn't cost the whole batch.
+fn skip_invalid<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    let values = Vec::<Value>::deserialize(deserializer)?;
+    Ok(values
+        .into_iter()
+        .filter_map(|value| match from_value(value) {
+            Ok(item) => Some(item),
+            Err(err) => {
+                eprintln!("WARN - Skipping item that cannot be parsed: {:?}", err);
+                None
+            }
+        })
+        .collect())
+}
+
+/// `liveChatMessages.list` response. `nextPageToken` and `pollingIntervalMillis` are missing on
+/// the last page and `offlineAt` is only there once the stream is over.
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct YoutubeResponse {
     pub kind: String,
     pub etag: String,
-    pub polling_interval_millis: i64,
-    pub page_info: PageInfo,
-    pub next_page_token: String,
+    pub polling_interval_millis: Option<u64>,
+    pub offline_at: Option<String>,
+    pub page_info: Option<PageInfo>,
+    pub next_page_token: Option<String>,
+    #[serde(deserialize_with = "skip_invalid")]
     pub items: Vec<LiveChatMessage>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct PageInfo {
     pub total_results: i64,
     pub results_per_page: i64,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct LiveChatMessage {
     pub kind: String,
     pub etag: String,
     pub id: String,
     pub snippet: Snippet,
-    pub author_details: AuthorDetails,
+    /// Absent for events without an author, like `chatEndedEvent`
+    pub author_details: Option<AuthorDetails>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct Snippet {
-    #[serde(rename = "type")]
-    pub type_field: String,
     pub live_chat_id: String,
     pub author_channel_id: String,
     pub published_at: String,
     pub has_display_content: bool,
-    pub display_message: String,
-    pub text_message_details: TextMessageDetails,
+    pub display_message: Option<String>,
+    #[serde(flatten, deserialize_with = "details_or_unknown")]
+    pub details: SnippetDetails,
+}
+
+/// A snippet without a `type` is `Unknown` too, rather than costing the whole message.
+fn details_or_unknown<'de, D>(deserializer: D) -> Result<SnippetDetails, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let fields = Map::<String, Value>::deserialize(deserializer)?;
+    if !fields.contains_key("type") {
+        return Ok(SnippetDetails::Unknown);
+    }
+    from_value(Value::Object(fields)).map_err(de::Error::custom)
 }
 
+/// The event carried by a `Snippet`, selected by its `type` field. Each type comes with its own
+/// `<type>Details` object. Types we don't know about end up in `Unknown` instead of failing.
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(tag = "type", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum SnippetDetails {
+    TextMessageEvent {
+        #[serde(default)]
+        text_message_details: TextMessageDetails,
+    },
+    SuperChatEvent {
+        #[serde(default)]
+        super_chat_details: SuperChatDetails,
+    },
+    SuperStickerEvent {
+        #[serde(default)]
+        super_sticker_details: SuperStickerDetails,
+    },
+    MessageDeletedEvent {
+        #[serde(default)]
+        message_deleted_details: MessageDeletedDetails,
+    },
+    UserBannedEvent {
+        #[serde(default)]
+        user_banned_details: UserBannedDetails,
+    },
+    MemberMilestoneChatEvent {
+        #[serde(default)]
+        member_milestone_chat_details: MemberMilestoneChatDetails,
+    },
+    NewSponsorEvent {
+        #[serde(default)]
+        new_sponsor_details: NewSponsorDetails,
+    },
+    MembershipGiftingEvent {
+        #[serde(default)]
+        membership_gifting_details: MembershipGiftingDetails,
+    },
+    GiftMembershipReceivedEvent {
+        #[serde(default)]
+        gift_membership_received_details: GiftMembershipReceivedDetails,
+    },
+    SponsorOnlyModeStartedEvent,
+    SponsorOnlyModeEndedEvent,
+    ChatEndedEvent,
+    Tombstone,
+    #[default]
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
 pub struct TextMessageDetails {
     pub message_text: String,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
+pub struct SuperChatDetails {
+    /// The API encodes this unsigned long as a string
+    pub amount_micros: String,
+    pub currency: String,
+    pub amount_display_string: String,
+    pub user_comment: String,
+    pub tier: u32,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SuperStickerDetails {
+    pub super_sticker_metadata: SuperStickerMetadata,
+    pub amount_micros: String,
+    pub currency: String,
+    pub amount_display_string: String,
+    pub tier: u32,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SuperStickerMetadata {
+    pub sticker_id: String,
+    pub alt_text: String,
+    pub language: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MessageDeletedDetails {
+    pub deleted_message_id: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct UserBannedDetails {
+    pub banned_user_details: UserDetails,
+    pub ban_type: String,
+    pub ban_duration_seconds: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MemberMilestoneChatDetails {
+    pub user_comment: String,
+    pub member_month: u32,
+    pub member_level_name: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NewSponsorDetails {
+    pub member_level_name: String,
+    pub is_upgrade: bool,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MembershipGiftingDetails {
+    pub gift_memberships_count: u32,
+    pub gift_memberships_level_name: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GiftMembershipReceivedDetails {
+    pub member_level_name: String,
+    pub gifter_channel_id: String,
+    pub associated_membership_gifting_message_id: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
 pub struct AuthorDetails {
     pub channel_id: String,
     pub channel_url: String,
@@ -61,17 +226,36 @@ pub struct AuthorDetails {
     pub is_chat_moderator: bool,
 }
 
+/// `channels.list` response, only the ids are requested.
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
+pub struct ChannelListResponse {
+    pub kind: String,
+    pub etag: String,
+    #[serde(deserialize_with = "skip_invalid")]
+    pub items: Vec<Channel>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Channel {
+    pub kind: String,
+    pub etag: String,
+    pub id: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
 pub struct LiveStreamsResponse {
     pub kind: String,
     pub etag: String,
-    pub page_info: PageInfo,
+    pub page_info: Option<PageInfo>,
+    #[serde(deserialize_with = "skip_invalid")]
     pub items: Vec<LiveStream>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct LiveStream {
     pub kind: String,
     pub etag: String,
@@ -82,21 +266,21 @@ pub struct LiveStream {
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct LiveStreamSnippet {
     pub published_at: String,
     pub channel_id: String,
     pub title: String,
     pub description: String,
     pub thumbnails: Thumbnails,
-    pub actual_start_time: String,
+    pub actual_start_time: Option<String>,
     pub is_default_broadcast: bool,
     pub live_chat_id: String,
     pub actual_end_time: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct Thumbnails {
     pub default: Default,
     pub medium: Medium,
@@ -106,7 +290,7 @@ pub struct Thumbnails {
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct Default {
     pub url: String,
     pub width: i64,
@@ -114,7 +298,7 @@ pub struct Default {
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct Medium {
     pub url: String,
     pub width: i64,
@@ -122,7 +306,7 @@ pub struct Medium {
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct High {
     pub url: String,
     pub width: i64,
@@ -130,7 +314,7 @@ pub struct High {
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct Standard {
     pub url: String,
     pub width: i64,
@@ -138,7 +322,7 @@ pub struct Standard {
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct Maxres {
     pub url: String,
     pub width: i64,
@@ -146,7 +330,7 @@ pub struct Maxres {
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct Status {
     pub life_cycle_status: String,
     pub privacy_status: String,
@@ -156,7 +340,7 @@ pub struct Status {
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct ContentDetails {
     pub bound_stream_id: String,
     pub bound_stream_last_update_time_ms: String,
@@ -176,9 +360,272 @@ pub struct ContentDetails {
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct MonitorStream {
     pub enable_monitor_stream: bool,
     pub broadcast_stream_delay_ms: i64,
     pub embed_html: String,
 }
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveChatBan {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub etag: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+    pub snippet: LiveChatBanSnippet,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveChatBanSnippet {
+    pub live_chat_id: String,
+    /// Either `permanent` or `temporary`
+    #[serde(rename = "type")]
+    pub type_field: String,
+    /// Only present for `temporary` bans. The API encodes this unsigned long as a string.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ban_duration_seconds: Option<String>,
+    pub banned_user_details: UserDetails,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserDetails {
+    pub channel_id: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub channel_url: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub display_name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub profile_image_url: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveChatModerator {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub etag: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+    pub snippet: LiveChatModeratorSnippet,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveChatModeratorSnippet {
+    pub live_chat_id: String,
+    pub moderator_details: UserDetails,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveChatModeratorListResponse {
+    pub kind: String,
+    pub etag: String,
+    pub next_page_token: Option<String>,
+    pub items: Vec<LiveChatModerator>,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn details(snippet: Value) -> SnippetDetails {
+        from_value::<Snippet>(snippet).unwrap().details
+    }
+
+    #[test]
+    fn parses_every_snippet_type() {
+        let cases = [
+            (
+                json!({"type": "textMessageEvent", "textMessageDetails": {"messageText": "hi"}}),
+                SnippetDetails::TextMessageEvent {
+                    text_message_details: TextMessageDetails {
+                        message_text: "hi".to_string(),
+                    },
+                },
+            ),
+            (
+                json!({"type": "superChatEvent", "superChatDetails": {
+                    "amountMicros": "5000000", "currency": "USD", "amountDisplayString": "$5.00",
+                    "userComment": "gg", "tier": 2
+                }}),
+                SnippetDetails::SuperChatEvent {
+                    super_chat_details: SuperChatDetails {
+                        amount_micros: "5000000".to_string(),
+                        currency: "USD".to_string(),
+                        amount_display_string: "$5.00".to_string(),
+                        user_comment: "gg".to_string(),
+                        tier: 2,
+                    },
+                },
+            ),
+            (
+                json!({"type": "superStickerEvent", "superStickerDetails": {
+                    "superStickerMetadata": {"stickerId": "s1", "altText": "cat", "language": "en"},
+                    "amountMicros": "1000000", "currency": "EUR", "amountDisplayString": "€1.00", "tier": 1
+                }}),
+                SnippetDetails::SuperStickerEvent {
+                    super_sticker_details: SuperStickerDetails {
+                        super_sticker_metadata: SuperStickerMetadata {
+                            sticker_id: "s1".to_string(),
+                            alt_text: "cat".to_string(),
+                            language: "en".to_string(),
+                        },
+                        amount_micros: "1000000".to_string(),
+                        currency: "EUR".to_string(),
+                        amount_display_string: "€1.00".to_string(),
+                        tier: 1,
+                    },
+                },
+            ),
+            (
+                json!({"type": "messageDeletedEvent", "messageDeletedDetails": {"deletedMessageId": "m1"}}),
+                SnippetDetails::MessageDeletedEvent {
+                    message_deleted_details: MessageDeletedDetails {
+                        deleted_message_id: "m1".to_string(),
+                    },
+                },
+            ),
+            (
+                json!({"type": "userBannedEvent", "userBannedDetails": {
+                    "bannedUserDetails": {"channelId": "UC1", "displayName": "troll"},
+                    "banType": "temporary", "banDurationSeconds": "300"
+                }}),
+                SnippetDetails::UserBannedEvent {
+                    user_banned_details: UserBannedDetails {
+                        banned_user_details: UserDetails {
+                            channel_id: "UC1".to_string(),
+                            display_name: "troll".to_string(),
+                            ..UserDetails::default()
+                        },
+                        ban_type: "temporary".to_string(),
+                        ban_duration_seconds: Some("300".to_string()),
+                    },
+                },
+            ),
+            (
+                json!({"type": "memberMilestoneChatEvent", "memberMilestoneChatDetails": {
+                    "userComment": "a year!", "memberMonth": 12, "memberLevelName": "Gold"
+                }}),
+                SnippetDetails::MemberMilestoneChatEvent {
+                    member_milestone_chat_details: MemberMilestoneChatDetails {
+                        user_comment: "a year!".to_string(),
+                        member_month: 12,
+                        member_level_name: "Gold".to_string(),
+                    },
+                },
+            ),
+            (
+                json!({"type": "newSponsorEvent", "newSponsorDetails": {"memberLevelName": "Gold", "isUpgrade": true}}),
+                SnippetDetails::NewSponsorEvent {
+                    new_sponsor_details: NewSponsorDetails {
+                        member_level_name: "Gold".to_string(),
+                        is_upgrade: true,
+                    },
+                },
+            ),
+            (
+                json!({"type": "membershipGiftingEvent", "membershipGiftingDetails": {
+                    "giftMembershipsCount": 5, "giftMembershipsLevelName": "Gold"
+                }}),
+                SnippetDetails::MembershipGiftingEvent {
+                    membership_gifting_details: MembershipGiftingDetails {
+                        gift_memberships_count: 5,
+                        gift_memberships_level_name: "Gold".to_string(),
+                    },
+                },
+            ),
+            (
+                json!({"type": "giftMembershipReceivedEvent", "giftMembershipReceivedDetails": {
+                    "memberLevelName": "Gold", "gifterChannelId": "UC2",
+                    "associatedMembershipGiftingMessageId": "m2"
+                }}),
+                SnippetDetails::GiftMembershipReceivedEvent {
+                    gift_membership_received_details: GiftMembershipReceivedDetails {
+                        member_level_name: "Gold".to_string(),
+                        gifter_channel_id: "UC2".to_string(),
+                        associated_membership_gifting_message_id: "m2".to_string(),
+                    },
+                },
+            ),
+            (
+                json!({"type": "sponsorOnlyModeStartedEvent"}),
+                SnippetDetails::SponsorOnlyModeStartedEvent,
+            ),
+            (
+                json!({"type": "sponsorOnlyModeEndedEvent"}),
+                SnippetDetails::SponsorOnlyModeEndedEvent,
+            ),
+            (json!({"type": "chatEndedEvent"}), SnippetDetails::ChatEndedEvent),
+            (json!({"type": "tombstone"}), SnippetDetails::Tombstone),
+        ];
+        for (snippet, expected) in cases {
+            assert_eq!(details(snippet), expected);
+        }
+    }
+
+    #[test]
+    fn details_missing_from_a_known_type_are_defaulted() {
+        assert_eq!(
+            details(json!({"type": "textMessageEvent"})),
+            SnippetDetails::TextMessageEvent {
+                text_message_details: TextMessageDetails::default()
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_types_are_kept_as_unknown() {
+        assert_eq!(
+            details(json!({"type": "pollEvent", "pollDetails": {"question": "?"}})),
+            SnippetDetails::Unknown
+        );
+    }
+
+    #[test]
+    fn snippets_without_a_type_are_unknown() {
+        let snippet: Snippet = from_value(json!({"liveChatId": "chat", "publishedAt": "2024-01-01T00:00:00Z"})).unwrap();
+        assert_eq!(snippet.live_chat_id, "chat");
+        assert_eq!(snippet.details, SnippetDetails::Unknown);
+    }
+
+    #[test]
+    fn last_page_has_no_next_page_token() {
+        let response: YoutubeResponse = from_value(json!({
+            "kind": "youtube#liveChatMessageListResponse",
+            "offlineAt": "2024-01-01T01:00:00Z",
+            "items": []
+        }))
+        .unwrap();
+        assert_eq!(response.next_page_token, None);
+        assert_eq!(response.polling_interval_millis, None);
+        assert_eq!(response.offline_at.as_deref(), Some("2024-01-01T01:00:00Z"));
+    }
+
+    #[test]
+    fn invalid_items_are_skipped() {
+        let response: YoutubeResponse = from_value(json!({
+            "nextPageToken": "next",
+            "pollingIntervalMillis": 2000,
+            "items": [
+                {"id": "1", "snippet": {"type": "textMessageEvent", "textMessageDetails": {"messageText": "one"}}},
+                {"id": "2", "snippet": "not an object"},
+                {"id": "3", "snippet": {"type": "chatEndedEvent"}}
+            ]
+        }))
+        .unwrap();
+        assert_eq!(response.next_page_token.as_deref(), Some("next"));
+        assert_eq!(response.polling_interval_millis, Some(2000));
+        let ids: Vec<&str> = response.items.iter().map(|item| item.id.as_str()).collect();
+        assert_eq!(ids, ["1", "3"]);
+    }
+}